@@ -0,0 +1,354 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    sync::{Arc, Condvar, Mutex, Weak},
+    thread,
+    time::Duration,
+};
+
+use crate::acid::log_record::TxnId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockMode {
+    IntentionShared,
+    IntentionExclusive,
+    Shared,
+    SharedIntentionExclusive,
+    Exclusive,
+}
+
+impl LockMode {
+    // Can two transactions hold these modes on the same resource?
+    //       IS  IX  S   SIX X
+    // IS    y   y   y   y   n
+    // IX    y   y   n   n   n
+    // S     y   n   y   n   n
+    // SIX   y   n   n   n   n
+    // X     n   n   n   n   n
+    pub fn compatible(self, other: LockMode) -> bool {
+        use LockMode::*;
+        match (self, other) {
+            (Exclusive, _) | (_, Exclusive) => false,
+            (IntentionShared, _) | (_, IntentionShared) => true,
+            (IntentionExclusive, IntentionExclusive) | (Shared, Shared) => true,
+            _ => false,
+        }
+    }
+
+    // Does holding this mode give everything `other` gives?
+    pub fn covers(self, other: LockMode) -> bool {
+        use LockMode::*;
+        match (self, other) {
+            (a, b) if a == b => true,
+            (Exclusive, _) => true,
+            (SharedIntentionExclusive, Shared | IntentionExclusive | IntentionShared) => true,
+            (Shared | IntentionExclusive, IntentionShared) => true,
+            _ => false,
+        }
+    }
+
+    // The weakest mode covering both, what an upgrade asks for
+    fn combine(self, other: LockMode) -> LockMode {
+        use LockMode::*;
+        match (self, other) {
+            (a, b) if a.covers(b) => a,
+            (a, b) if b.covers(a) => b,
+            // S + IX, S + SIX, IX + SIX
+            (Shared | IntentionExclusive, Shared | IntentionExclusive) => SharedIntentionExclusive,
+            _ => Exclusive,
+        }
+    }
+}
+
+impl fmt::Display for LockMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LockMode::IntentionShared => "IS",
+            LockMode::IntentionExclusive => "IX",
+            LockMode::Shared => "S",
+            LockMode::SharedIntentionExclusive => "SIX",
+            LockMode::Exclusive => "X",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// What a lock protects
+// Tables are named by their first page, tuples by their table and RID
+// Writers hold the database in IX, schema changes and checkpoints hold it in X
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Resource {
+    Database,
+    Table(usize),
+    Tuple(usize, (usize, u16)),
+}
+
+#[derive(Debug)]
+struct LockRequest {
+    txn: TxnId,
+    mode: LockMode,
+    granted: bool,
+    // A granted request waiting to become this stronger mode
+    upgrade: Option<LockMode>,
+}
+
+impl LockRequest {
+    fn wanted(&self) -> LockMode {
+        self.upgrade.unwrap_or(self.mode)
+    }
+
+    fn waiting(&self) -> bool {
+        !self.granted || self.upgrade.is_some()
+    }
+}
+
+#[derive(Debug, Default)]
+struct LockTable {
+    // Granted and waiting requests of each resource, oldest first
+    queues: HashMap<Resource, Vec<LockRequest>>,
+    // Resources each transaction holds or waits for
+    held: HashMap<TxnId, HashSet<Resource>>,
+    // Transactions the deadlock detector aborted, their waits fail
+    victims: HashSet<TxnId>,
+}
+
+// Tuple and table locks for strict two-phase locking
+// A transaction takes locks as it goes and releases all of them when it ends.
+// A request waits in its resource's queue until it is compatible with the granted ones
+// and with the requests queued before it.
+#[derive(Debug)]
+pub struct LockManager {
+    table: Mutex<LockTable>,
+    // Signaled whenever locks are released or a victim is chosen
+    changed: Condvar,
+}
+
+impl LockManager {
+    pub fn new() -> Self {
+        Self {
+            table: Mutex::new(LockTable::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    // Run the deadlock detector every `interval` until the lock manager is dropped
+    pub fn start_deadlock_detector(self: &Arc<Self>, interval: Duration) {
+        let manager: Weak<Self> = Arc::downgrade(self);
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                match manager.upgrade() {
+                    Some(manager) => manager.detect_deadlocks(),
+                    None => return,
+                }
+            }
+        });
+    }
+
+    // Block until the transaction holds the resource in (at least) this mode
+    // Fails if the transaction is chosen to break a deadlock meanwhile.
+    pub fn lock(&self, txn: TxnId, resource: Resource, mode: LockMode) -> Result<(), String> {
+        let mut table = self.table.lock().unwrap();
+        let queue = table.queues.entry(resource).or_default();
+
+        // Already held: nothing to do, or an upgrade that keeps the weaker lock meanwhile
+        match queue.iter_mut().find(|r| r.txn == txn) {
+            Some(request) if request.mode.covers(mode) => return Ok(()),
+            Some(request) => request.upgrade = Some(request.mode.combine(mode)),
+            None => queue.push(LockRequest {
+                txn,
+                mode,
+                granted: false,
+                upgrade: None,
+            }),
+        }
+        table.held.entry(txn).or_default().insert(resource);
+
+        loop {
+            if table.victims.contains(&txn) {
+                table.withdraw(txn, resource);
+                self.changed.notify_all();
+                return Err(format!(
+                    "Transaction {} was aborted to break a deadlock",
+                    txn
+                ));
+            }
+
+            if table.try_grant(txn, resource) {
+                return Ok(());
+            }
+            table = self.changed.wait(table).unwrap();
+        }
+    }
+
+    // Release everything the transaction holds or waits for, when it ends
+    pub fn release_all(&self, txn: TxnId) {
+        let mut table = self.table.lock().unwrap();
+        for resource in table.held.remove(&txn).unwrap_or_default() {
+            if let Some(queue) = table.queues.get_mut(&resource) {
+                queue.retain(|r| r.txn != txn);
+                if queue.is_empty() {
+                    table.queues.remove(&resource);
+                }
+            }
+        }
+        table.victims.remove(&txn);
+        self.changed.notify_all();
+    }
+
+    // The mode a transaction holds a resource in, if granted
+    pub fn held_mode(&self, txn: TxnId, resource: Resource) -> Option<LockMode> {
+        let table = self.table.lock().unwrap();
+        table
+            .queues
+            .get(&resource)?
+            .iter()
+            .find(|r| r.txn == txn && r.granted)
+            .map(|r| r.mode)
+    }
+
+    pub fn is_victim(&self, txn: TxnId) -> bool {
+        self.table.lock().unwrap().victims.contains(&txn)
+    }
+
+    // Edges of the waits-for graph: waiting transaction -> transactions it waits for
+    pub fn waits_for(&self) -> BTreeMap<TxnId, BTreeSet<TxnId>> {
+        self.table.lock().unwrap().waits_for()
+    }
+
+    // Abort the youngest transaction of every cycle in the waits-for graph
+    pub fn detect_deadlocks(&self) {
+        let mut table = self.table.lock().unwrap();
+        let mut found = false;
+
+        loop {
+            let mut graph = table.waits_for();
+            // Victims are leaving, their edges no longer count
+            graph.retain(|txn, _| !table.victims.contains(txn));
+            for targets in graph.values_mut() {
+                targets.retain(|txn| !table.victims.contains(txn));
+            }
+
+            let Some(cycle) = find_cycle(&graph) else {
+                break;
+            };
+            // Transaction IDs grow over time: the youngest has the largest
+            let victim = cycle.into_iter().max().unwrap();
+            table.victims.insert(victim);
+            found = true;
+        }
+
+        if found {
+            self.changed.notify_all();
+        }
+    }
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LockTable {
+    fn try_grant(&mut self, txn: TxnId, resource: Resource) -> bool {
+        let queue = self.queues.get_mut(&resource).unwrap();
+        let index = queue.iter().position(|r| r.txn == txn).unwrap();
+        if blockers(queue, index).next().is_some() {
+            return false;
+        }
+
+        let request = &mut queue[index];
+        request.mode = request.wanted();
+        request.granted = true;
+        request.upgrade = None;
+        true
+    }
+
+    // Give up a waiting request, a failed upgrade keeps the lock held before
+    fn withdraw(&mut self, txn: TxnId, resource: Resource) {
+        let queue = self.queues.get_mut(&resource).unwrap();
+        let index = queue.iter().position(|r| r.txn == txn).unwrap();
+        if queue[index].granted {
+            queue[index].upgrade = None;
+            return;
+        }
+
+        queue.remove(index);
+        if queue.is_empty() {
+            self.queues.remove(&resource);
+        }
+        if let Some(resources) = self.held.get_mut(&txn) {
+            resources.remove(&resource);
+        }
+    }
+
+    fn waits_for(&self) -> BTreeMap<TxnId, BTreeSet<TxnId>> {
+        let mut graph: BTreeMap<TxnId, BTreeSet<TxnId>> = BTreeMap::new();
+        for queue in self.queues.values() {
+            for (index, request) in queue.iter().enumerate() {
+                if request.waiting() {
+                    graph
+                        .entry(request.txn)
+                        .or_default()
+                        .extend(blockers(queue, index));
+                }
+            }
+        }
+        graph
+    }
+}
+
+// Transactions the request at `index` waits for: the granted requests it conflicts with,
+// and for a new request also the conflicting ones queued before it, so writers are not starved.
+// An upgrade only waits for granted requests, it already holds its place.
+fn blockers(queue: &[LockRequest], index: usize) -> impl Iterator<Item = TxnId> + '_ {
+    let request = &queue[index];
+    let wanted = request.wanted();
+    queue
+        .iter()
+        .enumerate()
+        .filter(move |&(i, other)| {
+            other.txn != request.txn
+                && ((other.granted && !other.mode.compatible(wanted))
+                    || (!request.granted && i < index && !other.wanted().compatible(wanted)))
+        })
+        .map(|(_, other)| other.txn)
+}
+
+// Some cycle of the graph, searched from the smallest transaction ID so the result is stable
+fn find_cycle(graph: &BTreeMap<TxnId, BTreeSet<TxnId>>) -> Option<Vec<TxnId>> {
+    let mut done = HashSet::new();
+    for &start in graph.keys() {
+        let mut path = Vec::new();
+        if let Some(cycle) = visit(graph, start, &mut path, &mut done) {
+            return Some(cycle);
+        }
+    }
+    None
+}
+
+// Depth-first search, `path` holds the transactions on the way to `txn`
+fn visit(
+    graph: &BTreeMap<TxnId, BTreeSet<TxnId>>,
+    txn: TxnId,
+    path: &mut Vec<TxnId>,
+    done: &mut HashSet<TxnId>,
+) -> Option<Vec<TxnId>> {
+    if let Some(start) = path.iter().position(|&t| t == txn) {
+        return Some(path[start..].to_vec());
+    }
+    if done.contains(&txn) {
+        return None;
+    }
+
+    path.push(txn);
+    for &next in graph.get(&txn).into_iter().flatten() {
+        if let Some(cycle) = visit(graph, next, path, done) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    done.insert(txn);
+    None
+}
@@ -0,0 +1,456 @@
+use std::io;
+
+use crate::indexing::table_page::{VERSION_SIZE, Version};
+
+// Log Sequence Number: where a record starts in the log, counted in bytes
+// since the log was created, so it keeps growing when the log file is truncated
+pub type Lsn = u64;
+
+// 0 = no record (a page never logged, the first record of a transaction)
+pub const NO_LSN: Lsn = 0;
+
+pub type TxnId = u64;
+
+// Changes made outside any transaction (e.g. setting up a new database file)
+pub const SYSTEM_TXN: TxnId = 0;
+
+// Record layout
+// Bytes 0-3: Length of the whole record
+// Bytes 4-11: LSN
+// Bytes 12-19: Transaction ID
+// Bytes 20-27: LSN of the previous record of the same transaction (0 = none)
+// Byte 28: Kind
+// Bytes 29-..: Body
+// Last 4 bytes: Checksum of everything before it
+const OFFSET_LSN: usize = 4;
+const OFFSET_TXN: usize = 12;
+const OFFSET_PREV_LSN: usize = 20;
+const OFFSET_KIND: usize = 28;
+const HEADER_SIZE: usize = 29;
+const CHECKSUM_SIZE: usize = 4;
+
+const KIND_BEGIN: u8 = 1;
+const KIND_COMMIT: u8 = 2;
+const KIND_ABORT: u8 = 3;
+const KIND_UPDATE: u8 = 4;
+const KIND_CLR: u8 = 5;
+const KIND_END: u8 = 6;
+const KIND_OPERATION: u8 = 7;
+
+const UNDO_NOTHING: u8 = 0;
+const UNDO_FREE_PAGE: u8 = 1;
+const UNDO_REMOVE_TUPLE: u8 = 2;
+const UNDO_EMPTY_SLOT: u8 = 3;
+const UNDO_RESTORE_TUPLE: u8 = 4;
+const UNDO_SET_VERSION: u8 = 5;
+const UNDO_TREE_DELETE: u8 = 6;
+const UNDO_TREE_INSERT: u8 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub lsn: Lsn,
+    pub txn: TxnId,
+    pub prev_lsn: Lsn,
+    pub body: LogBody,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogBody {
+    Begin,
+    Commit,
+    // Rollback started: CLRs follow, then End
+    Abort,
+    // The transaction is finished, recovery can forget it
+    End,
+    // Bytes [offset, offset + len) of a page changed from `before` to `after`
+    // Redo writes `after`, undo writes `before`.
+    // Body: Page ID(4) + Offset(2) + Length(2) + Before + After
+    Update {
+        page_id: u32,
+        offset: u16,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+    // Compensation log record: undoing an update wrote `after` at `offset`
+    // Redone but never undone; `undo_next` is the next record of the transaction to undo.
+    // Body: Page ID(4) + Offset(2) + Length(2) + Undo next(8) + After
+    Clr {
+        page_id: u32,
+        offset: u16,
+        after: Vec<u8>,
+        undo_next: Lsn,
+    },
+    // An operation of the transaction (an insert into a heap, a B+ tree delete...) is complete
+    // Its page changes are the records since `undo_next`: they are redone, never undone
+    // one by one, since other transactions may have changed the same pages since.
+    // Rollback runs `undo` instead, then goes on at `undo_next`.
+    // Body: Undo next(8) + Undo
+    Operation {
+        undo: Undo,
+        undo_next: Lsn,
+    },
+}
+
+// How rollback takes back a complete operation, with operations of its own
+#[derive(Debug, Clone, PartialEq)]
+pub enum Undo {
+    // The operation undid an earlier one, or only freed pages nobody uses anymore
+    Nothing,
+    // A page was allocated: give it back to the free list
+    FreePage(u32),
+    // Tuples are in the table heap whose first page is `table`
+    // A tuple was inserted: remove it, with its overflow pages
+    RemoveTuple {
+        table: u32,
+        page_id: u32,
+        slot_id: u16,
+    },
+    // A copy of a tuple was put in a slot of its own: empty the slot
+    // Its overflow pages belong to the operation that stored it.
+    EmptySlot {
+        table: u32,
+        page_id: u32,
+        slot_id: u16,
+    },
+    // A tuple was replaced: store this one again
+    RestoreTuple {
+        table: u32,
+        page_id: u32,
+        slot_id: u16,
+        version: Version,
+        tuple: Vec<u8>,
+    },
+    // Only the version header of a tuple changed: put this one back
+    SetVersion {
+        table: u32,
+        page_id: u32,
+        slot_id: u16,
+        version: Version,
+    },
+    // An entry was added to the B+ tree with this root: delete it
+    TreeDelete {
+        root: u32,
+        key: Vec<u8>,
+    },
+    // An entry was deleted from it: insert it again
+    TreeInsert {
+        root: u32,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+}
+
+// How an operation ends in the log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationEnd {
+    // A new operation, started after this record: rollback runs its undo
+    Started(Lsn),
+    // It undid an earlier operation whose undo_next is this: it is never undone
+    Compensating(Lsn),
+}
+
+impl LogBody {
+    // The page bytes a redo writes: page ID, offset, bytes
+    pub fn redo(&self) -> Option<(u32, u16, &[u8])> {
+        match self {
+            LogBody::Update {
+                page_id,
+                offset,
+                after,
+                ..
+            }
+            | LogBody::Clr {
+                page_id,
+                offset,
+                after,
+                ..
+            } => Some((*page_id, *offset, after)),
+            _ => None,
+        }
+    }
+}
+
+impl LogRecord {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE];
+        bytes[OFFSET_LSN..OFFSET_LSN + 8].copy_from_slice(&self.lsn.to_ne_bytes());
+        bytes[OFFSET_TXN..OFFSET_TXN + 8].copy_from_slice(&self.txn.to_ne_bytes());
+        bytes[OFFSET_PREV_LSN..OFFSET_PREV_LSN + 8].copy_from_slice(&self.prev_lsn.to_ne_bytes());
+
+        bytes[OFFSET_KIND] = match &self.body {
+            LogBody::Begin => KIND_BEGIN,
+            LogBody::Commit => KIND_COMMIT,
+            LogBody::Abort => KIND_ABORT,
+            LogBody::End => KIND_END,
+            LogBody::Update {
+                page_id,
+                offset,
+                before,
+                after,
+            } => {
+                bytes.extend_from_slice(&page_id.to_ne_bytes());
+                bytes.extend_from_slice(&offset.to_ne_bytes());
+                bytes.extend_from_slice(&(before.len() as u16).to_ne_bytes());
+                bytes.extend_from_slice(before);
+                bytes.extend_from_slice(after);
+                KIND_UPDATE
+            }
+            LogBody::Clr {
+                page_id,
+                offset,
+                after,
+                undo_next,
+            } => {
+                bytes.extend_from_slice(&page_id.to_ne_bytes());
+                bytes.extend_from_slice(&offset.to_ne_bytes());
+                bytes.extend_from_slice(&(after.len() as u16).to_ne_bytes());
+                bytes.extend_from_slice(&undo_next.to_ne_bytes());
+                bytes.extend_from_slice(after);
+                KIND_CLR
+            }
+            LogBody::Operation { undo, undo_next } => {
+                bytes.extend_from_slice(&undo_next.to_ne_bytes());
+                undo.encode(&mut bytes);
+                KIND_OPERATION
+            }
+        };
+
+        let length = (bytes.len() + CHECKSUM_SIZE) as u32;
+        bytes[0..4].copy_from_slice(&length.to_ne_bytes());
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(&checksum.to_ne_bytes());
+        bytes
+    }
+
+    // Length of the record starting at `bytes`, None if not even that is there
+    pub fn encoded_length(bytes: &[u8]) -> Option<usize> {
+        let length = u32::from_ne_bytes(bytes.get(0..4)?.try_into().unwrap()) as usize;
+        Some(length)
+    }
+
+    // `bytes` holds exactly one record
+    // A record cut short or with a bad checksum (a write torn by a crash) is an error
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let corrupted = || io::Error::new(io::ErrorKind::InvalidData, "Corrupted log record");
+        if bytes.len() < HEADER_SIZE + CHECKSUM_SIZE
+            || Self::encoded_length(bytes) != Some(bytes.len())
+        {
+            return Err(corrupted());
+        }
+        let (content, stored) = bytes.split_at(bytes.len() - CHECKSUM_SIZE);
+        if checksum(content) != u32::from_ne_bytes(stored.try_into().unwrap()) {
+            return Err(corrupted());
+        }
+
+        let body = &content[HEADER_SIZE..];
+        let body = match content[OFFSET_KIND] {
+            KIND_BEGIN => LogBody::Begin,
+            KIND_COMMIT => LogBody::Commit,
+            KIND_ABORT => LogBody::Abort,
+            KIND_END => LogBody::End,
+            KIND_UPDATE => {
+                if body.len() < 8 {
+                    return Err(corrupted());
+                }
+                let length = u16::from_ne_bytes(body[6..8].try_into().unwrap()) as usize;
+                if body.len() != 8 + 2 * length {
+                    return Err(corrupted());
+                }
+                LogBody::Update {
+                    page_id: u32::from_ne_bytes(body[0..4].try_into().unwrap()),
+                    offset: u16::from_ne_bytes(body[4..6].try_into().unwrap()),
+                    before: body[8..8 + length].to_vec(),
+                    after: body[8 + length..].to_vec(),
+                }
+            }
+            KIND_CLR => {
+                if body.len() < 16 {
+                    return Err(corrupted());
+                }
+                let length = u16::from_ne_bytes(body[6..8].try_into().unwrap()) as usize;
+                if body.len() != 16 + length {
+                    return Err(corrupted());
+                }
+                LogBody::Clr {
+                    page_id: u32::from_ne_bytes(body[0..4].try_into().unwrap()),
+                    offset: u16::from_ne_bytes(body[4..6].try_into().unwrap()),
+                    after: body[16..].to_vec(),
+                    undo_next: read_u64(body, 8),
+                }
+            }
+            KIND_OPERATION => {
+                if body.len() < 8 {
+                    return Err(corrupted());
+                }
+                LogBody::Operation {
+                    undo_next: read_u64(body, 0),
+                    undo: Undo::decode(&body[8..]).ok_or_else(corrupted)?,
+                }
+            }
+            _ => return Err(corrupted()),
+        };
+
+        Ok(Self {
+            lsn: read_u64(content, OFFSET_LSN),
+            txn: read_u64(content, OFFSET_TXN),
+            prev_lsn: read_u64(content, OFFSET_PREV_LSN),
+            body,
+        })
+    }
+}
+
+// Kind(1) + the fields in order
+// Page IDs are 4 bytes, slot IDs 2, byte strings have a length(4) in front
+impl Undo {
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        let put_bytes = |bytes: &mut Vec<u8>, value: &[u8]| {
+            bytes.extend_from_slice(&(value.len() as u32).to_ne_bytes());
+            bytes.extend_from_slice(value);
+        };
+
+        match self {
+            Undo::Nothing => bytes.push(UNDO_NOTHING),
+            Undo::FreePage(page_id) => {
+                bytes.push(UNDO_FREE_PAGE);
+                bytes.extend_from_slice(&page_id.to_ne_bytes());
+            }
+            Undo::RemoveTuple {
+                table,
+                page_id,
+                slot_id,
+            }
+            | Undo::EmptySlot {
+                table,
+                page_id,
+                slot_id,
+            } => {
+                bytes.push(match self {
+                    Undo::RemoveTuple { .. } => UNDO_REMOVE_TUPLE,
+                    _ => UNDO_EMPTY_SLOT,
+                });
+                bytes.extend_from_slice(&table.to_ne_bytes());
+                bytes.extend_from_slice(&page_id.to_ne_bytes());
+                bytes.extend_from_slice(&slot_id.to_ne_bytes());
+            }
+            Undo::RestoreTuple {
+                table,
+                page_id,
+                slot_id,
+                version,
+                tuple,
+            } => {
+                bytes.push(UNDO_RESTORE_TUPLE);
+                bytes.extend_from_slice(&table.to_ne_bytes());
+                bytes.extend_from_slice(&page_id.to_ne_bytes());
+                bytes.extend_from_slice(&slot_id.to_ne_bytes());
+                bytes.extend_from_slice(&version.encode());
+                put_bytes(bytes, tuple);
+            }
+            Undo::SetVersion {
+                table,
+                page_id,
+                slot_id,
+                version,
+            } => {
+                bytes.push(UNDO_SET_VERSION);
+                bytes.extend_from_slice(&table.to_ne_bytes());
+                bytes.extend_from_slice(&page_id.to_ne_bytes());
+                bytes.extend_from_slice(&slot_id.to_ne_bytes());
+                bytes.extend_from_slice(&version.encode());
+            }
+            Undo::TreeDelete { root, key } => {
+                bytes.push(UNDO_TREE_DELETE);
+                bytes.extend_from_slice(&root.to_ne_bytes());
+                put_bytes(bytes, key);
+            }
+            Undo::TreeInsert { root, key, value } => {
+                bytes.push(UNDO_TREE_INSERT);
+                bytes.extend_from_slice(&root.to_ne_bytes());
+                put_bytes(bytes, key);
+                put_bytes(bytes, value);
+            }
+        }
+    }
+
+    // None if the bytes are not exactly one undo
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader { bytes, position: 1 };
+        let undo = match *bytes.first()? {
+            UNDO_NOTHING => Undo::Nothing,
+            UNDO_FREE_PAGE => Undo::FreePage(reader.u32()?),
+            UNDO_REMOVE_TUPLE => Undo::RemoveTuple {
+                table: reader.u32()?,
+                page_id: reader.u32()?,
+                slot_id: reader.u16()?,
+            },
+            UNDO_EMPTY_SLOT => Undo::EmptySlot {
+                table: reader.u32()?,
+                page_id: reader.u32()?,
+                slot_id: reader.u16()?,
+            },
+            UNDO_RESTORE_TUPLE => Undo::RestoreTuple {
+                table: reader.u32()?,
+                page_id: reader.u32()?,
+                slot_id: reader.u16()?,
+                version: Version::decode(reader.take(VERSION_SIZE)?),
+                tuple: reader.bytes()?,
+            },
+            UNDO_SET_VERSION => Undo::SetVersion {
+                table: reader.u32()?,
+                page_id: reader.u32()?,
+                slot_id: reader.u16()?,
+                version: Version::decode(reader.take(VERSION_SIZE)?),
+            },
+            UNDO_TREE_DELETE => Undo::TreeDelete {
+                root: reader.u32()?,
+                key: reader.bytes()?,
+            },
+            UNDO_TREE_INSERT => Undo::TreeInsert {
+                root: reader.u32()?,
+                key: reader.bytes()?,
+                value: reader.bytes()?,
+            },
+            _ => return None,
+        };
+
+        (reader.position == bytes.len()).then_some(undo)
+    }
+}
+
+// Reads the fields of an undo one after the other, None past the end
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let taken = self.bytes.get(self.position..self.position + length)?;
+        self.position += length;
+        Some(taken)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_ne_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_ne_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Option<Vec<u8>> {
+        let length = self.u32()? as usize;
+        Some(self.take(length)?.to_vec())
+    }
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_ne_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+// FNV-1a
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
@@ -1 +1,6 @@
-
+pub mod lock_manager;
+pub mod log_record;
+pub mod mvcc;
+pub mod recovery;
+pub mod transaction;
+pub mod wal;
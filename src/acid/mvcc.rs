@@ -0,0 +1,121 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{acid::log_record::TxnId, indexing::table_page::Version};
+
+// How the transactions of a database are kept apart, stored in its file header
+// Locking: strict two-phase locking, readers and writers wait for each other
+// Mvcc: readers see a snapshot and never wait, writers of the same row wait for each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Concurrency {
+    Locking,
+    Mvcc,
+}
+
+impl Concurrency {
+    pub fn code(self) -> u32 {
+        match self {
+            Concurrency::Locking => 0,
+            Concurrency::Mvcc => 1,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Result<Self, String> {
+        match code {
+            0 => Ok(Concurrency::Locking),
+            1 => Ok(Concurrency::Mvcc),
+            code => Err(format!("Unknown concurrency control {}", code)),
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "locking" => Some(Concurrency::Locking),
+            "mvcc" => Some(Concurrency::Mvcc),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Concurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Concurrency::Locking => write!(f, "locking"),
+            Concurrency::Mvcc => write!(f, "mvcc"),
+        }
+    }
+}
+
+// What a transaction sees: its own changes,
+// and those of the transactions that had committed when it started
+// A rolled back transaction is undone through the log before it ends,
+// so a version never belongs to an aborted transaction.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    txn: TxnId,
+    // First transaction ID handed out after the snapshot
+    next: TxnId,
+    // Transactions that were running when it was taken
+    running: HashSet<TxnId>,
+}
+
+// A version of a tuple, as seen from a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Visible,
+    Deleted,
+    // Created after the snapshot: one of the previous versions may be visible
+    Invisible,
+}
+
+impl Snapshot {
+    pub fn new(txn: TxnId, next: TxnId, running: HashSet<TxnId>) -> Self {
+        Self { txn, next, running }
+    }
+
+    pub fn sees(&self, txn: TxnId) -> bool {
+        txn == self.txn || (txn < self.next && !self.running.contains(&txn))
+    }
+
+    // Every transaction below this had ended when the snapshot was taken
+    pub fn horizon(&self) -> TxnId {
+        self.running
+            .iter()
+            .copied()
+            .min()
+            .unwrap_or(self.next)
+            .min(self.next)
+    }
+
+    pub fn visibility(&self, version: &Version) -> Visibility {
+        if !self.sees(version.created_by) {
+            Visibility::Invisible
+        } else if version.deleted_by.is_some_and(|txn| self.sees(txn)) {
+            Visibility::Deleted
+        } else {
+            Visibility::Visible
+        }
+    }
+
+    // First-updater-wins: only the latest version of a tuple may be replaced or deleted,
+    // and only if the snapshot sees it. Otherwise another transaction changed the tuple
+    // after the snapshot was taken, and writing over it would lose that update.
+    // Returns false if the tuple is deleted as far as the snapshot knows.
+    pub fn check_write(&self, latest: &Version) -> Result<bool, String> {
+        match self.visibility(latest) {
+            Visibility::Deleted => Ok(false),
+            Visibility::Invisible => Err(self.conflict(latest.created_by)),
+            // Unless a transaction it does not see deleted it
+            Visibility::Visible => match latest.deleted_by {
+                Some(txn) => Err(self.conflict(txn)),
+                None => Ok(true),
+            },
+        }
+    }
+
+    fn conflict(&self, txn: TxnId) -> String {
+        format!(
+            "Could not serialize transaction {}: transaction {} changed the same row after it started",
+            self.txn, txn
+        )
+    }
+}
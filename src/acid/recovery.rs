@@ -0,0 +1,476 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use crate::{
+    acid::{
+        log_record::{LogBody, LogRecord, Lsn, NO_LSN, SYSTEM_TXN, TxnId, Undo},
+        wal::Wal,
+    },
+    backend::cache::Cache,
+    indexing::{btree::tree::BPlusTree, table_heap::TableHeap},
+};
+
+// ARIES recovery, run when a database is opened
+// Analysis: find the pages that may miss logged changes and the unfinished transactions
+// Redo: repeat history, writing back every logged change a page does not have yet
+// Undo: roll the unfinished transactions back, logging each step as a CLR,
+// or as an operation compensating a whole one (see `LogBody::Operation`)
+pub fn recover(cache: &Arc<Cache>, wal: &Wal) -> Result<(), String> {
+    let records = wal.records().map_err(log_error)?;
+    let (dirty_pages, losers) = analyze(&records);
+    redo(cache, &records, &dirty_pages)?;
+    undo(cache, wal, &records, losers, NO_LSN)
+}
+
+// Undo an aborted transaction while the database is running
+pub fn rollback(cache: &Arc<Cache>, wal: &Wal, txn: TxnId) -> Result<(), String> {
+    rollback_to(cache, wal, txn, NO_LSN)
+}
+
+// Undo the changes a running transaction logged after `savepoint`, it goes on afterwards
+// Its complete operations are undone like any other change of the tables,
+// latching their pages and trees as they go: other sessions keep running meanwhile.
+pub fn rollback_to(
+    cache: &Arc<Cache>,
+    wal: &Wal,
+    txn: TxnId,
+    savepoint: Lsn,
+) -> Result<(), String> {
+    let Some(last_lsn) = wal.last_lsn(txn).filter(|&lsn| lsn > savepoint) else {
+        return Ok(());
+    };
+    let records = wal.records().map_err(log_error)?;
+
+    undo(
+        cache,
+        wal,
+        &records,
+        HashMap::from([(txn, last_lsn)]),
+        savepoint,
+    )
+}
+
+// Dirty page table: page ID -> recLSN, the first record that may be missing from the page
+// Transaction table: unfinished transaction -> its last record
+// The log only starts at a checkpoint, when no page was dirty and no transaction ran.
+fn analyze(records: &[LogRecord]) -> (HashMap<u32, Lsn>, HashMap<TxnId, Lsn>) {
+    let mut dirty_pages = HashMap::new();
+    let mut transactions = HashMap::new();
+
+    for record in records {
+        if record.txn != SYSTEM_TXN {
+            match record.body {
+                LogBody::Commit | LogBody::End => {
+                    transactions.remove(&record.txn);
+                }
+                _ => {
+                    transactions.insert(record.txn, record.lsn);
+                }
+            }
+        }
+
+        if let Some((page_id, _, _)) = record.body.redo() {
+            dirty_pages.entry(page_id).or_insert(record.lsn);
+        }
+    }
+
+    (dirty_pages, transactions)
+}
+
+// Repeat history from the earliest recLSN, losers included: undo runs on top of it
+fn redo(
+    cache: &Cache,
+    records: &[LogRecord],
+    dirty_pages: &HashMap<u32, Lsn>,
+) -> Result<(), String> {
+    let Some(&start) = dirty_pages.values().min() else {
+        return Ok(());
+    };
+
+    for record in records.iter().filter(|r| r.lsn >= start) {
+        let Some((page_id, offset, after)) = record.body.redo() else {
+            continue;
+        };
+        if dirty_pages[&page_id] > record.lsn {
+            continue;
+        }
+
+        // The page was written back after this change
+        let page_lsn = cache.page_lsn(page_id as usize).map_err(page_error)?;
+        if page_lsn >= record.lsn {
+            continue;
+        }
+
+        cache
+            .apply(page_id as usize, offset as usize, after, record.lsn)
+            .map_err(page_error)?;
+    }
+    Ok(())
+}
+
+// Undo the losers' updates down to `stop`, latest first across all of them
+// A CLR is never undone: it points to the next record to undo instead,
+// so a crash during undo does not undo the same change twice.
+// A complete operation is undone as a whole, its page changes are skipped: the operation
+// undoing it ends with a record pointing past them, like a CLR.
+// Changes outside a complete operation (one cut short by a crash or an error) are undone
+// byte by byte: they only touch pages no other transaction changed since.
+// Only a transaction undone completely gets its End record.
+fn undo(
+    cache: &Arc<Cache>,
+    wal: &Wal,
+    records: &[LogRecord],
+    losers: HashMap<TxnId, Lsn>,
+    stop: Lsn,
+) -> Result<(), String> {
+    let by_lsn: HashMap<Lsn, &LogRecord> = records.iter().map(|r| (r.lsn, r)).collect();
+    let mut to_undo: BTreeMap<Lsn, TxnId> = losers.into_iter().map(|(t, l)| (l, t)).collect();
+
+    while let Some((lsn, txn)) = to_undo.pop_last() {
+        let record = by_lsn
+            .get(&lsn)
+            .ok_or_else(|| format!("Log record {} of transaction {} is missing", lsn, txn))?;
+
+        let undo_next = match &record.body {
+            LogBody::Update {
+                page_id,
+                offset,
+                before,
+                ..
+            } => {
+                let clr = wal.append(
+                    txn,
+                    LogBody::Clr {
+                        page_id: *page_id,
+                        offset: *offset,
+                        after: before.clone(),
+                        undo_next: record.prev_lsn,
+                    },
+                );
+                cache
+                    .apply(*page_id as usize, *offset as usize, before, clr)
+                    .map_err(page_error)?;
+                record.prev_lsn
+            }
+            LogBody::Clr { undo_next, .. } => *undo_next,
+            LogBody::Operation { undo, undo_next } => {
+                undo_operation(cache, txn, undo, *undo_next)?;
+                *undo_next
+            }
+            _ => record.prev_lsn,
+        };
+
+        if undo_next == NO_LSN {
+            wal.end(txn).map_err(log_error)?;
+        } else if undo_next > stop {
+            to_undo.insert(undo_next, txn);
+        }
+    }
+    Ok(())
+}
+
+// Take back a complete operation of `txn` with operations of its own,
+// the last of which compensates it: rollback goes on at `undo_next`
+fn undo_operation(
+    cache: &Arc<Cache>,
+    txn: TxnId,
+    undo: &Undo,
+    undo_next: Lsn,
+) -> Result<(), String> {
+    let heap = |table: u32| TableHeap::new(cache.clone(), table as usize, txn);
+    let tree = |root: u32| BPlusTree::open(cache.clone(), root as usize, txn);
+
+    match undo {
+        Undo::Nothing => Ok(()),
+        Undo::FreePage(page_id) => cache
+            .undo_allocation(*page_id as usize, txn, undo_next)
+            .map_err(page_error),
+        Undo::RemoveTuple {
+            table,
+            page_id,
+            slot_id,
+        } => heap(*table).undo_insert(*page_id as usize, *slot_id, undo_next),
+        Undo::EmptySlot {
+            table,
+            page_id,
+            slot_id,
+        } => heap(*table).undo_copy(*page_id as usize, *slot_id, undo_next),
+        Undo::RestoreTuple {
+            table,
+            page_id,
+            slot_id,
+            version,
+            tuple,
+        } => heap(*table).undo_update(*page_id as usize, *slot_id, *version, tuple, undo_next),
+        Undo::SetVersion {
+            table,
+            page_id,
+            slot_id,
+            version,
+        } => heap(*table).undo_version(*page_id as usize, *slot_id, *version, undo_next),
+        Undo::TreeDelete { root, key } => tree(*root).undo_insert(key, undo_next),
+        Undo::TreeInsert { root, key, value } => tree(*root).undo_delete(key, value, undo_next),
+    }
+}
+
+fn log_error(e: std::io::Error) -> String {
+    format!("Failed to read or write the log: {}", e)
+}
+
+fn page_error(e: std::io::Error) -> String {
+    format!("Failed to recover a page: {}", e)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs, process,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::{
+        backend::crash::{CrashStorage, crash_after, crashed, restart, writes},
+        database::{Database, QueryResult},
+        types::Value,
+    };
+
+    // Rows every test starts with, committed before any crash
+    const COMMITTED: i64 = 5;
+    // Rows the crashing transaction inserts, enough to evict some of its pages
+    const INSERTED: i64 = 400;
+    // Crash points tried between the first and the last write
+    const CRASH_POINTS: usize = 12;
+
+    struct TempDb {
+        path: String,
+    }
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir()
+                .join(format!("mysqlite-crash-{}-{}.db", process::id(), name))
+                .to_string_lossy()
+                .into_owned();
+            let db = Self { path };
+            db.remove();
+            db
+        }
+
+        fn open(&self) -> Database {
+            Database::open_in(&CrashStorage, &self.path).unwrap()
+        }
+
+        fn size(&self) -> u64 {
+            fs::metadata(&self.path).unwrap().len()
+        }
+
+        fn remove(&self) {
+            let _ = fs::remove_file(&self.path);
+            let _ = fs::remove_file(format!("{}-wal", self.path));
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    fn exec(db: &mut Database, sql: &str) -> Result<(), String> {
+        db.execute(sql).into_iter().try_for_each(|r| r.map(|_| ()))
+    }
+
+    fn ids(db: &mut Database, sql: &str) -> Vec<i64> {
+        let Ok(QueryResult::Rows { rows, .. }) = db.execute(sql).pop().unwrap() else {
+            panic!("{} failed", sql);
+        };
+        let mut ids: Vec<i64> = rows
+            .iter()
+            .map(|row| match row.values[0] {
+                Value::Integer(id) => id,
+                ref other => panic!("Unexpected id {:?}", other),
+            })
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    // A fresh database with the committed rows, its pages all written back
+    fn setup(file: &TempDb) {
+        restart();
+        file.remove();
+        let mut db = file.open();
+        exec(&mut db, "CREATE TABLE t (id INTEGER, v TEXT)").unwrap();
+        exec(&mut db, "CREATE INDEX t_id ON t (id)").unwrap();
+        for id in 0..COMMITTED {
+            exec(
+                &mut db,
+                &format!("INSERT INTO t VALUES ({}, 'committed')", id),
+            )
+            .unwrap();
+        }
+    }
+
+    // One statement inserting rows COMMITTED.., one of them spilling into overflow pages
+    fn insert_statement() -> String {
+        let rows: Vec<String> = (COMMITTED..COMMITTED + INSERTED)
+            .map(|id| match id {
+                COMMITTED => format!("({}, '{}')", id, "o".repeat(5000)),
+                id => format!("({}, '{}')", id, "x".repeat(900)),
+            })
+            .collect();
+        format!("INSERT INTO t VALUES {}", rows.join(", "))
+    }
+
+    // The process dies: nothing is written on the way out,
+    // the files are left as they were at the crash (see `crash_after`), or as they are now
+    fn crash(db: Database) {
+        std::mem::forget(db);
+        restart();
+    }
+
+    // Reopen after a crash, the heap and the index must hold the same rows
+    fn reopen(file: &TempDb) -> Vec<i64> {
+        let mut db = file.open();
+        let plan = db.execute("EXPLAIN SELECT id FROM t WHERE id >= 0").pop();
+        assert!(
+            matches!(plan, Some(Ok(QueryResult::Plan(lines))) if lines[1].contains("Index Scan"))
+        );
+
+        let in_heap = ids(&mut db, "SELECT id FROM t");
+        let in_index = ids(&mut db, "SELECT id FROM t WHERE id >= 0");
+        assert_eq!(in_heap, in_index);
+        in_heap
+    }
+
+    fn committed() -> Vec<i64> {
+        (0..COMMITTED).collect()
+    }
+
+    fn all() -> Vec<i64> {
+        (0..COMMITTED + INSERTED).collect()
+    }
+
+    // Run the work once without crashing to count its writes, then crash it
+    // before the first one, the last one and some in between, picked at random.
+    // Each run picks other points: the seed is printed, MYSQLITE_CRASH_SEED replays it.
+    fn each_crash_point(mut run: impl FnMut(usize)) {
+        run(usize::MAX);
+        let total = writes();
+        assert!(total > 0);
+
+        let mut seed = match env::var("MYSQLITE_CRASH_SEED") {
+            Ok(seed) => seed.parse().expect("MYSQLITE_CRASH_SEED is not a number"),
+            Err(_) => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos() as u64,
+        };
+        // Xorshift never leaves 0
+        seed |= 1;
+        eprintln!("crash points picked with MYSQLITE_CRASH_SEED={}", seed);
+
+        let mut points = vec![0, total - 1];
+        for _ in 0..CRASH_POINTS {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            points.push((seed % total as u64) as usize);
+        }
+        points.sort();
+        points.dedup();
+        for point in points {
+            run(point);
+        }
+    }
+
+    #[test]
+    fn crash_during_insert() {
+        let file = TempDb::new("insert");
+        let statement = insert_statement();
+
+        each_crash_point(|writes| {
+            setup(&file);
+            let mut db = file.open();
+            crash_after(writes);
+            let result = exec(&mut db, &statement);
+            let done = !crashed();
+            crash(db);
+
+            let rows = reopen(&file);
+            if done {
+                // Committed, and nothing was lost
+                assert!(result.is_ok());
+                assert_eq!(rows, all());
+            } else {
+                assert!(
+                    rows == committed() || rows == all(),
+                    "crash after {} writes",
+                    writes
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn pages_written_before_commit_are_undone() {
+        let file = TempDb::new("steal");
+        setup(&file);
+        let size = file.size();
+
+        let mut db = file.open();
+        exec(&mut db, "BEGIN").unwrap();
+        exec(&mut db, &insert_statement()).unwrap();
+        // Evicted before the commit
+        assert!(file.size() > size);
+
+        crash_after(0);
+        exec(&mut db, "COMMIT").unwrap();
+        crash(db);
+        assert_eq!(reopen(&file), committed());
+    }
+
+    #[test]
+    fn crash_during_rollback() {
+        let file = TempDb::new("rollback");
+        let statement = insert_statement();
+
+        each_crash_point(|writes| {
+            setup(&file);
+            let mut db = file.open();
+            exec(&mut db, "BEGIN").unwrap();
+            exec(&mut db, &statement).unwrap();
+
+            crash_after(writes);
+            exec(&mut db, "ROLLBACK").unwrap();
+            crash(db);
+
+            assert_eq!(reopen(&file), committed(), "crash after {} writes", writes);
+        });
+    }
+
+    // Recovery undoes the loser with CLRs, crashing again must not undo anything twice
+    #[test]
+    fn crash_during_recovery() {
+        let file = TempDb::new("recovery");
+        let statement = insert_statement();
+
+        each_crash_point(|writes| {
+            setup(&file);
+            let mut db = file.open();
+            exec(&mut db, "BEGIN").unwrap();
+            exec(&mut db, &statement).unwrap();
+            crash(db);
+
+            crash_after(writes);
+            if let Ok(db) = Database::open_in(&CrashStorage, &file.path) {
+                crash(db);
+            }
+            restart();
+
+            assert_eq!(reopen(&file), committed(), "crash after {} writes", writes);
+        });
+    }
+}
@@ -0,0 +1,507 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::{
+    acid::{
+        lock_manager::{LockManager, LockMode, Resource},
+        log_record::{Lsn, NO_LSN, SYSTEM_TXN, TxnId},
+        mvcc::{Concurrency, Snapshot, Visibility},
+        recovery::{rollback, rollback_to},
+        wal::Wal,
+    },
+    backend::cache::Cache,
+    indexing::{
+        btree::tree::BPlusTree, overflow_page::free_chain, table_heap::TableHeap,
+        table_page::Version,
+    },
+};
+
+// How often the deadlock detector looks for cycles
+const DEADLOCK_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionState {
+    Active,
+    Committed,
+    Aborted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteKind {
+    Insert,
+    Delete,
+    Update,
+}
+
+// A tuple a transaction changed through a `TableHeap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteRecord {
+    pub kind: WriteKind,
+    // First page of the table
+    pub table: usize,
+    // (page ID, slot ID)
+    pub rid: (usize, u16),
+}
+
+// Pages a transaction dropped (a table, an index, the overflow chain of a replaced row),
+// freed once it committed and no snapshot can read them (see `TransactionManager::free_dropped`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dropped {
+    // First page of a table heap
+    Table(usize),
+    // Root page of a B+ tree
+    Tree(usize),
+    // First page of an overflow chain
+    Chain(usize),
+}
+
+// A tuple of a table: (first page of the table, RID)
+type TableRow = (usize, (usize, u16));
+
+pub struct Transaction {
+    id: TxnId,
+    state: Mutex<TransactionState>,
+    write_set: Mutex<Vec<WriteRecord>>,
+    dropped: Mutex<Vec<Dropped>>,
+    // Under MVCC: what it reads, None under two-phase locking
+    snapshot: Option<Snapshot>,
+    // Tuples read through the snapshot, checked against concurrent writers at commit
+    read_set: Mutex<HashSet<TableRow>>,
+    // Lost a write conflict, it can only be rolled back
+    conflicted: AtomicBool,
+    // Has logged its Begin record (see `start_writing`)
+    writing: AtomicBool,
+    // Locks are held until the transaction ends (strict two-phase locking)
+    locks: Arc<LockManager>,
+    wal: Arc<Wal>,
+}
+
+// Where a running transaction can be rolled back to
+#[derive(Debug, Clone, Copy)]
+pub struct Savepoint {
+    lsn: Lsn,
+    writes: usize,
+    dropped: usize,
+}
+
+impl Transaction {
+    fn new(id: TxnId, snapshot: Option<Snapshot>, locks: Arc<LockManager>, wal: Arc<Wal>) -> Self {
+        Self {
+            id,
+            state: Mutex::new(TransactionState::Active),
+            write_set: Mutex::new(Vec::new()),
+            dropped: Mutex::new(Vec::new()),
+            snapshot,
+            read_set: Mutex::new(HashSet::new()),
+            conflicted: AtomicBool::new(false),
+            writing: AtomicBool::new(false),
+            locks,
+            wal,
+        }
+    }
+
+    pub fn id(&self) -> TxnId {
+        self.id
+    }
+
+    pub fn state(&self) -> TransactionState {
+        *self.state.lock().unwrap()
+    }
+
+    // Tuples changed so far, oldest first
+    pub fn write_set(&self) -> Vec<WriteRecord> {
+        self.write_set.lock().unwrap().clone()
+    }
+
+    pub fn record_write(&self, kind: WriteKind, table: usize, rid: (usize, u16)) {
+        self.write_set
+            .lock()
+            .unwrap()
+            .push(WriteRecord { kind, table, rid });
+    }
+
+    // Rolling back must not find the pages freed, they stay until the commit
+    pub fn drop_on_commit(&self, dropped: Dropped) {
+        self.dropped.lock().unwrap().push(dropped);
+    }
+
+    pub fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    // Which version of a tuple it reads
+    // Without a snapshot only the latest version exists, unless it is deleted.
+    pub fn visibility(&self, version: &Version) -> Visibility {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.visibility(version),
+            None if version.deleted_by.is_some() => Visibility::Deleted,
+            None => Visibility::Visible,
+        }
+    }
+
+    pub fn record_read(&self, table: usize, rid: (usize, u16)) {
+        if self.snapshot.is_some() {
+            self.read_set.lock().unwrap().insert((table, rid));
+        }
+    }
+
+    // Before replacing or deleting the latest version of a tuple
+    // Returns false if the tuple is already deleted.
+    pub fn check_write(&self, latest: &Version) -> Result<bool, String> {
+        match &self.snapshot {
+            Some(snapshot) => snapshot
+                .check_write(latest)
+                .inspect_err(|_| self.conflicted.store(true, Ordering::SeqCst)),
+            None => Ok(latest.deleted_by.is_none()),
+        }
+    }
+
+    // Call before changing any page
+    // Writers only hold the database in IX: they are kept apart by their tuple locks,
+    // so writers of different rows run concurrently.
+    pub fn start_writing(&self) -> Result<(), String> {
+        if self.writing.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        self.locks
+            .lock(self.id, Resource::Database, LockMode::IntentionExclusive)?;
+        self.wal.begin(self.id);
+        self.writing.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    // Wait until no other transaction writes, and keep new writers out until it ends
+    // Taken by schema changes: writers bind their statements against a stable catalog.
+    pub fn lock_database(&self) -> Result<(), String> {
+        self.locks
+            .lock(self.id, Resource::Database, LockMode::Exclusive)
+    }
+
+    // Has it changed anything (see `start_writing`)?
+    pub fn is_writing(&self) -> bool {
+        self.writing.load(Ordering::SeqCst)
+    }
+
+    // Wait for a lock on a whole table
+    // Under MVCC readers never lock, only writers do.
+    pub fn lock_table(&self, table: usize, mode: LockMode) -> Result<(), String> {
+        if self.snapshot.is_some() && reads_only(mode) {
+            return Ok(());
+        }
+        self.locks.lock(self.id, Resource::Table(table), mode)
+    }
+
+    // Wait for a shared or exclusive lock on a tuple
+    // The table gets the matching intention lock first.
+    // Under MVCC writers of the same row wait for each other, then the snapshot check fails.
+    pub fn lock_tuple(
+        &self,
+        table: usize,
+        rid: (usize, u16),
+        mode: LockMode,
+    ) -> Result<(), String> {
+        if self.snapshot.is_some() && reads_only(mode) {
+            return Ok(());
+        }
+        let intention = match mode {
+            LockMode::Shared | LockMode::IntentionShared => LockMode::IntentionShared,
+            _ => LockMode::IntentionExclusive,
+        };
+        self.lock_table(table, intention)?;
+        self.locks.lock(self.id, Resource::Tuple(table, rid), mode)
+    }
+}
+
+// Shared and intention shared locks only keep out writers
+fn reads_only(mode: LockMode) -> bool {
+    matches!(mode, LockMode::Shared | LockMode::IntentionShared)
+}
+
+// Starts, commits and rolls back transactions
+// Every page change is logged under the writing transaction,
+// so a rollback undoes its tuples together with their index entries and pages.
+pub struct TransactionManager {
+    cache: Arc<Cache>,
+    wal: Arc<Wal>,
+    locks: Arc<LockManager>,
+    concurrency: Mutex<Concurrency>,
+    active: Mutex<HashMap<TxnId, Arc<Transaction>>>,
+    // Rows changed by recently committed MVCC transactions,
+    // kept while a transaction that does not see them is running
+    committed: Mutex<Vec<(TxnId, HashSet<TableRow>)>>,
+    // Rows deleted by committed transactions, or updated under MVCC:
+    // their old versions are garbage
+    garbage: Mutex<Vec<(TxnId, TableRow)>>,
+    // Pages dropped by committed transactions, snapshot readers take no locks to keep them
+    dropped: Mutex<Vec<(TxnId, Dropped)>>,
+}
+
+impl TransactionManager {
+    pub fn new(cache: Arc<Cache>, wal: Arc<Wal>, concurrency: Concurrency) -> Self {
+        let locks = Arc::new(LockManager::new());
+        locks.start_deadlock_detector(DEADLOCK_CHECK_INTERVAL);
+
+        Self {
+            cache,
+            wal,
+            locks,
+            concurrency: Mutex::new(concurrency),
+            active: Mutex::new(HashMap::new()),
+            committed: Mutex::new(Vec::new()),
+            garbage: Mutex::new(Vec::new()),
+            dropped: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn lock_manager(&self) -> &Arc<LockManager> {
+        &self.locks
+    }
+
+    pub fn concurrency(&self) -> Concurrency {
+        *self.concurrency.lock().unwrap()
+    }
+
+    // Switch modes and keep the choice in the file header
+    // No session can begin a transaction until the switch is done
+    pub fn set_concurrency(&self, concurrency: Concurrency) -> Result<(), String> {
+        let active = self.active.lock().unwrap();
+        if !active.is_empty() {
+            return Err(
+                "Cannot change the concurrency control while a transaction is running".to_string(),
+            );
+        }
+
+        self.cache
+            .update_header(SYSTEM_TXN, |header| header.concurrency = concurrency.code())
+            .map_err(|e| format!("Failed to write the header: {}", e))?;
+        *self.concurrency.lock().unwrap() = concurrency;
+        drop(active);
+        Ok(())
+    }
+
+    // Nothing is logged until the transaction starts writing
+    // Under MVCC the snapshot is taken here: the IDs handed out so far,
+    // minus the transactions still running
+    pub fn begin(&self) -> Result<Arc<Transaction>, String> {
+        let mut active = self.active.lock().unwrap();
+        let id = self.wal.reserve_txn();
+        let snapshot = match self.concurrency() {
+            Concurrency::Locking => None,
+            Concurrency::Mvcc => {
+                let running = active.keys().copied().collect();
+                Some(Snapshot::new(id, id + 1, running))
+            }
+        };
+
+        let txn = Arc::new(Transaction::new(
+            id,
+            snapshot,
+            self.locks.clone(),
+            self.wal.clone(),
+        ));
+        active.insert(id, txn.clone());
+        Ok(txn)
+    }
+
+    pub fn get(&self, id: TxnId) -> Option<Arc<Transaction>> {
+        self.active.lock().unwrap().get(&id).cloned()
+    }
+
+    // Chosen to break a deadlock, or lost a write conflict: it can only be rolled back
+    pub fn must_roll_back(&self, txn: &Transaction) -> bool {
+        self.locks.is_victim(txn.id) || txn.conflicted.load(Ordering::SeqCst)
+    }
+
+    // Durable once this returns
+    // A transaction that logged nothing (it only read) has nothing to commit.
+    // One that fails validation stays active, the caller has to roll it back.
+    pub fn commit(&self, txn: &Transaction) -> Result<(), String> {
+        self.check_active(txn)?;
+        if self.wal.last_lsn(txn.id).is_none() {
+            self.finish(txn, TransactionState::Committed);
+            return Ok(());
+        }
+
+        if let Err(e) = self.validate(txn) {
+            txn.conflicted.store(true, Ordering::SeqCst);
+            return Err(e);
+        }
+        self.wal
+            .commit(txn.id)
+            .map_err(|e| format!("Failed to write the log: {}", e))?;
+        self.remember_writes(txn);
+        self.remember_dropped(txn);
+        self.finish(txn, TransactionState::Committed);
+        Ok(())
+    }
+
+    // Undo every change of the transaction
+    pub fn abort(&self, txn: &Transaction) -> Result<(), String> {
+        self.check_active(txn)?;
+        if self.wal.last_lsn(txn.id).is_some() {
+            self.wal.abort(txn.id);
+            rollback(&self.cache, &self.wal, txn.id)?;
+        }
+        self.finish(txn, TransactionState::Aborted);
+        Ok(())
+    }
+
+    pub fn savepoint(&self, txn: &Transaction) -> Savepoint {
+        Savepoint {
+            lsn: self.wal.last_lsn(txn.id).unwrap_or(NO_LSN),
+            writes: txn.write_set.lock().unwrap().len(),
+            dropped: txn.dropped.lock().unwrap().len(),
+        }
+    }
+
+    // Undo the changes made since the savepoint, the transaction stays active
+    pub fn rollback_to(&self, txn: &Transaction, savepoint: Savepoint) -> Result<(), String> {
+        self.check_active(txn)?;
+        rollback_to(&self.cache, &self.wal, txn.id, savepoint.lsn)?;
+        txn.write_set.lock().unwrap().truncate(savepoint.writes);
+        txn.dropped.lock().unwrap().truncate(savepoint.dropped);
+        Ok(())
+    }
+
+    // Write every dirty page back and empty the log, once no transaction is writing
+    // Writers hold the database in IX, so the exclusive lock keeps new changes out meanwhile.
+    pub fn checkpoint(&self) -> Result<(), String> {
+        let id = self.wal.reserve_txn();
+        self.locks
+            .lock(id, Resource::Database, LockMode::Exclusive)?;
+        let result = self.cache.checkpoint();
+        self.locks.release_all(id);
+        result.map_err(|e| format!("Failed to flush: {}", e))
+    }
+
+    // Transactions below this are seen by every running transaction
+    pub fn horizon(&self) -> TxnId {
+        self.active
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|txn| Some(txn.snapshot.as_ref()?.horizon().min(txn.id)))
+            .min()
+            .unwrap_or(TxnId::MAX)
+    }
+
+    // Rows whose old versions no running transaction can see anymore
+    pub fn take_garbage(&self) -> Vec<TableRow> {
+        let horizon = self.horizon();
+        let mut rows = Vec::new();
+        self.garbage.lock().unwrap().retain(|&(writer, row)| {
+            if writer < horizon {
+                rows.push(row);
+            }
+            writer >= horizon
+        });
+
+        rows.sort();
+        rows.dedup();
+        rows
+    }
+
+    // Free the pages dropped by committed transactions no running transaction can read anymore
+    // They are freed outside any transaction: a failure only leaks them.
+    pub fn free_dropped(&self) {
+        let horizon = self.horizon();
+        let mut freed = Vec::new();
+        self.dropped.lock().unwrap().retain(|&(writer, dropped)| {
+            if writer < horizon {
+                freed.push(dropped);
+            }
+            writer >= horizon
+        });
+
+        for dropped in freed {
+            let _ = match dropped {
+                Dropped::Table(first_page_id) => {
+                    TableHeap::new(self.cache.clone(), first_page_id, SYSTEM_TXN).destroy()
+                }
+                Dropped::Tree(root_page_id) => {
+                    BPlusTree::open(self.cache.clone(), root_page_id, SYSTEM_TXN).destroy()
+                }
+                Dropped::Chain(first_page_id) => free_chain(&self.cache, first_page_id, SYSTEM_TXN),
+            };
+        }
+    }
+
+    // Snapshot isolation lets two transactions each change a row the other one read
+    // (write skew). A writer whose reads were changed by a transaction it does not see
+    // may have decided on stale data, so it is rolled back instead.
+    // Rows inserted by other transactions are not checked.
+    fn validate(&self, txn: &Transaction) -> Result<(), String> {
+        let Some(snapshot) = &txn.snapshot else {
+            return Ok(());
+        };
+
+        let read_set = txn.read_set.lock().unwrap();
+        for (writer, rows) in self.committed.lock().unwrap().iter() {
+            if !snapshot.sees(*writer) && !rows.is_disjoint(&read_set) {
+                return Err(format!(
+                    "Could not serialize transaction {}: transaction {} changed a row it read",
+                    txn.id, writer
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Updates only leave old versions behind under MVCC, deletes always do
+    fn remember_writes(&self, txn: &Transaction) {
+        let writes = txn.write_set();
+        let versioned = txn.snapshot.is_some();
+
+        if versioned {
+            let horizon = self.horizon();
+            let mut committed = self.committed.lock().unwrap();
+            committed.retain(|&(writer, _)| writer >= horizon);
+            committed.push((txn.id, writes.iter().map(|w| (w.table, w.rid)).collect()));
+        }
+
+        self.garbage.lock().unwrap().extend(
+            writes
+                .iter()
+                .filter(|w| match w.kind {
+                    WriteKind::Insert => false,
+                    WriteKind::Delete => true,
+                    WriteKind::Update => versioned,
+                })
+                .map(|w| (txn.id, (w.table, w.rid))),
+        );
+    }
+
+    // A snapshot taken before the commit may still read the dropped pages
+    fn remember_dropped(&self, txn: &Transaction) {
+        let dropped: Vec<_> = txn.dropped.lock().unwrap().drain(..).collect();
+        self.dropped
+            .lock()
+            .unwrap()
+            .extend(dropped.into_iter().map(|dropped| (txn.id, dropped)));
+    }
+
+    fn check_active(&self, txn: &Transaction) -> Result<(), String> {
+        match txn.state() {
+            TransactionState::Active => Ok(()),
+            TransactionState::Committed => {
+                Err(format!("Transaction {} is already committed", txn.id))
+            }
+            TransactionState::Aborted => {
+                Err(format!("Transaction {} is already rolled back", txn.id))
+            }
+        }
+    }
+
+    fn finish(&self, txn: &Transaction, state: TransactionState) {
+        *txn.state.lock().unwrap() = state;
+        self.active.lock().unwrap().remove(&txn.id);
+        self.locks.release_all(txn.id);
+    }
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashMap, io, sync::Mutex};
+
+use crate::{
+    acid::log_record::{LogBody, LogRecord, Lsn, NO_LSN, OperationEnd, SYSTEM_TXN, TxnId, Undo},
+    backend::storage::{DiskStorage, Storage, StorageFile},
+};
+
+// Every log file starts with this string
+const MAGIC: &[u8; 8] = b"mysqlwal";
+
+// Log file layout
+// Bytes 0-7: Magic string
+// Bytes 8-15: LSN of the first record in the file
+// Bytes 16-23: Next transaction ID to hand out
+// Bytes 24-..: Records, back to back
+const OFFSET_START_LSN: usize = 8;
+const OFFSET_NEXT_TXN: usize = 16;
+const HEADER_SIZE: usize = 24;
+
+// Write-ahead log
+// Every change to a page is appended here before the page may reach the database file,
+// so a crash can always be repaired from the log.
+// Records are buffered in memory until `flush` forces them to disk.
+pub struct Wal {
+    inner: Mutex<WalInner>,
+}
+
+struct WalInner {
+    file: Box<dyn StorageFile>,
+
+    // LSN of the first byte after the file header
+    start_lsn: Lsn,
+
+    // Appended, not yet written to the file
+    buffer: Vec<u8>,
+
+    // LSN the next record gets
+    next_lsn: Lsn,
+
+    // Every record below this LSN is on disk
+    flushed_lsn: Lsn,
+
+    next_txn: TxnId,
+
+    // Last record of each unfinished transaction (chains records through `prev_lsn`)
+    // A transaction stays here after Abort, until its rollback writes End.
+    last_lsn: HashMap<TxnId, Lsn>,
+}
+
+impl Wal {
+    // Open (or create) a log file
+    // A record torn by a crash ends the log: it and anything after it are dropped.
+    pub fn open(path: &str) -> io::Result<Self> {
+        Self::open_in(&DiskStorage, path)
+    }
+
+    // The same, with a file of `storage`
+    pub fn open_in(storage: &dyn Storage, path: &str) -> io::Result<Self> {
+        let mut file = storage.open(path)?;
+        let bytes = read_all(file.as_mut())?;
+
+        // LSNs start after the header, so no record ever gets NO_LSN
+        let (start_lsn, mut next_txn) = if bytes.is_empty() {
+            write_header(file.as_mut(), HEADER_SIZE as Lsn, 1)?;
+            (HEADER_SIZE as Lsn, 1)
+        } else {
+            if bytes.len() < HEADER_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "File is not a mysqlite log",
+                ));
+            }
+            (
+                u64::from_ne_bytes(
+                    bytes[OFFSET_START_LSN..OFFSET_START_LSN + 8]
+                        .try_into()
+                        .unwrap(),
+                ),
+                u64::from_ne_bytes(
+                    bytes[OFFSET_NEXT_TXN..OFFSET_NEXT_TXN + 8]
+                        .try_into()
+                        .unwrap(),
+                ),
+            )
+        };
+
+        let (records, end) = parse_records(&bytes);
+        let mut last_lsn = HashMap::new();
+        for record in &records {
+            next_txn = next_txn.max(record.txn + 1);
+            match record.body {
+                _ if record.txn == SYSTEM_TXN => {}
+                LogBody::Commit | LogBody::End => {
+                    last_lsn.remove(&record.txn);
+                }
+                _ => {
+                    last_lsn.insert(record.txn, record.lsn);
+                }
+            }
+        }
+
+        if end < bytes.len() {
+            file.set_len(end as u64)?;
+            file.sync()?;
+        }
+
+        let next_lsn = start_lsn + (end - HEADER_SIZE) as Lsn;
+        Ok(Self {
+            inner: Mutex::new(WalInner {
+                file,
+                start_lsn,
+                buffer: Vec::new(),
+                next_lsn,
+                flushed_lsn: next_lsn,
+                next_txn,
+                last_lsn,
+            }),
+        })
+    }
+
+    // Every record in the log, oldest first
+    pub fn records(&self) -> io::Result<Vec<LogRecord>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.flush()?;
+
+        let bytes = read_all(inner.file.as_mut())?;
+        Ok(parse_records(&bytes).0)
+    }
+
+    // Bytes of records in the log
+    pub fn size(&self) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        inner.next_lsn - inner.start_lsn
+    }
+
+    // Add a record to the log buffer, return its LSN
+    pub fn append(&self, txn: TxnId, body: LogBody) -> Lsn {
+        let mut inner = self.inner.lock().unwrap();
+        inner.append(txn, body)
+    }
+
+    // Log a page change made by a transaction
+    // Changes of SYSTEM_TXN (outside any transaction) are redone, never undone.
+    pub fn append_update(
+        &self,
+        txn: TxnId,
+        page_id: u32,
+        offset: u16,
+        before: Vec<u8>,
+        after: Vec<u8>,
+    ) -> Lsn {
+        let mut inner = self.inner.lock().unwrap();
+        inner.append(
+            txn,
+            LogBody::Update {
+                page_id,
+                offset,
+                before,
+                after,
+            },
+        )
+    }
+
+    // Where an operation of `txn` starting now ends (see `LogBody::Operation`)
+    pub fn start_operation(&self, txn: TxnId) -> OperationEnd {
+        OperationEnd::Started(self.last_lsn(txn).unwrap_or(NO_LSN))
+    }
+
+    // Log that an operation of `txn` is complete, with how to undo it
+    // Changes of SYSTEM_TXN are never undone: they need no such record.
+    pub fn end_operation(&self, txn: TxnId, end: OperationEnd, undo: Undo) {
+        let body = match end {
+            OperationEnd::Started(undo_next) => LogBody::Operation { undo, undo_next },
+            OperationEnd::Compensating(undo_next) => LogBody::Operation {
+                undo: Undo::Nothing,
+                undo_next,
+            },
+        };
+        if txn != SYSTEM_TXN {
+            self.append(txn, body);
+        }
+    }
+
+    // Make sure every record up to `lsn` is on disk
+    pub fn flush(&self, lsn: Lsn) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if lsn < inner.flushed_lsn {
+            return Ok(());
+        }
+        inner.flush()
+    }
+
+    pub fn flushed_lsn(&self) -> Lsn {
+        self.inner.lock().unwrap().flushed_lsn
+    }
+
+    // Hand out a transaction ID without logging anything yet
+    // Transactions that only read never reach the log.
+    pub fn reserve_txn(&self) -> TxnId {
+        let mut inner = self.inner.lock().unwrap();
+        let txn = inner.next_txn;
+        inner.next_txn += 1;
+        txn
+    }
+
+    // Log the Begin record of a reserved transaction, before its first page change
+    // Does nothing if it is already in the log.
+    pub fn begin(&self, txn: TxnId) {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.last_lsn.contains_key(&txn) {
+            inner.append(txn, LogBody::Begin);
+        }
+    }
+
+    // A transaction is committed once its commit record is on disk
+    pub fn commit(&self, txn: TxnId) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.append(txn, LogBody::Commit);
+        inner.last_lsn.remove(&txn);
+        inner.flush()
+    }
+
+    // The transaction gives up, its rollback comes next
+    pub fn abort(&self, txn: TxnId) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.append(txn, LogBody::Abort);
+    }
+
+    // The transaction is rolled back
+    pub fn end(&self, txn: TxnId) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.append(txn, LogBody::End);
+        inner.last_lsn.remove(&txn);
+        inner.flush()
+    }
+
+    // Last record of an unfinished transaction
+    pub fn last_lsn(&self, txn: TxnId) -> Option<Lsn> {
+        self.inner.lock().unwrap().last_lsn.get(&txn).copied()
+    }
+
+    // Empty the log, once every change it holds is safely in the database file
+    // LSNs go on from where they were: pages keep their pageLSN.
+    // Nothing happens while a transaction is unfinished, its records must stay for its rollback:
+    // a later checkpoint empties the log.
+    pub fn truncate(&self) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.last_lsn.is_empty() {
+            return Ok(());
+        }
+
+        inner.buffer.clear();
+        inner.start_lsn = inner.next_lsn;
+        inner.flushed_lsn = inner.next_lsn;
+
+        let (start_lsn, next_txn) = (inner.start_lsn, inner.next_txn);
+        inner.file.set_len(HEADER_SIZE as u64)?;
+        write_header(inner.file.as_mut(), start_lsn, next_txn)
+    }
+}
+
+impl WalInner {
+    fn append(&mut self, txn: TxnId, body: LogBody) -> Lsn {
+        let lsn = self.next_lsn;
+        // System changes are not undone, so they are not chained
+        let prev_lsn = match txn {
+            SYSTEM_TXN => NO_LSN,
+            txn => self.last_lsn.insert(txn, lsn).unwrap_or(NO_LSN),
+        };
+
+        let bytes = LogRecord {
+            lsn,
+            txn,
+            prev_lsn,
+            body,
+        }
+        .encode();
+        self.next_lsn += bytes.len() as Lsn;
+        self.buffer.extend_from_slice(&bytes);
+        lsn
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            let end = self.file.len()?;
+            self.file.write_at(end, &self.buffer)?;
+            self.buffer.clear();
+        }
+        self.file.sync()?;
+        self.flushed_lsn = self.next_lsn;
+        Ok(())
+    }
+}
+
+// The records after the file header, and where the last whole one ends
+fn parse_records(bytes: &[u8]) -> (Vec<LogRecord>, usize) {
+    let mut records = Vec::new();
+    let mut end = HEADER_SIZE;
+    while let Some(length) = bytes.get(end..).and_then(LogRecord::encoded_length) {
+        let Some(record_bytes) = bytes.get(end..end + length) else {
+            break;
+        };
+        let Ok(record) = LogRecord::decode(record_bytes) else {
+            break;
+        };
+        records.push(record);
+        end += length;
+    }
+    (records, end)
+}
+
+fn read_all(file: &mut dyn StorageFile) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; file.len()? as usize];
+    file.read_at(0, &mut bytes)?;
+    Ok(bytes)
+}
+
+fn write_header(file: &mut dyn StorageFile, start_lsn: Lsn, next_txn: TxnId) -> io::Result<()> {
+    let mut header = [0u8; HEADER_SIZE];
+    header[..MAGIC.len()].copy_from_slice(MAGIC);
+    header[OFFSET_START_LSN..OFFSET_START_LSN + 8].copy_from_slice(&start_lsn.to_ne_bytes());
+    header[OFFSET_NEXT_TXN..OFFSET_NEXT_TXN + 8].copy_from_slice(&next_txn.to_ne_bytes());
+
+    file.write_at(0, &header)?;
+    file.sync()
+}
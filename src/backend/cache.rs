@@ -1,12 +1,23 @@
 use std::{
     collections::HashMap,
     io,
-    sync::{Arc, Mutex, RwLock},
+    sync::{
+        Arc, Mutex, RwLock, Weak,
+        atomic::{AtomicUsize, Ordering},
+    },
 };
 
-use crate::backend::{
-    lru_replacer::LRUReplacer,
-    pager::{PAGE_SIZE, Page, Pager},
+use crate::{
+    acid::{
+        log_record::{Lsn, OperationEnd, TxnId, Undo},
+        wal::Wal,
+    },
+    backend::{
+        freelist::FreelistTrunk,
+        header::{FileHeader, HEADER_PAGE_ID},
+        lru_replacer::LRUReplacer,
+        pager::{PAGE_SIZE, Page, PageData, Pager, USABLE_SIZE, page_lsn, set_page_lsn},
+    },
 };
 
 #[derive(Debug)]
@@ -20,6 +31,9 @@ pub struct Frame {
 
     // How many threads are currently using this?
     pub pin_count: usize,
+
+    // The page as of its last log record, to find what changed since
+    logged: Option<Box<PageData>>,
 }
 
 impl Frame {
@@ -28,10 +42,18 @@ impl Frame {
             page: None,
             is_dirty: false,
             pin_count: 0,
+            logged: None,
         }
     }
 }
 
+// Page requests served from memory (hits) and read from disk (misses)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
 pub struct Cache {
     pager: Mutex<Pager>,
 
@@ -49,10 +71,29 @@ pub struct Cache {
     // Algorithm to decide what frame should kick out
     // if the buffer pool is full
     replacer: Mutex<LRUReplacer>,
+
+    // Serializes page allocation / deallocation
+    // (the free list and the header must change together)
+    alloc_latch: Mutex<()>,
+
+    // Latch of each open B+ tree, keyed by its root page
+    tree_latches: Mutex<HashMap<usize, Weak<RwLock<()>>>>,
+
+    // Frames lent out by `borrow_frame`, at most half of the pool
+    borrowed: AtomicUsize,
+
+    // Counted by `fetch_page`, since the cache was created
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+
+    // Every page change is logged here when the page is unpinned,
+    // under the transaction that made it
+    // A dirty page is only written once the log is on disk up to its pageLSN.
+    wal: Arc<Wal>,
 }
 
 impl Cache {
-    pub fn new(pager: Pager, pool_size: usize) -> Self {
+    pub fn new(pager: Pager, pool_size: usize, wal: Arc<Wal>) -> Self {
         let mut frames = Vec::with_capacity(pool_size);
         let mut free_list = Vec::with_capacity(pool_size);
 
@@ -67,6 +108,19 @@ impl Cache {
             pf_table: Mutex::new(HashMap::new()),
             free_list: Mutex::new(free_list),
             replacer: Mutex::new(LRUReplacer::new()),
+            alloc_latch: Mutex::new(()),
+            tree_latches: Mutex::new(HashMap::new()),
+            borrowed: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            wal,
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
         }
     }
 
@@ -84,11 +138,13 @@ impl Cache {
             frame.pin_count += 1;
             replacer.pin(frame_id);
 
+            self.hits.fetch_add(1, Ordering::Relaxed);
             return Ok(frame_arc.clone());
         }
 
         // Miss Cache
         // Find a free frame, and read from pager (disk)
+        self.misses.fetch_add(1, Ordering::Relaxed);
         let frame_id = self.find_free_frame(&mut replacer)?;
         let frame_arc = self.frames[frame_id].clone();
         let mut frame = frame_arc.write().unwrap();
@@ -97,6 +153,7 @@ impl Cache {
         if let Some(ref old_page) = frame.page {
             // If the frame is dirty -> update to pager first
             if frame.is_dirty {
+                self.wal.flush(page_lsn(&old_page.data))?;
                 let mut pager = self.pager.lock().unwrap();
                 pager.write_page(old_page)?;
             }
@@ -114,6 +171,7 @@ impl Cache {
             },
         };
 
+        frame.logged = Some(Box::new(page_data.data));
         frame.page = Some(page_data);
         frame.pin_count = 1;
         frame.is_dirty = false;
@@ -133,6 +191,7 @@ impl Cache {
             let mut frame = frame_arc.write().unwrap();
 
             if let Some(ref page) = frame.page {
+                self.wal.flush(page_lsn(&page.data))?;
                 let mut pager = self.pager.lock().unwrap();
                 pager.write_page(page)?;
                 frame.is_dirty = false;
@@ -142,9 +201,39 @@ impl Cache {
         Ok(())
     }
 
+    // Write every dirty page back to disk
+    pub fn flush_all(&self) -> io::Result<()> {
+        let pf_table = self.pf_table.lock().unwrap();
+
+        for &frame_id in pf_table.values() {
+            let frame_arc = self.frames[frame_id].clone();
+            let mut frame = frame_arc.write().unwrap();
+
+            if frame.is_dirty {
+                if let Some(ref page) = frame.page {
+                    self.wal.flush(page_lsn(&page.data))?;
+                    let mut pager = self.pager.lock().unwrap();
+                    pager.write_page(page)?;
+                }
+                frame.is_dirty = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Write every dirty page back and empty the log
+    // Only while no transaction is running: its changes must stay undoable
+    pub fn checkpoint(&self) -> io::Result<()> {
+        self.flush_all()?;
+        self.pager.lock().unwrap().sync()?;
+        self.wal.truncate()
+    }
+
     // Release the lock
     // System knows this page is free to be remove later
-    pub fn unpin_page(&self, page_id: usize, is_dirty: bool) -> bool {
+    // `changed_by`: the transaction that changed the page while it was pinned, if any
+    pub fn unpin_page(&self, page_id: usize, changed_by: Option<TxnId>) -> bool {
         let pf_table = self.pf_table.lock().unwrap();
 
         if let Some(&frame_id) = pf_table.get(&page_id) {
@@ -156,8 +245,9 @@ impl Cache {
             }
 
             frame.pin_count -= 1;
-            if is_dirty {
+            if let Some(txn) = changed_by {
                 frame.is_dirty = true;
+                self.log_changes(&mut frame, txn);
             }
 
             // If pin_count hits 0, this frame is now a candidate for eviction
@@ -170,6 +260,334 @@ impl Cache {
         false
     }
 
+    // Take a frame out of the pool, as working memory (see the Sort executor)
+    // The page it held is written back first. It comes back to the pool when dropped.
+    // Fails once half of the pool is lent out, the rest is left for pages.
+    pub fn borrow_frame(self: &Arc<Self>) -> io::Result<BorrowedFrame> {
+        let mut pf_table = self.pf_table.lock().unwrap();
+        let mut replacer = self.replacer.lock().unwrap();
+
+        if self.borrowed.load(Ordering::Relaxed) >= self.frames.len() / 2 {
+            return Err(io::Error::other(
+                "Buffer pool full: Too many frames borrowed",
+            ));
+        }
+
+        let frame_id = self.find_free_frame(&mut replacer)?;
+        let mut frame = self.frames[frame_id].write().unwrap();
+
+        if let Some(ref old_page) = frame.page {
+            if frame.is_dirty {
+                self.wal.flush(page_lsn(&old_page.data))?;
+                let mut pager = self.pager.lock().unwrap();
+                pager.write_page(old_page)?;
+            }
+
+            pf_table.remove(&old_page.id);
+        }
+
+        // Not in the page table: no page id ever reaches it
+        frame.page = Some(Page {
+            id: usize::MAX,
+            data: [0; PAGE_SIZE],
+        });
+        frame.logged = None;
+        frame.pin_count = 1;
+        frame.is_dirty = false;
+
+        self.borrowed.fetch_add(1, Ordering::Relaxed);
+        Ok(BorrowedFrame {
+            cache: self.clone(),
+            frame_id,
+        })
+    }
+
+    // Frames lent out and not given back yet
+    pub fn borrowed_frames(&self) -> usize {
+        self.borrowed.load(Ordering::Relaxed)
+    }
+
+    // Latch of the B+ tree whose root is this page
+    // Every handle on the tree gets the same one, so their writers exclude each other.
+    pub fn tree_latch(&self, root_page_id: usize) -> Arc<RwLock<()>> {
+        let mut latches = self.tree_latches.lock().unwrap();
+        if let Some(latch) = latches.get(&root_page_id).and_then(Weak::upgrade) {
+            return latch;
+        }
+
+        // Forget the trees nobody has open anymore
+        latches.retain(|_, latch| latch.strong_count() > 0);
+        let latch = Arc::new(RwLock::new(()));
+        latches.insert(root_page_id, Arc::downgrade(&latch));
+        latch
+    }
+
+    // Snapshot of the file header
+    pub fn header(&self) -> FileHeader {
+        self.pager.lock().unwrap().header().clone()
+    }
+
+    // Change fields of the file header for `txn`
+    // Under the allocation latch, like the changes the allocator makes to it
+    pub fn update_header<F>(&self, txn: TxnId, f: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut FileHeader),
+    {
+        let _latch = self.alloc_latch.lock().unwrap();
+        let mut header = self.header();
+        f(&mut header);
+        self.set_header(header, txn)
+    }
+
+    // Persist a new file header, changed by `txn`
+    // Written right away, so its log record is flushed first
+    // The caller holds the allocation latch
+    fn set_header(&self, header: FileHeader, txn: TxnId) -> io::Result<()> {
+        let mut before = [0u8; PAGE_SIZE];
+        self.header().write(&mut before);
+        let mut after = [0u8; PAGE_SIZE];
+        header.write(&mut after);
+
+        let mut lsn = self.pager.lock().unwrap().header_lsn();
+        for (start, end) in changed_ranges(&before, &after) {
+            lsn = self.wal.append_update(
+                txn,
+                HEADER_PAGE_ID as u32,
+                start as u16,
+                before[start..end].to_vec(),
+                after[start..end].to_vec(),
+            );
+        }
+        self.wal.flush(lsn)?;
+
+        self.pager.lock().unwrap().set_header(header, lsn)
+    }
+
+    // Hand out a page id for a new page, its content is zeroed
+    // Reuse a page from the free list if possible, otherwise grow the file
+    // Logged under `txn`: its rollback gives the page back.
+    pub fn allocate_page(&self, txn: TxnId) -> io::Result<usize> {
+        let _latch = self.alloc_latch.lock().unwrap();
+        let end = self.wal.start_operation(txn);
+        let mut header = self.header();
+
+        let page_id = if header.freelist_head != 0 {
+            let trunk_id = header.freelist_head as usize;
+            let frame_arc = self.fetch_page(trunk_id)?;
+            let mut frame = frame_arc.write().unwrap();
+            let page = frame.page.as_mut().unwrap();
+            let mut trunk = FreelistTrunk::new(&mut page.data);
+
+            // Take a leaf first, the trunk itself goes last
+            let page_id = match trunk.pop_leaf() {
+                Some(leaf_id) => leaf_id as usize,
+                None => {
+                    header.freelist_head = trunk.get_next_trunk();
+                    trunk_id
+                }
+            };
+
+            drop(frame);
+            self.unpin_page(trunk_id, Some(txn));
+
+            header.freelist_count -= 1;
+            page_id
+        } else {
+            let page_id = header.page_count as usize;
+            header.page_count += 1;
+            page_id
+        };
+
+        self.set_header(header, txn)?;
+        self.reset_page(page_id, txn)?;
+
+        // The free list may change again before a rollback, which frees the page anew
+        self.wal
+            .end_operation(txn, end, Undo::FreePage(page_id as u32));
+        Ok(page_id)
+    }
+
+    // Give a page back to the free list, logged under `txn`
+    // The caller must not use the page (or keep it pinned) afterwards.
+    // Never undone: only free pages nothing points to anymore, even after a rollback.
+    pub fn deallocate_page(&self, page_id: usize, txn: TxnId) -> io::Result<()> {
+        let _latch = self.alloc_latch.lock().unwrap();
+        let end = self.wal.start_operation(txn);
+        self.push_free_page(page_id, txn)?;
+        self.wal.end_operation(txn, end, Undo::Nothing);
+        Ok(())
+    }
+
+    // Undo of an allocation (see `Undo::FreePage`), rollback goes on at `undo_next`
+    pub fn undo_allocation(&self, page_id: usize, txn: TxnId, undo_next: Lsn) -> io::Result<()> {
+        let _latch = self.alloc_latch.lock().unwrap();
+        self.push_free_page(page_id, txn)?;
+        self.wal
+            .end_operation(txn, OperationEnd::Compensating(undo_next), Undo::Nothing);
+        Ok(())
+    }
+
+    // The caller holds the allocation latch
+    fn push_free_page(&self, page_id: usize, txn: TxnId) -> io::Result<()> {
+        if page_id == HEADER_PAGE_ID {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Page 0 is reserved for the file header",
+            ));
+        }
+
+        let mut header = self.header();
+
+        // Try to record it as a leaf of the current head trunk
+        if header.freelist_head != 0 {
+            let trunk_id = header.freelist_head as usize;
+            let frame_arc = self.fetch_page(trunk_id)?;
+            let mut frame = frame_arc.write().unwrap();
+            let page = frame.page.as_mut().unwrap();
+            let pushed = FreelistTrunk::new(&mut page.data).push_leaf(page_id as u32);
+
+            drop(frame);
+            self.unpin_page(trunk_id, pushed.then_some(txn));
+
+            if pushed {
+                header.freelist_count += 1;
+                return self.set_header(header, txn);
+            }
+        }
+
+        // No head trunk, or it is full: the freed page becomes the new head trunk
+        let frame_arc = self.fetch_page(page_id)?;
+        {
+            let mut frame = frame_arc.write().unwrap();
+            let page = frame.page.as_mut().unwrap();
+            FreelistTrunk::new(&mut page.data).init(header.freelist_head);
+        }
+        self.unpin_page(page_id, Some(txn));
+
+        header.freelist_head = page_id as u32;
+        header.freelist_count += 1;
+        self.set_header(header, txn)
+    }
+
+    // Zero out a page through the buffer pool
+    fn reset_page(&self, page_id: usize, txn: TxnId) -> io::Result<()> {
+        let frame_arc = self.fetch_page(page_id)?;
+        {
+            let mut frame = frame_arc.write().unwrap();
+            if let Some(ref mut page) = frame.page {
+                page.data[..USABLE_SIZE].fill(0);
+            }
+        }
+        self.unpin_page(page_id, Some(txn));
+        Ok(())
+    }
+
+    // pageLSN of a page: the last logged change it holds
+    pub fn page_lsn(&self, page_id: usize) -> io::Result<Lsn> {
+        if page_id == HEADER_PAGE_ID {
+            return Ok(self.pager.lock().unwrap().header_lsn());
+        }
+
+        let frame_arc = self.fetch_page(page_id)?;
+        let lsn = page_lsn(&frame_arc.read().unwrap().page.as_ref().unwrap().data);
+        self.unpin_page(page_id, None);
+        Ok(lsn)
+    }
+
+    // Write bytes of a change that is already in the log (recovery, rollback)
+    // The page gets `lsn` as its pageLSN and is dirty, the change is not logged again.
+    pub fn apply(&self, page_id: usize, offset: usize, bytes: &[u8], lsn: Lsn) -> io::Result<()> {
+        let range = offset..offset + bytes.len();
+        if range.end > USABLE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Log record does not fit in page {}", page_id),
+            ));
+        }
+
+        // The header is written right away, like in `set_header`
+        if page_id == HEADER_PAGE_ID {
+            self.wal.flush(lsn)?;
+            let mut pager = self.pager.lock().unwrap();
+            let mut data = [0u8; PAGE_SIZE];
+            pager.header().write(&mut data);
+            data[range].copy_from_slice(bytes);
+            let header = FileHeader::read(&data)?;
+            return pager.set_header(header, lsn);
+        }
+
+        let frame_arc = self.fetch_page(page_id)?;
+        {
+            let mut frame = frame_arc.write().unwrap();
+            let Frame {
+                page,
+                logged,
+                is_dirty,
+                ..
+            } = &mut *frame;
+            let page = page.as_mut().unwrap();
+            page.data[range.clone()].copy_from_slice(bytes);
+            set_page_lsn(&mut page.data, lsn);
+            if let Some(logged) = logged {
+                logged[range].copy_from_slice(bytes);
+            }
+            *is_dirty = true;
+        }
+        self.unpin_page(page_id, None);
+        Ok(())
+    }
+
+    // Where an operation of `txn` starting now ends (see `LogBody::Operation`)
+    pub fn start_operation(&self, txn: TxnId) -> OperationEnd {
+        self.wal.start_operation(txn)
+    }
+
+    // Log the changes `txn` made to a page it still holds latched, then the end of its operation
+    // Nobody else can change the page in between, so undoing the operation
+    // never has to take back a change of another transaction.
+    // The caller unpins the page without a writer afterwards.
+    pub fn end_operation_latched(
+        &self,
+        frame: &mut Frame,
+        txn: TxnId,
+        end: OperationEnd,
+        undo: Undo,
+    ) {
+        frame.is_dirty = true;
+        self.log_changes(frame, txn);
+        self.wal.end_operation(txn, end, undo);
+    }
+
+    // Log the end of an operation whose page changes are all logged already
+    // The caller holds a latch keeping other writers off those pages (e.g. the tree latch).
+    pub fn end_operation(&self, txn: TxnId, end: OperationEnd, undo: Undo) {
+        self.wal.end_operation(txn, end, undo);
+    }
+
+    // Log the bytes changed since the last log record of the page, and stamp its pageLSN
+    fn log_changes(&self, frame: &mut Frame, txn: TxnId) {
+        let Frame {
+            page: Some(page),
+            logged: Some(logged),
+            ..
+        } = frame
+        else {
+            return;
+        };
+
+        for (start, end) in changed_ranges(logged.as_ref(), &page.data) {
+            let lsn = self.wal.append_update(
+                txn,
+                page.id as u32,
+                start as u16,
+                logged[start..end].to_vec(),
+                page.data[start..end].to_vec(),
+            );
+            set_page_lsn(&mut page.data, lsn);
+            logged[start..end].copy_from_slice(&page.data[start..end]);
+        }
+    }
+
     // Find a free frame or remove a victim
     fn find_free_frame(&self, replacer: &mut LRUReplacer) -> io::Result<usize> {
         // Cheapest: try from free_list
@@ -183,9 +601,58 @@ impl Cache {
             return Ok(victim_id);
         }
 
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            "Buffer pool full: All pages are pinned",
-        ))
+        Err(io::Error::other("Buffer pool full: All pages are pinned"))
+    }
+}
+
+// A frame lent out by `Cache::borrow_frame`
+// Its bytes are never logged, nor written to the database file.
+pub struct BorrowedFrame {
+    cache: Arc<Cache>,
+    frame_id: usize,
+}
+
+impl BorrowedFrame {
+    // Run `f` on the bytes of the frame
+    pub fn with_data<R>(&self, f: impl FnOnce(&mut PageData) -> R) -> R {
+        let mut frame = self.cache.frames[self.frame_id].write().unwrap();
+        f(&mut frame.page.as_mut().unwrap().data)
+    }
+}
+
+impl Drop for BorrowedFrame {
+    fn drop(&mut self) {
+        let mut frame = self.cache.frames[self.frame_id].write().unwrap();
+        frame.page = None;
+        frame.pin_count = 0;
+        drop(frame);
+
+        self.cache.free_list.lock().unwrap().push(self.frame_id);
+        self.cache.borrowed.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Unchanged bytes worth less than a log record of their own
+const MIN_GAP: usize = 64;
+
+// Byte ranges where two versions of a page differ, pageLSN excluded
+// An insert changes both ends of a slotted page: log the two ends, not the whole page.
+fn changed_ranges(before: &PageData, after: &PageData) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < USABLE_SIZE {
+        if before[i] == after[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < USABLE_SIZE && before[i] != after[i] {
+            i += 1;
+        }
+        match ranges.last_mut() {
+            Some(last) if start - last.1 < MIN_GAP => last.1 = i,
+            _ => ranges.push((start, i)),
+        }
     }
+    ranges
 }
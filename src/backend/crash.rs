@@ -0,0 +1,123 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs, io,
+};
+
+use crate::backend::storage::{DiskStorage, Storage, StorageFile};
+
+// Simulated crashes, for the recovery tests
+//
+// Files opened through `CrashStorage` count their writes (syncs and truncations included).
+// A test allows a number of them. Right before the next one, the files are copied as they are:
+// what a process that died at that point leaves behind.
+// The code goes on normally, `restart` then puts the copies back.
+// Only the calling thread is affected.
+thread_local! {
+    static WRITES_LEFT: Cell<Option<usize>> = const { Cell::new(None) };
+    // Writes since `crash_after`, until the restart
+    static WRITES: Cell<usize> = const { Cell::new(0) };
+    // Database and log files opened since the last restart
+    static FILES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static CRASHED: Cell<bool> = const { Cell::new(false) };
+}
+
+// The file system, with its writes counted
+pub struct CrashStorage;
+
+impl Storage for CrashStorage {
+    fn open(&self, path: &str) -> io::Result<Box<dyn StorageFile>> {
+        let file = DiskStorage.open(path)?;
+        register(path);
+        Ok(Box::new(CrashFile { file }))
+    }
+}
+
+#[derive(Debug)]
+struct CrashFile {
+    file: Box<dyn StorageFile>,
+}
+
+impl StorageFile for CrashFile {
+    fn len(&self) -> io::Result<u64> {
+        self.file.len()
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.file.read_at(offset, buf)
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        before_write();
+        self.file.write_at(offset, data)
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        before_write();
+        self.file.set_len(len)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        before_write();
+        self.file.sync()
+    }
+}
+
+fn register(path: &str) {
+    FILES.with_borrow_mut(|files| {
+        if !files.iter().any(|file| file == path) {
+            files.push(path.to_string());
+        }
+    });
+}
+
+// Crash right before the write after the next `writes`
+pub fn crash_after(writes: usize) {
+    WRITES_LEFT.set(Some(writes));
+    WRITES.set(0);
+    CRASHED.set(false);
+}
+
+// Did the crash happen?
+pub fn crashed() -> bool {
+    CRASHED.get()
+}
+
+// Writes counted between `crash_after` and the crash or the restart
+pub fn writes() -> usize {
+    WRITES.get()
+}
+
+fn before_write() {
+    match WRITES_LEFT.get() {
+        None => {}
+        Some(0) => {
+            WRITES_LEFT.set(None);
+            CRASHED.set(true);
+            FILES.with_borrow(|files| {
+                for path in files {
+                    fs::copy(path, image(path)).unwrap();
+                }
+            });
+        }
+        Some(n) => {
+            WRITES_LEFT.set(Some(n - 1));
+            WRITES.set(WRITES.get() + 1);
+        }
+    }
+}
+
+// The files as the crash left them, for the next process to open
+pub fn restart() {
+    let files = FILES.take();
+    if CRASHED.get() {
+        for path in &files {
+            fs::rename(image(path), path).unwrap();
+        }
+    }
+    WRITES_LEFT.set(None);
+    CRASHED.set(false);
+}
+
+fn image(path: &str) -> String {
+    format!("{}-crash", path)
+}
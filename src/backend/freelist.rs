@@ -0,0 +1,78 @@
+use crate::backend::pager::{PageData, USABLE_SIZE};
+
+// Free-list trunk page layout
+// Bytes 0-3: ID of the next trunk page (0 = last trunk)
+// Bytes 4-7: How many leaf page IDs this trunk holds
+// Bytes 8-..: Leaf page IDs (4 bytes each)
+const OFFSET_NEXT_TRUNK: usize = 0;
+const OFFSET_LEAF_COUNT: usize = 4;
+const HEADER_SIZE: usize = 8;
+
+pub const MAX_LEAVES: usize = (USABLE_SIZE - HEADER_SIZE) / 4;
+
+// Free pages are kept in a linked list of trunk pages (like SQLite)
+// Each trunk is itself a free page, and remembers a batch of other free pages (leaves)
+// Head trunk -> [leaf, leaf, ...]
+//     |
+//     v
+// Next trunk -> [leaf, leaf, ...]
+pub struct FreelistTrunk<'a> {
+    data: &'a mut PageData,
+}
+
+impl<'a> FreelistTrunk<'a> {
+    pub fn new(data: &'a mut PageData) -> Self {
+        Self { data }
+    }
+
+    // Turn a page into an empty trunk
+    pub fn init(&mut self, next_trunk: u32) {
+        self.data[..USABLE_SIZE].fill(0);
+        self.write_u32(OFFSET_NEXT_TRUNK, next_trunk);
+        self.write_u32(OFFSET_LEAF_COUNT, 0);
+    }
+
+    pub fn get_next_trunk(&self) -> u32 {
+        self.read_u32(OFFSET_NEXT_TRUNK)
+    }
+
+    pub fn get_leaf_count(&self) -> usize {
+        self.read_u32(OFFSET_LEAF_COUNT) as usize
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.get_leaf_count() >= MAX_LEAVES
+    }
+
+    // Returns false if the trunk has no room left
+    pub fn push_leaf(&mut self, page_id: u32) -> bool {
+        let count = self.get_leaf_count();
+        if count >= MAX_LEAVES {
+            return false;
+        }
+
+        self.write_u32(HEADER_SIZE + count * 4, page_id);
+        self.write_u32(OFFSET_LEAF_COUNT, (count + 1) as u32);
+        true
+    }
+
+    pub fn pop_leaf(&mut self) -> Option<u32> {
+        let count = self.get_leaf_count();
+        if count == 0 {
+            return None;
+        }
+
+        let page_id = self.read_u32(HEADER_SIZE + (count - 1) * 4);
+        self.write_u32(OFFSET_LEAF_COUNT, (count - 1) as u32);
+        Some(page_id)
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        let bytes = &self.data[offset..offset + 4];
+        u32::from_ne_bytes(bytes.try_into().unwrap())
+    }
+
+    fn write_u32(&mut self, offset: usize, value: u32) {
+        self.data[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+    }
+}
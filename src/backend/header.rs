@@ -0,0 +1,132 @@
+use std::io;
+
+use crate::backend::pager::{PAGE_SIZE, PageData};
+
+// Every database file starts with this string
+pub const MAGIC: &[u8; 16] = b"mysqlite format\0";
+
+// Bump this whenever the on-disk layout changes
+pub const FORMAT_VERSION: u32 = 4;
+
+// Page 0 is always the file header
+pub const HEADER_PAGE_ID: usize = 0;
+
+// File header layout (page 0)
+// Bytes 0-15: Magic string
+// Bytes 16-19: Format version
+// Bytes 20-23: Page size
+// Bytes 24-27: How many pages the file has (including page 0)
+// Bytes 28-31: ID of the first free-list trunk page (0 = empty)
+// Bytes 32-35: How many pages are on the free list
+// Bytes 36-39: ID of the catalog root page (0 = no catalog yet)
+// Bytes 40-43: Concurrency control (0 = two-phase locking, 1 = MVCC)
+const OFFSET_MAGIC: usize = 0;
+const OFFSET_VERSION: usize = 16;
+const OFFSET_PAGE_SIZE: usize = 20;
+const OFFSET_PAGE_COUNT: usize = 24;
+const OFFSET_FREELIST_HEAD: usize = 28;
+const OFFSET_FREELIST_COUNT: usize = 32;
+const OFFSET_CATALOG_ROOT: usize = 36;
+const OFFSET_CONCURRENCY: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHeader {
+    pub version: u32,
+    pub page_size: u32,
+    pub page_count: u32,
+    pub freelist_head: u32,
+    pub freelist_count: u32,
+    pub catalog_root: u32,
+    pub concurrency: u32,
+}
+
+impl FileHeader {
+    // Header of a brand new file: only page 0 exists
+    pub fn new() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            page_size: PAGE_SIZE as u32,
+            page_count: 1,
+            freelist_head: 0,
+            freelist_count: 0,
+            catalog_root: 0,
+            concurrency: 0,
+        }
+    }
+
+    // Parse and validate the header stored in page 0
+    // Refuse files that were not written by us, or by an incompatible build
+    pub fn read(data: &PageData) -> io::Result<Self> {
+        if &data[OFFSET_MAGIC..OFFSET_MAGIC + MAGIC.len()] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "File is not a mysqlite database",
+            ));
+        }
+
+        let header = Self {
+            version: read_u32(data, OFFSET_VERSION),
+            page_size: read_u32(data, OFFSET_PAGE_SIZE),
+            page_count: read_u32(data, OFFSET_PAGE_COUNT),
+            freelist_head: read_u32(data, OFFSET_FREELIST_HEAD),
+            freelist_count: read_u32(data, OFFSET_FREELIST_COUNT),
+            catalog_root: read_u32(data, OFFSET_CATALOG_ROOT),
+            concurrency: read_u32(data, OFFSET_CONCURRENCY),
+        };
+
+        if header.version != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unsupported file format version {} (expected {})",
+                    header.version, FORMAT_VERSION
+                ),
+            ));
+        }
+
+        if header.page_size as usize != PAGE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unsupported page size {} (expected {})",
+                    header.page_size, PAGE_SIZE
+                ),
+            ));
+        }
+
+        if header.page_count == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Corrupted header: page count is 0",
+            ));
+        }
+
+        Ok(header)
+    }
+
+    // Serialize the header into page 0
+    pub fn write(&self, data: &mut PageData) {
+        data[OFFSET_MAGIC..OFFSET_MAGIC + MAGIC.len()].copy_from_slice(MAGIC);
+        write_u32(data, OFFSET_VERSION, self.version);
+        write_u32(data, OFFSET_PAGE_SIZE, self.page_size);
+        write_u32(data, OFFSET_PAGE_COUNT, self.page_count);
+        write_u32(data, OFFSET_FREELIST_HEAD, self.freelist_head);
+        write_u32(data, OFFSET_FREELIST_COUNT, self.freelist_count);
+        write_u32(data, OFFSET_CATALOG_ROOT, self.catalog_root);
+        write_u32(data, OFFSET_CONCURRENCY, self.concurrency);
+    }
+}
+
+impl Default for FileHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_u32(data: &PageData, offset: usize) -> u32 {
+    u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u32(data: &mut PageData, offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+}
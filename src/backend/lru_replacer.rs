@@ -39,3 +39,9 @@ impl LRUReplacer {
         self.free_frames.pop_front()
     }
 }
+
+impl Default for LRUReplacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
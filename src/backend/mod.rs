@@ -1,3 +1,9 @@
 pub mod cache;
+#[cfg(test)]
+pub mod crash;
+pub mod freelist;
+pub mod header;
 pub mod lru_replacer;
 pub mod pager;
+pub mod storage;
+pub mod temp_file;
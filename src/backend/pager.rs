@@ -1,5 +1,9 @@
-use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::io;
+
+use crate::backend::{
+    header::{FileHeader, HEADER_PAGE_ID},
+    storage::{DiskStorage, Storage, StorageFile},
+};
 
 // Page size is 4096 bytes (4KB)
 pub const PAGE_SIZE: usize = 4096;
@@ -7,6 +11,21 @@ pub const PAGE_SIZE: usize = 4096;
 // Page data includes PAGE_SIZE bytes (u8 = 1 byte)
 pub type PageData = [u8; PAGE_SIZE];
 
+// The last 8 bytes of every page hold its pageLSN:
+// the LSN of the last log record that changed the page (see `acid::wal`)
+pub const PAGE_LSN_OFFSET: usize = PAGE_SIZE - 8;
+
+// Bytes of a page the page layouts may use, everything before the pageLSN
+pub const USABLE_SIZE: usize = PAGE_LSN_OFFSET;
+
+pub fn page_lsn(data: &PageData) -> u64 {
+    u64::from_ne_bytes(data[PAGE_LSN_OFFSET..].try_into().unwrap())
+}
+
+pub fn set_page_lsn(data: &mut PageData, lsn: u64) {
+    data[PAGE_LSN_OFFSET..].copy_from_slice(&lsn.to_ne_bytes());
+}
+
 #[derive(Debug)]
 pub struct Page {
     pub id: usize,
@@ -16,27 +35,76 @@ pub struct Page {
 // Pager
 // Responsible for persist / reading data from disk
 // Reading data via Page
+// Page 0 always holds the file header, owned by the Pager
 #[derive(Debug)]
 pub struct Pager {
-    file: File,
+    file: Box<dyn StorageFile>,
+    header: FileHeader,
+
+    // pageLSN of page 0: the header is logged like any other page
+    header_lsn: u64,
 }
 
 impl Pager {
+    // Open (or create) a database file
+    // A new file gets a fresh header, an existing one must pass validation
     pub fn new(filename: &str) -> io::Result<Self> {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(filename)?;
+        Self::open_in(&DiskStorage, filename)
+    }
+
+    // The same, with a file of `storage`
+    pub fn open_in(storage: &dyn Storage, filename: &str) -> io::Result<Self> {
+        let file = storage.open(filename)?;
+        let mut pager = Self {
+            file,
+            header: FileHeader::new(),
+            header_lsn: 0,
+        };
+
+        if pager.file.is_empty()? {
+            pager.write_header()?;
+        } else {
+            let page = pager.read_page(HEADER_PAGE_ID)?;
+            pager.header = FileHeader::read(&page.data)?;
+            pager.header_lsn = page_lsn(&page.data);
+        }
 
-        Ok(Self { file })
+        Ok(pager)
+    }
+
+    pub fn header(&self) -> &FileHeader {
+        &self.header
+    }
+
+    pub fn header_lsn(&self) -> u64 {
+        self.header_lsn
+    }
+
+    // Replace the header and persist it immediately
+    // `lsn` is the log record of the change, the log must be flushed up to it
+    pub fn set_header(&mut self, header: FileHeader, lsn: u64) -> io::Result<()> {
+        self.header = header;
+        self.header_lsn = lsn;
+        self.write_header()
+    }
+
+    // Force everything written so far to disk
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync()
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut data = [0u8; PAGE_SIZE];
+        self.header.write(&mut data);
+        set_page_lsn(&mut data, self.header_lsn);
+
+        self.file.write_at(0, &data)
     }
 
     // Get the total number of pages
     // currently in the file
     pub fn page_count(&self) -> io::Result<usize> {
-        let metadata = self.file.metadata()?;
-        let len = metadata.len();
+        let len = self.file.len()?;
         let page_count = (len as usize) / PAGE_SIZE;
         Ok(page_count)
     }
@@ -60,21 +128,28 @@ impl Pager {
 
         let offset = (page_id * PAGE_SIZE) as u64;
 
-        self.file.seek(SeekFrom::Start(offset))?;
         let mut data = [0u8; PAGE_SIZE];
-        self.file.read_exact(&mut data)?;
+        self.file.read_at(offset, &mut data)?;
 
         Ok(Page { id: page_id, data })
     }
 
     // Write page
     // Reverse to the `read_page`
+    // Page 0 can only be changed through `set_header`
     pub fn write_page(&mut self, page: &Page) -> io::Result<()> {
-        let offset = (page.id * PAGE_SIZE) as u64;
+        if page.id == HEADER_PAGE_ID {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Page 0 is reserved for the file header",
+            ));
+        }
 
-        self.file.seek(SeekFrom::Start(offset))?;
-        self.file.write_all(&page.data)?;
+        // The header's page count is not raised here: it only changes in `Cache::allocate_page`,
+        // where it is logged. A page whose allocation was rolled back may still be written
+        // back; it lies beyond the count and is handed out again by the next allocation.
+        let offset = (page.id * PAGE_SIZE) as u64;
 
-        Ok(())
+        self.file.write_at(offset, &page.data)
     }
 }
@@ -0,0 +1,71 @@
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+// Where the database file and its log are kept
+// Every read and write of the Pager and the Wal goes through it.
+// `DiskStorage` is the file system, the recovery tests wrap it to simulate crashes.
+pub trait Storage {
+    // Open (or create) a file
+    fn open(&self, path: &str) -> io::Result<Box<dyn StorageFile>>;
+}
+
+// An open file of a Storage
+pub trait StorageFile: fmt::Debug + Send {
+    fn len(&self) -> io::Result<u64>;
+
+    // Fill `buf` with the bytes at `offset`, all of them must exist
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()>;
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()>;
+
+    // Cut (or extend) the file
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+
+    // Force everything written so far to disk
+    fn sync(&self) -> io::Result<()>;
+
+    fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+pub struct DiskStorage;
+
+impl Storage for DiskStorage {
+    fn open(&self, path: &str) -> io::Result<Box<dyn StorageFile>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Ok(Box::new(file))
+    }
+}
+
+impl StorageFile for File {
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.read_exact(buf)
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.write_all(data)
+    }
+
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        File::set_len(self, len)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.sync_data()
+    }
+}
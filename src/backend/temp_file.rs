@@ -0,0 +1,78 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::backend::pager::{PAGE_SIZE, PageData};
+
+// Tells apart the temporary files of one process
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Temporary pages for operators that spill to disk (see the Sort executor)
+// They live in a scratch file of the system temp directory, outside the database:
+// they are never logged, never grow the database file, and the file is deleted when dropped.
+pub struct TempFile {
+    file: File,
+    path: PathBuf,
+    // Pages ever allocated, the file holds at most this many
+    page_count: usize,
+    // Freed pages, handed out again before the file grows
+    free_pages: Vec<usize>,
+}
+
+impl TempFile {
+    pub fn new() -> io::Result<Self> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("mysqlite-{}-{}.tmp", process::id(), id));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(Self {
+            file,
+            path,
+            page_count: 0,
+            free_pages: Vec::new(),
+        })
+    }
+
+    pub fn allocate_page(&mut self) -> usize {
+        self.free_pages.pop().unwrap_or_else(|| {
+            self.page_count += 1;
+            self.page_count - 1
+        })
+    }
+
+    pub fn free_page(&mut self, page_no: usize) {
+        self.free_pages.push(page_no);
+    }
+
+    // Pages allocated and not freed
+    pub fn pages_in_use(&self) -> usize {
+        self.page_count - self.free_pages.len()
+    }
+
+    pub fn write_page(&mut self, page_no: usize, data: &PageData) -> io::Result<()> {
+        self.file
+            .seek(SeekFrom::Start((page_no * PAGE_SIZE) as u64))?;
+        self.file.write_all(data)
+    }
+
+    pub fn read_page(&mut self, page_no: usize, data: &mut PageData) -> io::Result<()> {
+        self.file
+            .seek(SeekFrom::Start((page_no * PAGE_SIZE) as u64))?;
+        self.file.read_exact(data)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
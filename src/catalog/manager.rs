@@ -0,0 +1,378 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    acid::{
+        log_record::{SYSTEM_TXN, TxnId},
+        transaction::{Dropped, Transaction, WriteKind},
+    },
+    backend::cache::Cache,
+    catalog::schema::{Column, Schema},
+    frontend::{ast::Statement, parser::Parser, token::Keyword},
+    indexing::{table_heap::TableHeap, table_iterator::TableIterator},
+    row::Row,
+    types::{DataType, Value},
+};
+
+// The catalog itself, readable with SELECT like any other table
+pub const MASTER_TABLE: &str = "mysqlite_master";
+
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub name: String,
+    pub schema: Schema,
+    pub first_page_id: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexInfo {
+    pub name: String,
+    pub table_name: String,
+    pub columns: Vec<String>,
+    pub root_page_id: usize,
+}
+
+// System catalog (like `sqlite_master`)
+// Every table / index is one row of the `mysqlite_master` table, kept in a dedicated
+// TableHeap whose first page is remembered in the file header.
+// Entries are loaded into memory when the catalog is opened.
+//
+// Columns of `mysqlite_master`
+// type: 'table' or 'index'
+// name: name of the table / index
+// tbl_name: table an index is built on, for a table its own name
+// rootpage: first page of a table, root page of an index
+// sql: the CREATE statement, parsed again to load the columns
+//
+// Changes are made by the transaction passed in, and logged under it.
+#[derive(Clone)]
+pub struct Catalog {
+    cache: Arc<Cache>,
+    master: TableInfo,
+
+    // Keyed by lowercase name, SQL names are case-insensitive
+    tables: HashMap<String, TableInfo>,
+    indexes: HashMap<String, IndexInfo>,
+}
+
+impl Catalog {
+    // Load the catalog, or create an empty one on a new database
+    pub fn open(cache: Arc<Cache>) -> Result<Self, String> {
+        let header = cache.header();
+
+        if header.catalog_root == 0 {
+            let heap = TableHeap::create(cache.clone(), SYSTEM_TXN)?;
+
+            cache
+                .update_header(SYSTEM_TXN, |header| {
+                    header.catalog_root = heap.first_page_id() as u32
+                })
+                .map_err(|e| format!("Failed to write header: {}", e))?;
+
+            return Ok(Self {
+                cache,
+                master: master_table(heap.first_page_id()),
+                tables: HashMap::new(),
+                indexes: HashMap::new(),
+            });
+        }
+
+        let first_page_id = header.catalog_root as usize;
+        let heap = Arc::new(TableHeap::new(cache.clone(), first_page_id, SYSTEM_TXN));
+        let master = master_table(first_page_id);
+
+        let mut tables = HashMap::new();
+        let mut indexes = HashMap::new();
+        for entry in TableIterator::new(heap.clone(), first_page_id) {
+            let (_, tuple) = entry?;
+            let row = Row::deserialize(&tuple, &master.schema)?;
+            match decode_entry(&row)? {
+                Entry::Table(info) => {
+                    tables.insert(info.name.to_lowercase(), info);
+                }
+                Entry::Index(info) => {
+                    indexes.insert(info.name.to_lowercase(), info);
+                }
+            }
+        }
+
+        Ok(Self {
+            cache,
+            master,
+            tables,
+            indexes,
+        })
+    }
+
+    pub fn create_table(
+        &mut self,
+        txn: TxnId,
+        name: &str,
+        schema: Schema,
+    ) -> Result<&TableInfo, String> {
+        let key = name.to_lowercase();
+        if self.get_table(name).is_some() || self.indexes.contains_key(&key) {
+            return Err(format!("Table '{}' already exists", name));
+        }
+
+        let table_heap = TableHeap::create(self.cache.clone(), txn)?;
+        let info = TableInfo {
+            name: name.to_string(),
+            schema,
+            first_page_id: table_heap.first_page_id(),
+        };
+
+        self.insert_entry(txn, &Entry::Table(info.clone()))?;
+        Ok(self.tables.entry(key).or_insert(info))
+    }
+
+    // Register an index whose root page was already set up by the caller
+    pub fn create_index(
+        &mut self,
+        txn: TxnId,
+        name: &str,
+        table_name: &str,
+        columns: Vec<String>,
+        root_page_id: usize,
+    ) -> Result<&IndexInfo, String> {
+        let key = name.to_lowercase();
+        if self.get_table(name).is_some() || self.indexes.contains_key(&key) {
+            return Err(format!("Index '{}' already exists", name));
+        }
+        if is_master(table_name) {
+            return Err(format!("Table '{}' may not be indexed", MASTER_TABLE));
+        }
+
+        let table = self
+            .get_table(table_name)
+            .ok_or_else(|| format!("No such table: {}", table_name))?;
+        for column in &columns {
+            if table.schema.column_index(column).is_none() {
+                return Err(format!("Table '{}' has no column '{}'", table.name, column));
+            }
+        }
+
+        let info = IndexInfo {
+            name: name.to_string(),
+            table_name: table.name.clone(),
+            columns,
+            root_page_id,
+        };
+
+        self.insert_entry(txn, &Entry::Index(info.clone()))?;
+        Ok(self.indexes.entry(key).or_insert(info))
+    }
+
+    // Remove a table with its indexes, their pages are freed after the commit,
+    // once no snapshot reads them anymore
+    // The in-memory entries go last, once nothing on disk can fail anymore.
+    pub fn drop_table(&mut self, txn: &Transaction, name: &str) -> Result<(), String> {
+        if is_master(name) {
+            return Err(format!("Table '{}' may not be dropped", MASTER_TABLE));
+        }
+        let key = name.to_lowercase();
+        let table = self
+            .tables
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| format!("No such table: {}", name))?;
+        let indexes: Vec<IndexInfo> = self
+            .table_indexes(&table.name)
+            .into_iter()
+            .cloned()
+            .collect();
+
+        // Catalog entries of the table and its indexes
+        let heap = self.heap(txn.id());
+        let mut rids = Vec::new();
+        for entry in TableIterator::new(heap.clone(), heap.first_page_id()) {
+            let (rid, tuple) = entry?;
+            let row = Row::deserialize(&tuple, &self.master.schema)?;
+            if matches!(&row.values[2], Value::Text(t) if t.eq_ignore_ascii_case(&table.name)) {
+                rids.push(rid);
+            }
+        }
+        // Deleted rows wait for the garbage collector like those of any table
+        for (page_id, slot_id) in rids {
+            heap.delete(page_id, slot_id)?;
+            txn.record_write(WriteKind::Delete, heap.first_page_id(), (page_id, slot_id));
+        }
+
+        for index in &indexes {
+            txn.drop_on_commit(Dropped::Tree(index.root_page_id));
+        }
+        txn.drop_on_commit(Dropped::Table(table.first_page_id));
+
+        self.tables.remove(&key);
+        for index in &indexes {
+            self.indexes.remove(&index.name.to_lowercase());
+        }
+        Ok(())
+    }
+
+    // `mysqlite_master` included, it cannot be changed with SQL
+    pub fn get_table(&self, name: &str) -> Option<&TableInfo> {
+        if is_master(name) {
+            return Some(&self.master);
+        }
+        self.tables.get(&name.to_lowercase())
+    }
+
+    pub fn is_system_table(&self, name: &str) -> bool {
+        is_master(name)
+    }
+
+    pub fn get_index(&self, name: &str) -> Option<&IndexInfo> {
+        self.indexes.get(&name.to_lowercase())
+    }
+
+    // All tables created with SQL, sorted by name
+    pub fn tables(&self) -> Vec<&TableInfo> {
+        let mut tables: Vec<&TableInfo> = self.tables.values().collect();
+        tables.sort_by(|a, b| a.name.cmp(&b.name));
+        tables
+    }
+
+    // All indexes built on a table, sorted by name
+    pub fn table_indexes(&self, table_name: &str) -> Vec<&IndexInfo> {
+        let mut indexes: Vec<&IndexInfo> = self
+            .indexes
+            .values()
+            .filter(|i| i.table_name.eq_ignore_ascii_case(table_name))
+            .collect();
+        indexes.sort_by(|a, b| a.name.cmp(&b.name));
+        indexes
+    }
+
+    fn insert_entry(&self, txn: TxnId, entry: &Entry) -> Result<(), String> {
+        let bytes = encode_entry(entry).serialize(&self.master.schema)?;
+        self.heap(txn).insert(&bytes)?;
+        Ok(())
+    }
+
+    // The `mysqlite_master` heap, changed by `txn`
+    fn heap(&self, txn: TxnId) -> Arc<TableHeap> {
+        Arc::new(TableHeap::new(
+            self.cache.clone(),
+            self.master.first_page_id,
+            txn,
+        ))
+    }
+}
+
+enum Entry {
+    Table(TableInfo),
+    Index(IndexInfo),
+}
+
+fn is_master(name: &str) -> bool {
+    name.eq_ignore_ascii_case(MASTER_TABLE)
+}
+
+fn master_table(first_page_id: usize) -> TableInfo {
+    TableInfo {
+        name: MASTER_TABLE.to_string(),
+        schema: Schema::new(vec![
+            Column::new("type", DataType::Text),
+            Column::new("name", DataType::Text),
+            Column::new("tbl_name", DataType::Text),
+            Column::new("rootpage", DataType::Integer),
+            Column::new("sql", DataType::Text),
+        ]),
+        first_page_id,
+    }
+}
+
+fn encode_entry(entry: &Entry) -> Row {
+    let (kind, name, table_name, root_page_id, sql) = match entry {
+        Entry::Table(info) => {
+            let columns: Vec<String> = info
+                .schema
+                .columns
+                .iter()
+                .map(|c| format!("{} {}", quote(&c.name), c.data_type))
+                .collect();
+            let sql = format!(
+                "CREATE TABLE {} ({})",
+                quote(&info.name),
+                columns.join(", ")
+            );
+            ("table", &info.name, &info.name, info.first_page_id, sql)
+        }
+        Entry::Index(info) => {
+            let columns: Vec<String> = info.columns.iter().map(|c| quote(c)).collect();
+            let sql = format!(
+                "CREATE INDEX {} ON {} ({})",
+                quote(&info.name),
+                quote(&info.table_name),
+                columns.join(", ")
+            );
+            (
+                "index",
+                &info.name,
+                &info.table_name,
+                info.root_page_id,
+                sql,
+            )
+        }
+    };
+
+    Row::new(vec![
+        Value::Text(kind.to_string()),
+        Value::Text(name.clone()),
+        Value::Text(table_name.clone()),
+        Value::Integer(root_page_id as i64),
+        Value::Text(sql),
+    ])
+}
+
+fn decode_entry(row: &Row) -> Result<Entry, String> {
+    let corrupted = || "Corrupted catalog: malformed entry".to_string();
+    let [
+        _,
+        Value::Text(name),
+        _,
+        Value::Integer(root_page_id),
+        Value::Text(sql),
+    ] = row.values.as_slice()
+    else {
+        return Err(corrupted());
+    };
+    let root_page_id = *root_page_id as usize;
+
+    let statement = Parser::parse(sql)
+        .map_err(|e| format!("Corrupted catalog: {}", e.render(sql)))?
+        .pop()
+        .ok_or_else(corrupted)?;
+    match statement {
+        Statement::CreateTable { columns, .. } => Ok(Entry::Table(TableInfo {
+            name: name.clone(),
+            schema: Schema::new(
+                columns
+                    .iter()
+                    .map(|c| Column::new(&c.name, c.data_type))
+                    .collect(),
+            ),
+            first_page_id: root_page_id,
+        })),
+        Statement::CreateIndex { table, columns, .. } => Ok(Entry::Index(IndexInfo {
+            name: name.clone(),
+            table_name: table,
+            columns,
+            root_page_id,
+        })),
+        _ => Err(corrupted()),
+    }
+}
+
+// Names that would not read back as a single identifier are quoted
+fn quote(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        && Keyword::from_word(name).is_none();
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
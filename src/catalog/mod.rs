@@ -0,0 +1,2 @@
+pub mod manager;
+pub mod schema;
@@ -1,6 +1,7 @@
-use std::process::exit;
-
-use crate::{indexing::btree::BTreeTable, row::Row, types::Value};
+use crate::{
+    acid::mvcc::Concurrency,
+    database::{Database, QueryResult},
+};
 
 #[derive(Debug)]
 pub enum Command {
@@ -22,68 +23,72 @@ pub fn parse_command(input: &str) -> Command {
     Command::Statement(trimmed.to_string())
 }
 
-pub fn execute_command(command: Command, table: &mut BTreeTable) {
+// Returns false when the REPL should stop
+pub fn execute_command(command: Command, db: &mut Database) -> bool {
     match command {
         Command::Meta(cmd) => match cmd.as_str() {
-            "exit" => {
+            ".exit" => {
                 println!("Bye!");
-                exit(0);
-            }
-            _ => {
-                println!("Meta-command not recognized: {}", cmd);
+                return false;
             }
-        },
-        Command::Statement(sql) => {
-            let parts: Vec<&str> = sql.split_whitespace().collect();
-            if parts.is_empty() {
-                return;
+            ".tables" => {
+                for table in db.catalog().tables() {
+                    println!("{}", table.name);
+                }
             }
-
-            match parts[0].to_lowercase().as_str() {
-                "insert" => {
-                    if parts.len() - 1 != table.schema.columns.len() {
-                        println!(
-                            "Error: Expected {} values, but got {}",
-                            table.schema.columns.len(),
-                            parts.len() - 1
-                        );
-                        return;
-                    }
-
-                    let mut values = Vec::new();
-                    for (i, col) in table.schema.columns.iter().enumerate() {
-                        let val_str = parts[i + 1];
-                        let val = match col.data_type {
-                            crate::types::DataType::Integer => {
-                                Value::Integer(val_str.parse().unwrap_or(0))
-                            }
-                            crate::types::DataType::Varchar => Value::Varchar(val_str.to_string()),
-                            crate::types::DataType::Boolean => {
-                                Value::Boolean(val_str == "true" || val_str == "1")
-                            }
-                        };
-                        values.push(val);
-                    }
-
-                    let row = Row::new(values);
-                    table.insert(row);
-                    println!("Executed insert.");
+            // .memory [bytes]: show or set the memory budget of sorts, joins and aggregations
+            cmd if cmd.split_whitespace().next() == Some(".memory") => {
+                match cmd.split_whitespace().nth(1).map(|n| n.parse::<usize>()) {
+                    None => println!("{} bytes", db.memory_budget()),
+                    Some(Ok(bytes)) => db.set_memory_budget(bytes),
+                    Some(Err(_)) => println!("Usage: .memory [bytes]"),
                 }
-                "select" => {
-                    let rows = table.select_all();
-                    if rows.is_empty() {
-                        println!("Table is empty")
-                    } else {
-                        for row in rows {
-                            println!("{:?}", row.values);
+            }
+            // .concurrency [locking|mvcc]: show or set how transactions are kept apart
+            cmd if cmd.split_whitespace().next() == Some(".concurrency") => {
+                match cmd.split_whitespace().nth(1).map(Concurrency::parse) {
+                    None => println!("{}", db.concurrency()),
+                    Some(Some(mode)) => {
+                        if let Err(e) = db.set_concurrency(mode) {
+                            println!("Error: {}", e);
                         }
                     }
+                    Some(None) => println!("Usage: .concurrency [locking|mvcc]"),
                 }
-                _ => {
-                    println!("Unrecognized statement: '{}'", sql);
+            }
+            _ => {
+                println!("Meta-command not recognized: {}", cmd);
+            }
+        },
+        Command::Statement(sql) => {
+            for result in db.execute(&sql) {
+                match result {
+                    Ok(result) => print_result(result),
+                    Err(e) => println!("Error: {}", e),
                 }
             }
         }
         Command::Empty => {}
     }
+    true
+}
+
+fn print_result(result: QueryResult) {
+    match result {
+        QueryResult::Rows { columns, rows } => {
+            println!("{}", columns.join(" | "));
+            for row in &rows {
+                let values: Vec<String> = row.values.iter().map(|v| v.to_string()).collect();
+                println!("{}", values.join(" | "));
+            }
+            println!("({} rows)", rows.len());
+        }
+        QueryResult::Affected(count) => println!("{} rows affected.", count),
+        QueryResult::Done => println!("Done."),
+        QueryResult::Plan(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+    }
 }
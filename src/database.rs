@@ -0,0 +1,572 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Instant,
+};
+
+use crate::{
+    acid::{
+        lock_manager::LockMode,
+        log_record::SYSTEM_TXN,
+        mvcc::Concurrency,
+        recovery::recover,
+        transaction::{Transaction, TransactionManager},
+        wal::Wal,
+    },
+    backend::{
+        cache::Cache,
+        pager::Pager,
+        storage::{DiskStorage, Storage},
+    },
+    catalog::manager::{Catalog, MASTER_TABLE, TableInfo},
+    frontend::{
+        ast::Statement,
+        binder::{Binder, BoundStatement},
+        explain::{describe, render},
+        parser::Parser,
+        planner::Planner,
+    },
+    indexing::{
+        btree::index::{BTreeIndex, Rid},
+        table_heap::TableHeap,
+        table_iterator::TableIterator,
+    },
+    row::Row,
+    types::Value,
+};
+
+// Frames in the buffer pool
+const CACHE_SIZE: usize = 64;
+
+// Empty the log once it holds this many bytes
+const CHECKPOINT_SIZE: u64 = 4 * 1024 * 1024;
+
+// Bytes a single operator (sort, hash join, aggregation) may hold in memory
+const DEFAULT_MEMORY_BUDGET: usize = 16 * 1024 * 1024;
+
+pub enum QueryResult {
+    // SELECT
+    Rows {
+        columns: Vec<String>,
+        rows: Vec<Row>,
+    },
+    // INSERT / UPDATE / DELETE: number of rows changed
+    Affected(usize),
+    // CREATE / DROP
+    Done,
+    // EXPLAIN: one line per operator
+    Plan(Vec<String>),
+}
+
+// What every session of a database file shares
+struct Shared {
+    cache: Arc<Cache>,
+    wal: Arc<Wal>,
+    transactions: TransactionManager,
+    // As of the last committed transaction
+    catalog: RwLock<Arc<Catalog>>,
+}
+
+impl Drop for Shared {
+    // The last session is gone
+    fn drop(&mut self) {
+        let _ = self.cache.checkpoint();
+    }
+}
+
+// A session on a database file, and the entry point for running SQL on it
+// SQL -> Parser -> Binder -> Optimizer -> Planner -> Executors
+//
+// More sessions on the same file come from `connect`, each one runs its own transactions
+// (for example in its own thread).
+pub struct Database {
+    shared: Arc<Shared>,
+    // Opened by BEGIN, None when every statement commits on its own
+    txn: Option<Arc<Transaction>>,
+    // The catalog as changed by the running transaction, published when it commits
+    catalog: Option<Arc<Catalog>>,
+    memory_budget: usize,
+}
+
+impl Database {
+    pub fn open(path: &str) -> Result<Self, String> {
+        Self::open_in(&DiskStorage, path)
+    }
+
+    // Open the database and its log as files of `storage`
+    pub fn open_in(storage: &dyn Storage, path: &str) -> Result<Self, String> {
+        let pager =
+            Pager::open_in(storage, path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        let wal_path = format!("{}-wal", path);
+        let wal = Arc::new(
+            Wal::open_in(storage, &wal_path)
+                .map_err(|e| format!("Failed to open {}: {}", wal_path, e))?,
+        );
+        let cache = Arc::new(Cache::new(pager, CACHE_SIZE, wal.clone()));
+
+        // Repair what a crash left behind, then start from an empty log
+        let crashed = wal.size() > 0;
+        recover(&cache, &wal)?;
+        cache
+            .checkpoint()
+            .map_err(|e| format!("Failed to flush: {}", e))?;
+
+        let catalog = Catalog::open(cache.clone())?;
+        let concurrency = Concurrency::from_code(cache.header().concurrency)?;
+
+        let db = Self {
+            shared: Arc::new(Shared {
+                transactions: TransactionManager::new(cache.clone(), wal.clone(), concurrency),
+                cache,
+                wal,
+                catalog: RwLock::new(Arc::new(catalog)),
+            }),
+            txn: None,
+            catalog: None,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+        };
+
+        // The old versions and deleted rows left by a crash were never garbage collected
+        if crashed {
+            db.vacuum()?;
+        }
+        Ok(db)
+    }
+
+    // Another session on the same database
+    pub fn connect(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            txn: None,
+            catalog: None,
+            memory_budget: self.memory_budget,
+        }
+    }
+
+    // What the session sees: its own uncommitted changes included
+    pub fn catalog(&self) -> Arc<Catalog> {
+        match &self.catalog {
+            Some(catalog) => catalog.clone(),
+            None => self.shared.catalog.read().unwrap().clone(),
+        }
+    }
+
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = bytes;
+    }
+
+    pub fn concurrency(&self) -> Concurrency {
+        self.shared.transactions.concurrency()
+    }
+
+    // Switch between two-phase locking and MVCC, the choice is kept in the file header
+    pub fn set_concurrency(&mut self, concurrency: Concurrency) -> Result<(), String> {
+        if self.txn.is_some() {
+            return Err("Cannot change the concurrency control inside a transaction".to_string());
+        }
+        self.shared.transactions.set_concurrency(concurrency)
+    }
+
+    // Run every statement of the input, in order
+    // A failing statement doesn't stop the next ones
+    pub fn execute(&mut self, sql: &str) -> Vec<Result<QueryResult, String>> {
+        Parser::parse_recoverable(sql)
+            .into_iter()
+            .map(|statement| match statement {
+                Ok(statement) => self.execute_statement(statement),
+                Err(e) => Err(e.render(sql)),
+            })
+            .collect()
+    }
+
+    pub fn execute_statement(&mut self, statement: Statement) -> Result<QueryResult, String> {
+        let result = match statement {
+            Statement::Begin => self.begin(),
+            Statement::Commit => self.commit(),
+            Statement::Rollback => self.rollback(),
+            statement => self.execute_in_transaction(statement),
+        };
+
+        if self.txn.is_none() {
+            self.collect_garbage()?;
+
+            // Committed changes are safe in the log, pages are written back lazily
+            if self.shared.wal.size() > CHECKPOINT_SIZE {
+                self.shared.transactions.checkpoint()?;
+            }
+        }
+        result
+    }
+
+    // Inside BEGIN ... COMMIT a failed statement is undone and the transaction goes on,
+    // unless it lost a deadlock or a write conflict: then all of it is rolled back
+    // Otherwise the statement runs in a transaction of its own
+    fn execute_in_transaction(&mut self, statement: Statement) -> Result<QueryResult, String> {
+        if let Some(txn) = self.txn.clone() {
+            let savepoint = self.shared.transactions.savepoint(&txn);
+            let catalog = self.catalog.clone();
+            let result = self.bind_and_execute(statement, &txn);
+            if result.is_err() {
+                if self.shared.transactions.must_roll_back(&txn) {
+                    self.txn = None;
+                    self.abort_transaction(&txn)?;
+                } else {
+                    self.shared.transactions.rollback_to(&txn, savepoint)?;
+                    self.catalog = catalog;
+                }
+            }
+            return result;
+        }
+
+        let txn = self.shared.transactions.begin()?;
+        let result = self.bind_and_execute(statement, &txn);
+        match result {
+            Ok(_) => self.commit_transaction(&txn)?,
+            Err(_) => self.abort_transaction(&txn)?,
+        }
+        result
+    }
+
+    fn begin(&mut self) -> Result<QueryResult, String> {
+        if self.txn.is_some() {
+            return Err("A transaction is already running".to_string());
+        }
+        self.txn = Some(self.shared.transactions.begin()?);
+        Ok(QueryResult::Done)
+    }
+
+    fn commit(&mut self) -> Result<QueryResult, String> {
+        let txn = self.txn.take().ok_or("No transaction is running")?;
+        self.commit_transaction(&txn)?;
+        Ok(QueryResult::Done)
+    }
+
+    fn rollback(&mut self) -> Result<QueryResult, String> {
+        let txn = self.txn.take().ok_or("No transaction is running")?;
+        self.abort_transaction(&txn)?;
+        Ok(QueryResult::Done)
+    }
+
+    // A transaction that fails validation at commit is rolled back
+    // A changed catalog is published before the database lock is released,
+    // so the next writer sees it.
+    fn commit_transaction(&mut self, txn: &Transaction) -> Result<(), String> {
+        let Some(catalog) = self.catalog.take() else {
+            return self
+                .shared
+                .transactions
+                .commit(txn)
+                .or_else(|e| self.abort_after(txn, e));
+        };
+
+        let mut published = self.shared.catalog.write().unwrap();
+        match self.shared.transactions.commit(txn) {
+            Ok(()) => {
+                *published = catalog;
+                Ok(())
+            }
+            Err(e) => {
+                drop(published);
+                self.abort_after(txn, e)
+            }
+        }
+    }
+
+    fn abort_after(&self, txn: &Transaction, e: String) -> Result<(), String> {
+        self.shared.transactions.abort(txn)?;
+        Err(e)
+    }
+
+    // Its catalog changes are dropped with the pages
+    fn abort_transaction(&mut self, txn: &Transaction) -> Result<(), String> {
+        self.catalog = None;
+        self.shared.transactions.abort(txn)
+    }
+
+    // Old versions of the rows changed by committed transactions, and dropped pages,
+    // once no running transaction can read them anymore
+    fn collect_garbage(&self) -> Result<(), String> {
+        self.shared.transactions.free_dropped();
+        self.collect(&self.shared.transactions.take_garbage())
+    }
+
+    // Garbage collect every row of every table, the catalog's included
+    fn vacuum(&self) -> Result<(), String> {
+        let catalog = self.catalog();
+        let mut rows = Vec::new();
+        for table in with_master(&catalog) {
+            let heap = TableHeap::new(self.shared.cache.clone(), table.first_page_id, SYSTEM_TXN);
+            rows.extend(
+                heap.rids()?
+                    .into_iter()
+                    .map(|rid| (table.first_page_id, rid)),
+            );
+        }
+        self.collect(&rows)
+    }
+
+    // Prune the versions of these rows (first page of the table, RID) nobody can read,
+    // in a transaction of its own
+    fn collect(&self, rows: &[(usize, Rid)]) -> Result<(), String> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let txn = self.shared.transactions.begin()?;
+        let result = self
+            .prune(&txn, rows)
+            .and_then(|()| self.shared.transactions.commit(&txn));
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) => self.abort_after(&txn, e),
+        }
+    }
+
+    // Index entries go with the versions, unless a version left has the same key
+    fn prune(&self, txn: &Arc<Transaction>, rows: &[(usize, Rid)]) -> Result<(), String> {
+        // Schema changes wait for writers, so the catalog stays as read
+        txn.start_writing()?;
+        let horizon = self.shared.transactions.horizon();
+        let catalog = self.catalog();
+        let planner = self.planner(txn, &catalog);
+
+        for &(first_page_id, rid) in rows {
+            // Dropped since
+            let Some(table) = with_master(&catalog)
+                .into_iter()
+                .find(|table| table.first_page_id == first_page_id)
+            else {
+                continue;
+            };
+
+            // A writer reusing the slot waits until its index entries are gone
+            txn.lock_tuple(first_page_id, rid, LockMode::Exclusive)?;
+            let heap = TableHeap::new(self.shared.cache.clone(), first_page_id, txn.id());
+            let pruned = heap.prune(rid.0, rid.1, horizon)?;
+            if pruned.removed.is_empty() {
+                continue;
+            }
+
+            let deserialize = |tuples: &[Vec<u8>]| -> Result<Vec<Row>, String> {
+                tuples
+                    .iter()
+                    .map(|bytes| Row::deserialize(bytes, &table.schema))
+                    .collect()
+            };
+            let removed = deserialize(&pruned.removed)?;
+            let kept = deserialize(&pruned.kept)?;
+
+            for index in planner.table_indexes(table)? {
+                let kept_keys: Vec<Vec<Value>> = kept.iter().map(|row| index.key(row)).collect();
+                for row in &removed {
+                    let key = index.key(row);
+                    if !kept_keys.contains(&key) {
+                        index.index.delete(&key, rid)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // A statement that changes anything starts writing before it is bound.
+    // A schema change first waits until no other transaction writes,
+    // so writers always see the catalog as left by the last schema change.
+    fn bind_and_execute(
+        &mut self,
+        statement: Statement,
+        txn: &Arc<Transaction>,
+    ) -> Result<QueryResult, String> {
+        if changes_schema(&statement) {
+            txn.lock_database()?;
+        }
+        if writes(&statement) {
+            txn.start_writing()?;
+        }
+        let catalog = self.catalog();
+        let bound = Binder::new(&catalog).bind(statement)?;
+
+        match bound {
+            BoundStatement::CreateTable {
+                name,
+                schema,
+                if_not_exists,
+            } => {
+                if !(if_not_exists && catalog.get_table(&name).is_some()) {
+                    let mut changed = (*catalog).clone();
+                    let table = changed.create_table(txn.id(), &name, schema)?;
+                    // Nobody else sees it before the commit
+                    txn.lock_table(table.first_page_id, LockMode::Exclusive)?;
+                    self.catalog = Some(Arc::new(changed));
+                }
+                Ok(QueryResult::Done)
+            }
+            BoundStatement::DropTable { name, if_exists } => {
+                if let Some(table) = catalog.get_table(&name) {
+                    txn.lock_table(table.first_page_id, LockMode::Exclusive)?;
+                }
+                if !(if_exists && catalog.get_table(&name).is_none()) {
+                    let mut changed = (*catalog).clone();
+                    changed.drop_table(txn, &name)?;
+                    self.catalog = Some(Arc::new(changed));
+                }
+                Ok(QueryResult::Done)
+            }
+            BoundStatement::CreateIndex {
+                name,
+                table,
+                columns,
+                if_not_exists,
+            } => {
+                if !(if_not_exists && catalog.get_index(&name).is_some()) {
+                    // No row may change while the index is filled
+                    txn.lock_table(table.first_page_id, LockMode::Shared)?;
+                    let index = self.build_index(txn, &table, &columns)?;
+                    let root_page_id = index.root_page_id();
+                    let mut changed = (*catalog).clone();
+                    changed.create_index(txn.id(), &name, &table.name, columns, root_page_id)?;
+                    self.catalog = Some(Arc::new(changed));
+                }
+                Ok(QueryResult::Done)
+            }
+            BoundStatement::Explain { statement, analyze } => {
+                self.explain(*statement, analyze, txn, &catalog)
+            }
+            BoundStatement::Select(_) => {
+                let (mut root, columns) = self.planner(txn, &catalog).plan(bound)?;
+                root.init()?;
+
+                let mut rows = Vec::new();
+                while let Some(tuple) = root.next()? {
+                    rows.push(tuple.row);
+                }
+                Ok(QueryResult::Rows { columns, rows })
+            }
+            _ => {
+                let (mut root, _) = self.planner(txn, &catalog).plan(bound)?;
+                root.init()?;
+
+                // DML executors return a single row with the count
+                let tuple = root.next()?;
+                match tuple.as_ref().and_then(|t| t.row.values.first()) {
+                    Some(Value::Integer(count)) => Ok(QueryResult::Affected(*count as usize)),
+                    _ => Err("DML statement returned no row count".to_string()),
+                }
+            }
+        }
+    }
+
+    // The operator tree of a statement, with the estimated rows of each operator
+    // ANALYZE runs the statement (DML changes are kept) and adds what each operator did
+    fn explain(
+        &self,
+        statement: BoundStatement,
+        analyze: bool,
+        txn: &Arc<Transaction>,
+        catalog: &Catalog,
+    ) -> Result<QueryResult, String> {
+        let planner = self.planner(txn, catalog);
+        let (plan, _) = planner.optimize(statement)?;
+        let lines = describe(&plan, &planner.optimizer())?;
+        if !analyze {
+            return Ok(QueryResult::Plan(render(&lines, None)));
+        }
+
+        let mut profiles = Vec::new();
+        let mut root = planner.build_profiled(plan, &mut profiles)?;
+        let start = Instant::now();
+        root.init()?;
+        while root.next()?.is_some() {}
+        let elapsed = start.elapsed();
+
+        let mut text = render(&lines, Some(&profiles));
+        text.push(format!(
+            "Execution time: {:.3} ms",
+            elapsed.as_secs_f64() * 1000.0
+        ));
+        Ok(QueryResult::Plan(text))
+    }
+
+    fn planner<'a>(&self, txn: &Arc<Transaction>, catalog: &'a Catalog) -> Planner<'a> {
+        Planner::new(
+            self.shared.cache.clone(),
+            catalog,
+            self.memory_budget,
+            txn.clone(),
+        )
+    }
+
+    // A new index holding every row already in the table
+    fn build_index(
+        &self,
+        txn: &Transaction,
+        table: &TableInfo,
+        columns: &[String],
+    ) -> Result<BTreeIndex, String> {
+        // A failure rolls the statement back, the new pages with it
+        let index = BTreeIndex::create(self.shared.cache.clone(), txn.id())?;
+        self.fill_index(&index, table, columns)?;
+        Ok(index)
+    }
+
+    fn fill_index(
+        &self,
+        index: &BTreeIndex,
+        table: &TableInfo,
+        columns: &[String],
+    ) -> Result<(), String> {
+        let positions: Vec<usize> = columns
+            .iter()
+            .filter_map(|c| table.schema.column_index(c))
+            .collect();
+
+        let heap = Arc::new(TableHeap::new(
+            self.shared.cache.clone(),
+            table.first_page_id,
+            SYSTEM_TXN,
+        ));
+        for entry in TableIterator::new(heap, table.first_page_id) {
+            let (rid, bytes) = entry?;
+            let row = Row::deserialize(&bytes, &table.schema)?;
+            let key: Vec<Value> = positions.iter().map(|&i| row.values[i].clone()).collect();
+            index.insert(&key, rid)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Database {
+    // A transaction left open is rolled back
+    fn drop(&mut self) {
+        if let Some(txn) = self.txn.take() {
+            let _ = self.abort_transaction(&txn);
+        }
+        let _ = self.collect_garbage();
+    }
+}
+
+// Does the statement change anything (EXPLAIN ANALYZE runs it)?
+// Every table, `mysqlite_master` included: dropping tables deletes its rows
+fn with_master(catalog: &Catalog) -> Vec<&TableInfo> {
+    let mut tables = catalog.tables();
+    tables.extend(catalog.get_table(MASTER_TABLE));
+    tables
+}
+
+fn writes(statement: &Statement) -> bool {
+    match statement {
+        Statement::Select(_) => false,
+        Statement::Explain { statement, analyze } => *analyze && writes(statement),
+        _ => true,
+    }
+}
+
+fn changes_schema(statement: &Statement) -> bool {
+    matches!(
+        statement,
+        Statement::CreateTable { .. } | Statement::DropTable { .. } | Statement::CreateIndex { .. }
+    )
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+
+use crate::types::{DataType, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    CreateTable {
+        name: String,
+        columns: Vec<ColumnDef>,
+        if_not_exists: bool,
+    },
+    DropTable {
+        name: String,
+        if_exists: bool,
+    },
+    CreateIndex {
+        name: String,
+        table: String,
+        columns: Vec<String>,
+        if_not_exists: bool,
+    },
+    Insert {
+        table: String,
+        // None = all columns, in table order
+        columns: Option<Vec<String>>,
+        rows: Vec<Vec<Expr>>,
+    },
+    Select(Box<Select>),
+    Update {
+        table: String,
+        assignments: Vec<(String, Expr)>,
+        where_clause: Option<Expr>,
+    },
+    Delete {
+        table: String,
+        where_clause: Option<Expr>,
+    },
+    // EXPLAIN [ANALYZE] statement
+    Explain {
+        statement: Box<Statement>,
+        analyze: bool,
+    },
+    // BEGIN / COMMIT / ROLLBACK [TRANSACTION]
+    Begin,
+    Commit,
+    Rollback,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub projection: Vec<SelectItem>,
+    pub from: Option<TableRef>,
+    pub joins: Vec<Join>,
+    pub where_clause: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderByItem>,
+    pub limit: Option<Expr>,
+    pub offset: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    // *
+    Wildcard,
+    // t.*
+    QualifiedWildcard(String),
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRef {
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+// `FROM a, b` is a CROSS join
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: TableRef,
+    pub on: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Cross,
+}
+
+impl fmt::Display for JoinKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            JoinKind::Inner => "INNER",
+            JoinKind::Left => "LEFT OUTER",
+            JoinKind::Cross => "CROSS",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderByItem {
+    pub expr: Expr,
+    pub descending: bool,
+    // NULLS FIRST / NULLS LAST, None when not given
+    pub nulls_first: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    // name(args), only aggregates exist for now
+    Function {
+        name: String,
+        args: FunctionArgs,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionArgs {
+    // COUNT(*)
+    Star,
+    List { distinct: bool, args: Vec<Expr> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Plus,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Concat,
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Plus => "+",
+            UnaryOp::Not => "NOT ",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOp::Or => "OR",
+            BinaryOp::And => "AND",
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Concat => "||",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+// SQL-like text, also used as the name of an unaliased result column
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let not = |negated: &bool| if *negated { "NOT " } else { "" };
+        match self {
+            Expr::Literal(Value::Text(s)) => write!(f, "'{}'", s.replace('\'', "''")),
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Column {
+                table: Some(table),
+                name,
+            } => write!(f, "{}.{}", table, name),
+            Expr::Column { table: None, name } => write!(f, "{}", name),
+            Expr::Unary { op, expr } => write!(f, "{}{}", op, expr),
+            Expr::Binary { left, op, right } => write!(f, "({} {} {})", left, op, right),
+            Expr::IsNull { expr, negated } => write!(f, "{} IS {}NULL", expr, not(negated)),
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => write!(f, "{} {}BETWEEN {} AND {}", expr, not(negated), low, high),
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let list: Vec<String> = list.iter().map(|e| e.to_string()).collect();
+                write!(f, "{} {}IN ({})", expr, not(negated), list.join(", "))
+            }
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+            } => write!(f, "{} {}LIKE {}", expr, not(negated), pattern),
+            Expr::Function {
+                name,
+                args: FunctionArgs::Star,
+            } => write!(f, "{}(*)", name),
+            Expr::Function {
+                name,
+                args: FunctionArgs::List { distinct, args },
+            } => {
+                let args: Vec<String> = args.iter().map(|e| e.to_string()).collect();
+                let distinct = if *distinct { "DISTINCT " } else { "" };
+                write!(f, "{}({}{})", name, distinct, args.join(", "))
+            }
+        }
+    }
+}
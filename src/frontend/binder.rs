@@ -0,0 +1,1274 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    catalog::{
+        manager::{Catalog, TableInfo},
+        schema::{Column, Schema},
+    },
+    frontend::ast::{
+        BinaryOp, Expr, FunctionArgs, JoinKind, Select, SelectItem, Statement, TableRef, UnaryOp,
+    },
+    types::{DataType, Value},
+};
+
+// A statement whose names are resolved against the catalog
+// Tables are looked up, columns become positions in the input row,
+// and every expression is known to be well-typed.
+#[derive(Debug, Clone)]
+pub enum BoundStatement {
+    CreateTable {
+        name: String,
+        schema: Schema,
+        if_not_exists: bool,
+    },
+    DropTable {
+        name: String,
+        if_exists: bool,
+    },
+    CreateIndex {
+        name: String,
+        table: TableInfo,
+        columns: Vec<String>,
+        if_not_exists: bool,
+    },
+    Insert {
+        table: TableInfo,
+        // One expression per table column, in schema order
+        rows: Vec<Vec<BoundExpr>>,
+    },
+    Select(Box<BoundSelect>),
+    Update {
+        table: TableInfo,
+        // (column index, new value)
+        assignments: Vec<(usize, BoundExpr)>,
+        filter: Option<BoundExpr>,
+    },
+    Delete {
+        table: TableInfo,
+        filter: Option<BoundExpr>,
+    },
+    // The plan of a SELECT or DML statement, ANALYZE also runs it
+    Explain {
+        statement: Box<BoundStatement>,
+        analyze: bool,
+    },
+    Begin,
+    Commit,
+    Rollback,
+}
+
+#[derive(Debug, Clone)]
+pub struct BoundSelect {
+    pub from: Option<TableInfo>,
+    // Joined left to right onto `from`
+    pub joins: Vec<BoundJoin>,
+    pub filter: Option<BoundExpr>,
+    // Set for GROUP BY queries and queries using aggregates
+    // Projection, HAVING and ORDER BY then read the aggregation output instead of the input row
+    pub aggregation: Option<Aggregation>,
+    pub having: Option<BoundExpr>,
+    // (expression, output column name)
+    pub projection: Vec<(BoundExpr, String)>,
+    pub order_by: Vec<SortKey>,
+    // Constant INTEGER expressions
+    pub limit: Option<BoundExpr>,
+    pub offset: Option<BoundExpr>,
+}
+
+// The input row of a join is the row built so far followed by the table's columns
+#[derive(Debug, Clone)]
+pub struct BoundJoin {
+    pub kind: JoinKind,
+    pub table: TableInfo,
+    // Over the joined row, None for CROSS joins
+    pub on: Option<BoundExpr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    pub expr: BoundExpr,
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+// Output row: the GROUP BY values, followed by one value per aggregate
+#[derive(Debug, Clone)]
+pub struct Aggregation {
+    pub group_by: Vec<BoundExpr>,
+    pub aggregates: Vec<AggregateCall>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunc {
+    fn from_name(name: &str) -> Option<Self> {
+        let func = match name.to_ascii_uppercase().as_str() {
+            "COUNT" => AggregateFunc::Count,
+            "SUM" => AggregateFunc::Sum,
+            "AVG" => AggregateFunc::Avg,
+            "MIN" => AggregateFunc::Min,
+            "MAX" => AggregateFunc::Max,
+            _ => return None,
+        };
+        Some(func)
+    }
+}
+
+impl fmt::Display for AggregateFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = format!("{:?}", self).to_ascii_uppercase();
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateCall {
+    pub func: AggregateFunc,
+    // Over the input row, None for COUNT(*)
+    pub arg: Option<BoundExpr>,
+    pub distinct: bool,
+}
+
+impl AggregateCall {
+    // None if the result is always NULL
+    pub fn data_type(&self) -> Option<DataType> {
+        let arg = self.arg.as_ref().and_then(|arg| arg.data_type());
+        match self.func {
+            AggregateFunc::Count => Some(DataType::Integer),
+            AggregateFunc::Avg => arg.map(|_| DataType::Real),
+            AggregateFunc::Sum | AggregateFunc::Min | AggregateFunc::Max => arg,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoundExpr {
+    Literal(Value),
+    // Position of the column in the input row
+    Column {
+        index: usize,
+        name: String,
+        data_type: DataType,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<BoundExpr>,
+    },
+    Binary {
+        left: Box<BoundExpr>,
+        op: BinaryOp,
+        right: Box<BoundExpr>,
+    },
+    IsNull {
+        expr: Box<BoundExpr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<BoundExpr>,
+        low: Box<BoundExpr>,
+        high: Box<BoundExpr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<BoundExpr>,
+        list: Vec<BoundExpr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<BoundExpr>,
+        pattern: Box<BoundExpr>,
+        negated: bool,
+    },
+    // Only valid before the binder replaces it with a column of the aggregation output
+    Aggregate(Box<AggregateCall>),
+}
+
+impl BoundExpr {
+    // Type of the result, None if it is always NULL
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            BoundExpr::Literal(value) => value.data_type(),
+            BoundExpr::Column { data_type, .. } => Some(*data_type),
+            BoundExpr::Unary {
+                op: UnaryOp::Not, ..
+            } => Some(DataType::Boolean),
+            BoundExpr::Unary { expr, .. } => expr.data_type(),
+            BoundExpr::Binary { left, op, right } => match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
+                    match (left.data_type(), right.data_type()) {
+                        (None, _) | (_, None) => None,
+                        (Some(DataType::Integer), Some(DataType::Integer)) => {
+                            Some(DataType::Integer)
+                        }
+                        _ => Some(DataType::Real),
+                    }
+                }
+                BinaryOp::Concat => Some(DataType::Text),
+                _ => Some(DataType::Boolean),
+            },
+            BoundExpr::IsNull { .. }
+            | BoundExpr::Between { .. }
+            | BoundExpr::InList { .. }
+            | BoundExpr::Like { .. } => Some(DataType::Boolean),
+            BoundExpr::Aggregate(call) => call.data_type(),
+        }
+    }
+
+    // True if the expression reads no column
+    pub fn is_constant(&self) -> bool {
+        match self {
+            BoundExpr::Literal(_) => true,
+            BoundExpr::Column { .. } => false,
+            BoundExpr::Unary { expr, .. } | BoundExpr::IsNull { expr, .. } => expr.is_constant(),
+            BoundExpr::Binary { left, right, .. } => left.is_constant() && right.is_constant(),
+            BoundExpr::Between {
+                expr, low, high, ..
+            } => expr.is_constant() && low.is_constant() && high.is_constant(),
+            BoundExpr::InList { expr, list, .. } => {
+                expr.is_constant() && list.iter().all(|e| e.is_constant())
+            }
+            BoundExpr::Like { expr, pattern, .. } => expr.is_constant() && pattern.is_constant(),
+            BoundExpr::Aggregate(_) => false,
+        }
+    }
+
+    pub fn contains_aggregate(&self) -> bool {
+        match self {
+            BoundExpr::Aggregate(_) => true,
+            expr => expr.children().iter().any(|e| e.contains_aggregate()),
+        }
+    }
+
+    // Direct subexpressions, including the argument of an aggregate
+    fn children(&self) -> Vec<&BoundExpr> {
+        match self {
+            BoundExpr::Literal(_) | BoundExpr::Column { .. } => Vec::new(),
+            BoundExpr::Unary { expr, .. } | BoundExpr::IsNull { expr, .. } => vec![expr],
+            BoundExpr::Binary { left, right, .. } => vec![left, right],
+            BoundExpr::Between {
+                expr, low, high, ..
+            } => vec![expr, low, high],
+            BoundExpr::InList { expr, list, .. } => {
+                let mut children = vec![expr.as_ref()];
+                children.extend(list);
+                children
+            }
+            BoundExpr::Like { expr, pattern, .. } => vec![expr, pattern],
+            BoundExpr::Aggregate(call) => call.arg.iter().collect(),
+        }
+    }
+
+    // Positions of the columns the expression reads
+    pub fn columns(&self) -> Vec<usize> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns(&self, out: &mut Vec<usize>) {
+        match self {
+            BoundExpr::Column { index, .. } => out.push(*index),
+            expr => expr
+                .children()
+                .into_iter()
+                .for_each(|e| e.collect_columns(out)),
+        }
+    }
+
+    // The same expression over a different input row, `f` maps old column positions to new ones
+    pub fn map_columns(&self, f: &impl Fn(usize) -> usize) -> BoundExpr {
+        let map = |expr: &BoundExpr| Box::new(expr.map_columns(f));
+        match self {
+            BoundExpr::Literal(value) => BoundExpr::Literal(value.clone()),
+            BoundExpr::Column {
+                index,
+                name,
+                data_type,
+            } => BoundExpr::Column {
+                index: f(*index),
+                name: name.clone(),
+                data_type: *data_type,
+            },
+            BoundExpr::Unary { op, expr } => BoundExpr::Unary {
+                op: *op,
+                expr: map(expr),
+            },
+            BoundExpr::Binary { left, op, right } => BoundExpr::Binary {
+                left: map(left),
+                op: *op,
+                right: map(right),
+            },
+            BoundExpr::IsNull { expr, negated } => BoundExpr::IsNull {
+                expr: map(expr),
+                negated: *negated,
+            },
+            BoundExpr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => BoundExpr::Between {
+                expr: map(expr),
+                low: map(low),
+                high: map(high),
+                negated: *negated,
+            },
+            BoundExpr::InList {
+                expr,
+                list,
+                negated,
+            } => BoundExpr::InList {
+                expr: map(expr),
+                list: list.iter().map(|e| e.map_columns(f)).collect(),
+                negated: *negated,
+            },
+            BoundExpr::Like {
+                expr,
+                pattern,
+                negated,
+            } => BoundExpr::Like {
+                expr: map(expr),
+                pattern: map(pattern),
+                negated: *negated,
+            },
+            BoundExpr::Aggregate(call) => BoundExpr::Aggregate(Box::new(AggregateCall {
+                func: call.func,
+                arg: call.arg.as_ref().map(|arg| arg.map_columns(f)),
+                distinct: call.distinct,
+            })),
+        }
+    }
+
+    // The same expression with each direct subexpression replaced by `f(child)`
+    pub fn try_map_children(
+        &self,
+        f: &mut impl FnMut(&BoundExpr) -> Result<BoundExpr, String>,
+    ) -> Result<BoundExpr, String> {
+        let expr = match self {
+            BoundExpr::Literal(_) | BoundExpr::Column { .. } => self.clone(),
+            BoundExpr::Unary { op, expr } => BoundExpr::Unary {
+                op: *op,
+                expr: Box::new(f(expr)?),
+            },
+            BoundExpr::Binary { left, op, right } => BoundExpr::Binary {
+                left: Box::new(f(left)?),
+                op: *op,
+                right: Box::new(f(right)?),
+            },
+            BoundExpr::IsNull { expr, negated } => BoundExpr::IsNull {
+                expr: Box::new(f(expr)?),
+                negated: *negated,
+            },
+            BoundExpr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => BoundExpr::Between {
+                expr: Box::new(f(expr)?),
+                low: Box::new(f(low)?),
+                high: Box::new(f(high)?),
+                negated: *negated,
+            },
+            BoundExpr::InList {
+                expr,
+                list,
+                negated,
+            } => BoundExpr::InList {
+                expr: Box::new(f(expr)?),
+                list: list.iter().map(&mut *f).collect::<Result<_, _>>()?,
+                negated: *negated,
+            },
+            BoundExpr::Like {
+                expr,
+                pattern,
+                negated,
+            } => BoundExpr::Like {
+                expr: Box::new(f(expr)?),
+                pattern: Box::new(f(pattern)?),
+                negated: *negated,
+            },
+            BoundExpr::Aggregate(call) => BoundExpr::Aggregate(Box::new(AggregateCall {
+                func: call.func,
+                arg: call.arg.as_ref().map(&mut *f).transpose()?,
+                distinct: call.distinct,
+            })),
+        };
+        Ok(expr)
+    }
+}
+
+// SQL-like text, columns by name
+impl fmt::Display for BoundExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let not = |negated: &bool| if *negated { "NOT " } else { "" };
+        match self {
+            BoundExpr::Literal(Value::Text(s)) => write!(f, "'{}'", s.replace('\'', "''")),
+            BoundExpr::Literal(value) => write!(f, "{}", value),
+            BoundExpr::Column { name, .. } => write!(f, "{}", name),
+            BoundExpr::Unary { op, expr } => write!(f, "{}{}", op, expr),
+            BoundExpr::Binary { left, op, right } => write!(f, "({} {} {})", left, op, right),
+            BoundExpr::IsNull { expr, negated } => write!(f, "{} IS {}NULL", expr, not(negated)),
+            BoundExpr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => write!(f, "{} {}BETWEEN {} AND {}", expr, not(negated), low, high),
+            BoundExpr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let list: Vec<String> = list.iter().map(|e| e.to_string()).collect();
+                write!(f, "{} {}IN ({})", expr, not(negated), list.join(", "))
+            }
+            BoundExpr::Like {
+                expr,
+                pattern,
+                negated,
+            } => write!(f, "{} {}LIKE {}", expr, not(negated), pattern),
+            BoundExpr::Aggregate(call) => write!(f, "{}", call),
+        }
+    }
+}
+
+impl fmt::Display for AggregateCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let distinct = if self.distinct { "DISTINCT " } else { "" };
+        match &self.arg {
+            Some(arg) => write!(f, "{}({}{})", self.func, distinct, arg),
+            None => write!(f, "{}(*)", self.func),
+        }
+    }
+}
+
+impl Aggregation {
+    // Rewrite an expression over the input row to read the aggregation output,
+    // adding the aggregates it uses. Other columns must be GROUP BY expressions.
+    fn output(&mut self, expr: &BoundExpr) -> Result<BoundExpr, String> {
+        if expr.is_constant() {
+            return Ok(expr.clone());
+        }
+
+        if let Some(index) = self.group_by.iter().position(|g| g == expr) {
+            return Ok(match expr.data_type() {
+                Some(data_type) => BoundExpr::Column {
+                    index,
+                    name: expr_text(expr),
+                    data_type,
+                },
+                None => BoundExpr::Literal(Value::Null),
+            });
+        }
+
+        match expr {
+            BoundExpr::Aggregate(call) => {
+                let Some(data_type) = call.data_type() else {
+                    return Ok(BoundExpr::Literal(Value::Null));
+                };
+                let position = match self.aggregates.iter().position(|a| a == call.as_ref()) {
+                    Some(position) => position,
+                    None => {
+                        self.aggregates.push(call.as_ref().clone());
+                        self.aggregates.len() - 1
+                    }
+                };
+                Ok(BoundExpr::Column {
+                    index: self.group_by.len() + position,
+                    name: call.func.to_string(),
+                    data_type,
+                })
+            }
+            BoundExpr::Column { name, .. } => Err(format!(
+                "Column '{}' must appear in GROUP BY or be used in an aggregate function",
+                name
+            )),
+            expr => expr.try_map_children(&mut |child| self.output(child)),
+        }
+    }
+}
+
+// Columns visible to expressions, in input row order
+struct Scope {
+    columns: Vec<ScopeColumn>,
+}
+
+struct ScopeColumn {
+    // Alias if the table has one, its name otherwise
+    table: String,
+    name: String,
+    data_type: DataType,
+}
+
+impl Scope {
+    fn empty() -> Self {
+        Self {
+            columns: Vec::new(),
+        }
+    }
+
+    fn from_table(table: &TableInfo, alias: Option<&str>) -> Self {
+        let table_name = alias.unwrap_or(&table.name);
+        Self {
+            columns: table
+                .schema
+                .columns
+                .iter()
+                .map(|c| ScopeColumn {
+                    table: table_name.to_string(),
+                    name: c.name.clone(),
+                    data_type: c.data_type,
+                })
+                .collect(),
+        }
+    }
+
+    // Append the columns of a joined table
+    fn add_table(&mut self, table: &TableInfo, alias: Option<&str>) -> Result<(), String> {
+        let table_name = alias.unwrap_or(&table.name);
+        if self.has_table(table_name) {
+            return Err(format!(
+                "Table name '{}' is used twice, give one of them an alias",
+                table_name
+            ));
+        }
+        self.columns
+            .extend(Self::from_table(table, Some(table_name)).columns);
+        Ok(())
+    }
+
+    fn has_table(&self, table: &str) -> bool {
+        self.columns
+            .iter()
+            .any(|c| c.table.eq_ignore_ascii_case(table))
+    }
+
+    fn resolve(&self, table: Option<&str>, name: &str) -> Result<BoundExpr, String> {
+        if let Some(table) = table
+            && !self.has_table(table)
+        {
+            return Err(format!("No such table: {}", table));
+        }
+
+        let mut matches = self.columns.iter().enumerate().filter(|(_, c)| {
+            c.name.eq_ignore_ascii_case(name)
+                && table.is_none_or(|t| c.table.eq_ignore_ascii_case(t))
+        });
+
+        let display = match table {
+            Some(table) => format!("{}.{}", table, name),
+            None => name.to_string(),
+        };
+        let Some((index, column)) = matches.next() else {
+            return Err(format!("No such column: {}", display));
+        };
+        if matches.next().is_some() {
+            return Err(format!("Ambiguous column name: {}", display));
+        }
+
+        Ok(BoundExpr::Column {
+            index,
+            name: column.name.clone(),
+            data_type: column.data_type,
+        })
+    }
+}
+
+pub struct Binder<'a> {
+    catalog: &'a Catalog,
+}
+
+impl<'a> Binder<'a> {
+    pub fn new(catalog: &'a Catalog) -> Self {
+        Self { catalog }
+    }
+
+    pub fn bind(&self, statement: Statement) -> Result<BoundStatement, String> {
+        match statement {
+            Statement::CreateTable {
+                name,
+                columns,
+                if_not_exists,
+            } => {
+                if !if_not_exists && self.catalog.get_table(&name).is_some() {
+                    return Err(format!("Table '{}' already exists", name));
+                }
+
+                let mut seen = HashSet::new();
+                for column in &columns {
+                    if !seen.insert(column.name.to_lowercase()) {
+                        return Err(format!("Duplicate column name: {}", column.name));
+                    }
+                }
+
+                let schema = Schema::new(
+                    columns
+                        .iter()
+                        .map(|c| Column::new(&c.name, c.data_type))
+                        .collect(),
+                );
+                Ok(BoundStatement::CreateTable {
+                    name,
+                    schema,
+                    if_not_exists,
+                })
+            }
+            Statement::DropTable { name, if_exists } => {
+                if self.catalog.is_system_table(&name) {
+                    return Err(format!("Table '{}' may not be dropped", name));
+                }
+                if !if_exists && self.catalog.get_table(&name).is_none() {
+                    return Err(format!("No such table: {}", name));
+                }
+                Ok(BoundStatement::DropTable { name, if_exists })
+            }
+            Statement::CreateIndex {
+                name,
+                table,
+                columns,
+                if_not_exists,
+            } => {
+                let exists = self.catalog.get_index(&name).is_some();
+                if exists && !if_not_exists {
+                    return Err(format!("Index '{}' already exists", name));
+                }
+                if self.catalog.get_table(&name).is_some() {
+                    return Err(format!("There is already a table named '{}'", name));
+                }
+
+                let table = self.lookup_writable_table(&table)?;
+                let mut seen = HashSet::new();
+                for column in &columns {
+                    if table.schema.column_index(column).is_none() {
+                        return Err(format!("Table '{}' has no column '{}'", table.name, column));
+                    }
+                    if !seen.insert(column.to_lowercase()) {
+                        return Err(format!("Column '{}' is listed twice", column));
+                    }
+                }
+
+                Ok(BoundStatement::CreateIndex {
+                    name,
+                    table,
+                    columns,
+                    if_not_exists,
+                })
+            }
+            Statement::Insert {
+                table,
+                columns,
+                rows,
+            } => self.bind_insert(&table, columns, rows),
+            Statement::Select(select) => self.bind_select(*select),
+            Statement::Update {
+                table,
+                assignments,
+                where_clause,
+            } => {
+                let table = self.lookup_writable_table(&table)?;
+                let scope = Scope::from_table(&table, None);
+
+                let mut seen = HashSet::new();
+                let mut bound = Vec::with_capacity(assignments.len());
+                for (name, expr) in assignments {
+                    let index = table
+                        .schema
+                        .column_index(&name)
+                        .ok_or_else(|| format!("No such column: {}", name))?;
+                    if !seen.insert(index) {
+                        return Err(format!("Column '{}' is assigned twice", name));
+                    }
+
+                    let expr = self.bind_expr(&expr, &scope)?;
+                    reject_aggregate(&expr, "UPDATE")?;
+                    check_assignable(&table.schema.columns[index], &expr)?;
+                    bound.push((index, expr));
+                }
+
+                let filter = self.bind_filter(where_clause.as_ref(), &scope)?;
+                Ok(BoundStatement::Update {
+                    table,
+                    assignments: bound,
+                    filter,
+                })
+            }
+            Statement::Delete {
+                table,
+                where_clause,
+            } => {
+                let table = self.lookup_writable_table(&table)?;
+                let scope = Scope::from_table(&table, None);
+                let filter = self.bind_filter(where_clause.as_ref(), &scope)?;
+                Ok(BoundStatement::Delete { table, filter })
+            }
+            Statement::Explain { statement, analyze } => match self.bind(*statement)? {
+                statement @ (BoundStatement::Select(_)
+                | BoundStatement::Insert { .. }
+                | BoundStatement::Update { .. }
+                | BoundStatement::Delete { .. }) => Ok(BoundStatement::Explain {
+                    statement: Box::new(statement),
+                    analyze,
+                }),
+                _ => Err("EXPLAIN only works on SELECT, INSERT, UPDATE and DELETE".to_string()),
+            },
+            Statement::Begin => Ok(BoundStatement::Begin),
+            Statement::Commit => Ok(BoundStatement::Commit),
+            Statement::Rollback => Ok(BoundStatement::Rollback),
+        }
+    }
+
+    fn bind_insert(
+        &self,
+        table_name: &str,
+        columns: Option<Vec<String>>,
+        rows: Vec<Vec<Expr>>,
+    ) -> Result<BoundStatement, String> {
+        let table = self.lookup_writable_table(table_name)?;
+        let schema = &table.schema;
+
+        // Target column index of each supplied value
+        let targets: Vec<usize> = match &columns {
+            Some(names) => {
+                let mut targets = Vec::with_capacity(names.len());
+                for name in names {
+                    let index = schema.column_index(name).ok_or_else(|| {
+                        format!("Table '{}' has no column '{}'", table.name, name)
+                    })?;
+                    if targets.contains(&index) {
+                        return Err(format!("Column '{}' is listed twice", name));
+                    }
+                    targets.push(index);
+                }
+                targets
+            }
+            None => (0..schema.columns.len()).collect(),
+        };
+
+        // VALUES can't see any column
+        let scope = Scope::empty();
+        let mut bound_rows = Vec::with_capacity(rows.len());
+        for (row_number, row) in rows.iter().enumerate() {
+            if row.len() != targets.len() {
+                return Err(format!(
+                    "Row {} has {} values, but {} columns were expected",
+                    row_number + 1,
+                    row.len(),
+                    targets.len()
+                ));
+            }
+
+            // Columns left out get NULL
+            let mut values = vec![BoundExpr::Literal(Value::Null); schema.columns.len()];
+            for (expr, &index) in row.iter().zip(&targets) {
+                let expr = self.bind_expr(expr, &scope)?;
+                reject_aggregate(&expr, "VALUES")?;
+                check_assignable(&schema.columns[index], &expr)?;
+                values[index] = expr;
+            }
+            bound_rows.push(values);
+        }
+
+        Ok(BoundStatement::Insert {
+            table,
+            rows: bound_rows,
+        })
+    }
+
+    fn bind_select(&self, select: Select) -> Result<BoundStatement, String> {
+        let (from, mut scope) = match &select.from {
+            Some(table_ref) => {
+                let table = self.lookup_table(&table_ref.name)?;
+                let scope = Scope::from_table(&table, table_ref.alias.as_deref());
+                (Some(table), scope)
+            }
+            None => (None, Scope::empty()),
+        };
+
+        // Each ON clause sees the tables joined so far, including its own
+        let mut joins = Vec::with_capacity(select.joins.len());
+        for join in &select.joins {
+            let TableRef { name, alias } = &join.table;
+            let table = self.lookup_table(name)?;
+            scope.add_table(&table, alias.as_deref())?;
+
+            let on = match &join.on {
+                Some(on) => {
+                    let bound = self.bind_expr(on, &scope)?;
+                    expect_type(&bound, &[DataType::Boolean], "ON")?;
+                    reject_aggregate(&bound, "ON")?;
+                    Some(bound)
+                }
+                None => None,
+            };
+            joins.push(BoundJoin {
+                kind: join.kind,
+                table,
+                on,
+            });
+        }
+
+        let filter = self.bind_filter(select.where_clause.as_ref(), &scope)?;
+
+        let mut group_by = Vec::with_capacity(select.group_by.len());
+        for expr in &select.group_by {
+            let bound = self.bind_expr(expr, &scope)?;
+            reject_aggregate(&bound, "GROUP BY")?;
+            group_by.push(bound);
+        }
+
+        let mut projection = Vec::new();
+        for item in &select.projection {
+            match item {
+                SelectItem::Wildcard => {
+                    if from.is_none() {
+                        return Err("SELECT * needs a FROM clause".to_string());
+                    }
+                    for (index, column) in scope.columns.iter().enumerate() {
+                        projection.push((column_ref(index, column), column.name.clone()));
+                    }
+                }
+                SelectItem::QualifiedWildcard(table) => {
+                    if !scope.has_table(table) {
+                        return Err(format!("No such table: {}", table));
+                    }
+                    for (index, column) in scope.columns.iter().enumerate() {
+                        if column.table.eq_ignore_ascii_case(table) {
+                            projection.push((column_ref(index, column), column.name.clone()));
+                        }
+                    }
+                }
+                SelectItem::Expr { expr, alias } => {
+                    let bound = self.bind_expr(expr, &scope)?;
+                    let name = match (alias, expr) {
+                        (Some(alias), _) => alias.clone(),
+                        (None, Expr::Column { name, .. }) => name.clone(),
+                        (None, expr) => expr.to_string(),
+                    };
+                    projection.push((bound, name));
+                }
+            }
+        }
+
+        // ORDER BY may also name an output column or give its position
+        let mut order_by = Vec::with_capacity(select.order_by.len());
+        for item in &select.order_by {
+            let expr = match &item.expr {
+                Expr::Literal(Value::Integer(n)) => {
+                    let position = usize::try_from(*n).unwrap_or(0);
+                    if position == 0 || position > projection.len() {
+                        return Err(format!(
+                            "ORDER BY position {} is not between 1 and {}",
+                            n,
+                            projection.len()
+                        ));
+                    }
+                    projection[position - 1].0.clone()
+                }
+                Expr::Column { table: None, name } => match scope.resolve(None, name) {
+                    Ok(expr) => expr,
+                    Err(e) => projection
+                        .iter()
+                        .find(|(_, output)| output.eq_ignore_ascii_case(name))
+                        .map(|(expr, _)| expr.clone())
+                        .ok_or(e)?,
+                },
+                expr => self.bind_expr(expr, &scope)?,
+            };
+            // NULL is the smallest value unless told otherwise, like in SQLite
+            order_by.push(SortKey {
+                expr,
+                descending: item.descending,
+                nulls_first: item.nulls_first.unwrap_or(!item.descending),
+            });
+        }
+
+        let mut having = match &select.having {
+            Some(expr) => {
+                let bound = self.bind_expr(expr, &scope)?;
+                expect_type(&bound, &[DataType::Boolean], "HAVING")?;
+                Some(bound)
+            }
+            None => None,
+        };
+
+        // Everything after WHERE runs on the aggregation output
+        let is_aggregate = !group_by.is_empty()
+            || having.is_some()
+            || projection.iter().any(|(e, _)| e.contains_aggregate())
+            || order_by.iter().any(|k| k.expr.contains_aggregate());
+        let aggregation = if is_aggregate {
+            let mut aggregation = Aggregation {
+                group_by,
+                aggregates: Vec::new(),
+            };
+            let exprs = projection.iter_mut().map(|(e, _)| e);
+            for expr in exprs.chain(order_by.iter_mut().map(|k| &mut k.expr)) {
+                *expr = aggregation.output(expr)?;
+            }
+            having = having.map(|h| aggregation.output(&h)).transpose()?;
+            Some(aggregation)
+        } else {
+            None
+        };
+
+        let limit = self.bind_count(select.limit.as_ref(), "LIMIT")?;
+        let offset = self.bind_count(select.offset.as_ref(), "OFFSET")?;
+
+        Ok(BoundStatement::Select(Box::new(BoundSelect {
+            from,
+            joins,
+            filter,
+            aggregation,
+            having,
+            projection,
+            order_by,
+            limit,
+            offset,
+        })))
+    }
+
+    // WHERE must be a condition
+    fn bind_filter(&self, expr: Option<&Expr>, scope: &Scope) -> Result<Option<BoundExpr>, String> {
+        let Some(expr) = expr else {
+            return Ok(None);
+        };
+        let bound = self.bind_expr(expr, scope)?;
+        expect_type(&bound, &[DataType::Boolean], "WHERE")?;
+        reject_aggregate(&bound, "WHERE")?;
+        Ok(Some(bound))
+    }
+
+    // LIMIT / OFFSET must be a constant integer
+    fn bind_count(&self, expr: Option<&Expr>, clause: &str) -> Result<Option<BoundExpr>, String> {
+        let Some(expr) = expr else {
+            return Ok(None);
+        };
+        let bound = self.bind_expr(expr, &Scope::empty())?;
+        expect_type(&bound, &[DataType::Integer], clause)?;
+        reject_aggregate(&bound, clause)?;
+        Ok(Some(bound))
+    }
+
+    fn bind_expr(&self, expr: &Expr, scope: &Scope) -> Result<BoundExpr, String> {
+        let bound = match expr {
+            Expr::Literal(value) => BoundExpr::Literal(value.clone()),
+            Expr::Column { table, name } => scope.resolve(table.as_deref(), name)?,
+            Expr::Unary { op, expr } => {
+                let expr = self.bind_expr(expr, scope)?;
+                match op {
+                    UnaryOp::Not => expect_type(&expr, &[DataType::Boolean], "NOT")?,
+                    UnaryOp::Neg | UnaryOp::Plus => {
+                        expect_type(&expr, NUMERIC, &format!("Unary {}", op))?
+                    }
+                }
+                BoundExpr::Unary {
+                    op: *op,
+                    expr: Box::new(expr),
+                }
+            }
+            Expr::Binary { left, op, right } => {
+                let left = self.bind_expr(left, scope)?;
+                let right = self.bind_expr(right, scope)?;
+                let name = op.to_string();
+                match op {
+                    BinaryOp::And | BinaryOp::Or => {
+                        expect_type(&left, &[DataType::Boolean], &name)?;
+                        expect_type(&right, &[DataType::Boolean], &name)?;
+                    }
+                    BinaryOp::Add
+                    | BinaryOp::Sub
+                    | BinaryOp::Mul
+                    | BinaryOp::Div
+                    | BinaryOp::Mod => {
+                        expect_type(&left, NUMERIC, &name)?;
+                        expect_type(&right, NUMERIC, &name)?;
+                    }
+                    BinaryOp::Concat => {
+                        expect_type(&left, &[DataType::Text], &name)?;
+                        expect_type(&right, &[DataType::Text], &name)?;
+                    }
+                    BinaryOp::Eq
+                    | BinaryOp::NotEq
+                    | BinaryOp::Lt
+                    | BinaryOp::LtEq
+                    | BinaryOp::Gt
+                    | BinaryOp::GtEq => check_comparable(&left, &right)?,
+                }
+                BoundExpr::Binary {
+                    left: Box::new(left),
+                    op: *op,
+                    right: Box::new(right),
+                }
+            }
+            Expr::IsNull { expr, negated } => BoundExpr::IsNull {
+                expr: Box::new(self.bind_expr(expr, scope)?),
+                negated: *negated,
+            },
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let expr = self.bind_expr(expr, scope)?;
+                let low = self.bind_expr(low, scope)?;
+                let high = self.bind_expr(high, scope)?;
+                check_comparable(&expr, &low)?;
+                check_comparable(&expr, &high)?;
+                BoundExpr::Between {
+                    expr: Box::new(expr),
+                    low: Box::new(low),
+                    high: Box::new(high),
+                    negated: *negated,
+                }
+            }
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let expr = self.bind_expr(expr, scope)?;
+                let mut bound_list = Vec::with_capacity(list.len());
+                for item in list {
+                    let item = self.bind_expr(item, scope)?;
+                    check_comparable(&expr, &item)?;
+                    bound_list.push(item);
+                }
+                BoundExpr::InList {
+                    expr: Box::new(expr),
+                    list: bound_list,
+                    negated: *negated,
+                }
+            }
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+            } => {
+                let expr = self.bind_expr(expr, scope)?;
+                let pattern = self.bind_expr(pattern, scope)?;
+                expect_type(&expr, &[DataType::Text], "LIKE")?;
+                expect_type(&pattern, &[DataType::Text], "LIKE")?;
+                BoundExpr::Like {
+                    expr: Box::new(expr),
+                    pattern: Box::new(pattern),
+                    negated: *negated,
+                }
+            }
+            Expr::Function { name, args } => {
+                let func = AggregateFunc::from_name(name)
+                    .ok_or_else(|| format!("No such function: {}", name))?;
+                let (arg, distinct) = match args {
+                    FunctionArgs::Star if func == AggregateFunc::Count => (None, false),
+                    FunctionArgs::Star => return Err(format!("{}(*) is not allowed", func)),
+                    FunctionArgs::List { distinct, args } => {
+                        if args.len() != 1 {
+                            return Err(format!(
+                                "{} expects 1 argument, but got {}",
+                                func,
+                                args.len()
+                            ));
+                        }
+                        let arg = self.bind_expr(&args[0], scope)?;
+                        if arg.contains_aggregate() {
+                            return Err("Aggregate functions can't be nested".to_string());
+                        }
+                        if matches!(func, AggregateFunc::Sum | AggregateFunc::Avg) {
+                            expect_type(&arg, NUMERIC, &func.to_string())?;
+                        }
+                        (Some(arg), *distinct)
+                    }
+                };
+                BoundExpr::Aggregate(Box::new(AggregateCall {
+                    func,
+                    arg,
+                    distinct,
+                }))
+            }
+        };
+        Ok(bound)
+    }
+
+    fn lookup_table(&self, name: &str) -> Result<TableInfo, String> {
+        self.catalog
+            .get_table(name)
+            .cloned()
+            .ok_or_else(|| format!("No such table: {}", name))
+    }
+
+    // The catalog table is only changed through CREATE and DROP
+    fn lookup_writable_table(&self, name: &str) -> Result<TableInfo, String> {
+        if self.catalog.is_system_table(name) {
+            return Err(format!("Table '{}' may not be modified", name));
+        }
+        self.lookup_table(name)
+    }
+}
+
+const NUMERIC: &[DataType] = &[DataType::Integer, DataType::Real];
+
+fn column_ref(index: usize, column: &ScopeColumn) -> BoundExpr {
+    BoundExpr::Column {
+        index,
+        name: column.name.clone(),
+        data_type: column.data_type,
+    }
+}
+
+// NULL fits anywhere
+fn expect_type(expr: &BoundExpr, allowed: &[DataType], context: &str) -> Result<(), String> {
+    match expr.data_type() {
+        Some(data_type) if !allowed.contains(&data_type) => {
+            let allowed: Vec<String> = allowed.iter().map(|t| t.to_string()).collect();
+            Err(format!(
+                "{} expects {}, but got {} ({})",
+                context,
+                allowed.join(" or "),
+                data_type,
+                expr_text(expr)
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Aggregates only make sense in SELECT items, HAVING and ORDER BY
+fn reject_aggregate(expr: &BoundExpr, clause: &str) -> Result<(), String> {
+    if expr.contains_aggregate() {
+        return Err(format!("Aggregate functions are not allowed in {}", clause));
+    }
+    Ok(())
+}
+
+// Numbers compare with numbers, every other type only with itself
+fn check_comparable(left: &BoundExpr, right: &BoundExpr) -> Result<(), String> {
+    match (left.data_type(), right.data_type()) {
+        (Some(a), Some(b)) if a != b && !(NUMERIC.contains(&a) && NUMERIC.contains(&b)) => {
+            Err(format!("Cannot compare {} with {}", a, b))
+        }
+        _ => Ok(()),
+    }
+}
+
+// An INTEGER can be stored in a REAL column, otherwise types must match
+fn check_assignable(column: &Column, expr: &BoundExpr) -> Result<(), String> {
+    match expr.data_type() {
+        Some(data_type)
+            if data_type != column.data_type
+                && !(data_type == DataType::Integer && column.data_type == DataType::Real) =>
+        {
+            Err(format!(
+                "Column '{}' expects {}, but got {}",
+                column.name, column.data_type, data_type
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn expr_text(expr: &BoundExpr) -> String {
+    match expr {
+        BoundExpr::Column { name, .. } => name.clone(),
+        BoundExpr::Literal(Value::Text(s)) => format!("'{}'", s),
+        BoundExpr::Literal(value) => value.to_string(),
+        _ => "expression".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{database::Database, frontend::parser::Parser};
+
+    // A database with two tables sharing the column name `id`
+    struct TempDb {
+        path: String,
+        db: Database,
+    }
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir()
+                .join(format!("mysqlite-binder-{}-{}.db", process::id(), name))
+                .to_string_lossy()
+                .into_owned();
+            remove(&path);
+
+            let mut db = Database::open(&path).unwrap();
+            let sql = "CREATE TABLE a (id INTEGER, name TEXT); CREATE TABLE b (id INTEGER, a_id INTEGER, score REAL);";
+            for result in db.execute(sql) {
+                result.unwrap();
+            }
+            Self { path, db }
+        }
+
+        fn bind(&self, sql: &str) -> Result<BoundStatement, String> {
+            let statement = Parser::parse(sql).unwrap().pop().unwrap();
+            Binder::new(&self.db.catalog()).bind(statement)
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            remove(&self.path);
+        }
+    }
+
+    fn remove(path: &str) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn columns_resolve_to_positions_in_the_joined_row() {
+        let temp = TempDb::new("positions");
+        let Ok(BoundStatement::Select(select)) =
+            temp.bind("SELECT b.id, name, score FROM a JOIN b ON a.id = b.a_id")
+        else {
+            panic!("SELECT did not bind");
+        };
+
+        let positions: Vec<_> = select
+            .projection
+            .iter()
+            .map(|(expr, name)| (expr.columns(), name.as_str()))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![(vec![2], "id"), (vec![1], "name"), (vec![4], "score")]
+        );
+    }
+
+    #[test]
+    fn bad_names_and_types_are_rejected() {
+        let temp = TempDb::new("errors");
+        let error = |sql: &str| temp.bind(sql).unwrap_err();
+
+        assert_eq!(error("SELECT x FROM a"), "No such column: x");
+        assert_eq!(error("SELECT * FROM c"), "No such table: c");
+        assert_eq!(
+            error("SELECT id FROM a JOIN b ON a.id = b.a_id"),
+            "Ambiguous column name: id"
+        );
+        assert_eq!(
+            error("SELECT name FROM a WHERE name = 1"),
+            "Cannot compare TEXT with INTEGER"
+        );
+        assert!(error("SELECT SUM(SUM(id)) FROM a").contains("nested"));
+        assert!(error("INSERT INTO a (id, id) VALUES (1, 2)").contains("listed twice"));
+        assert!(
+            temp.bind("SELECT a.id, b.id FROM a JOIN b ON a.id = b.a_id")
+                .is_ok()
+        );
+    }
+}
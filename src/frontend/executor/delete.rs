@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use crate::{
+    acid::lock_manager::LockMode,
+    frontend::executor::{Executor, TableIndex, Tuple},
+    indexing::table_heap::TableHeap,
+    row::Row,
+    types::Value,
+};
+
+// Delete every row produced by the child (which must carry RIDs)
+// and its index entries (under MVCC the garbage collector removes them later)
+// Returns a single row: the number of deleted rows
+pub struct Delete {
+    table_heap: Arc<TableHeap>,
+    indexes: Vec<TableIndex>,
+    child: Box<dyn Executor>,
+    done: bool,
+}
+
+impl Delete {
+    pub fn new(
+        table_heap: Arc<TableHeap>,
+        indexes: Vec<TableIndex>,
+        child: Box<dyn Executor>,
+    ) -> Self {
+        Self {
+            table_heap,
+            indexes,
+            child,
+            done: false,
+        }
+    }
+}
+
+impl Executor for Delete {
+    fn init(&mut self) -> Result<(), String> {
+        self.done = false;
+        self.table_heap.lock_table(LockMode::IntentionExclusive)?;
+        self.child.init()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+
+        // Collect first, so the scan below never sees a half-modified table
+        let mut deleted = Vec::new();
+        while let Some(tuple) = self.child.next()? {
+            let rid = tuple.rid.ok_or("Delete needs rows read from the table")?;
+            // Hold the row until the end of the transaction, nobody may change it meanwhile
+            self.table_heap.lock_tuple(rid, LockMode::Exclusive)?;
+            deleted.push((rid, tuple.row));
+        }
+
+        for (rid, row) in &deleted {
+            self.table_heap.delete(rid.0, rid.1)?;
+            if self.table_heap.keeps_versions() {
+                continue;
+            }
+            for index in &self.indexes {
+                index.index.delete(&index.key(row), *rid)?;
+            }
+        }
+        let count = deleted.len() as i64;
+        Ok(Some(Tuple::new(
+            Row::new(vec![Value::Integer(count)]),
+            None,
+        )))
+    }
+}
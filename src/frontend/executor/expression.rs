@@ -0,0 +1,258 @@
+use std::cmp::Ordering;
+
+use crate::{
+    frontend::{
+        ast::{BinaryOp, UnaryOp},
+        binder::BoundExpr,
+    },
+    row::Row,
+    types::Value,
+};
+
+// Evaluate a bound expression over an input row
+// NULL follows SQL three-valued logic: it propagates through operators,
+// and AND / OR only return NULL when the other side doesn't decide the result.
+pub fn evaluate(expr: &BoundExpr, row: &Row) -> Result<Value, String> {
+    match expr {
+        BoundExpr::Literal(value) => Ok(value.clone()),
+        BoundExpr::Column { index, .. } => row
+            .values
+            .get(*index)
+            .cloned()
+            .ok_or_else(|| format!("Column {} is out of range", index)),
+        BoundExpr::Unary { op, expr } => {
+            let value = evaluate(expr, row)?;
+            match (op, value) {
+                (_, Value::Null) => Ok(Value::Null),
+                (UnaryOp::Plus, value) => Ok(value),
+                (UnaryOp::Neg, Value::Integer(v)) => v
+                    .checked_neg()
+                    .map(Value::Integer)
+                    .ok_or_else(|| "Integer overflow".to_string()),
+                (UnaryOp::Neg, Value::Real(v)) => Ok(Value::Real(-v)),
+                (UnaryOp::Not, Value::Boolean(v)) => Ok(Value::Boolean(!v)),
+                (op, value) => Err(type_error(&op.to_string(), &value)),
+            }
+        }
+        BoundExpr::Binary { left, op, right } => match op {
+            BinaryOp::And => {
+                let left = to_bool(evaluate(left, row)?)?;
+                if left == Some(false) {
+                    return Ok(Value::Boolean(false));
+                }
+                let right = to_bool(evaluate(right, row)?)?;
+                Ok(match (left, right) {
+                    (_, Some(false)) => Value::Boolean(false),
+                    (Some(true), Some(true)) => Value::Boolean(true),
+                    _ => Value::Null,
+                })
+            }
+            BinaryOp::Or => {
+                let left = to_bool(evaluate(left, row)?)?;
+                if left == Some(true) {
+                    return Ok(Value::Boolean(true));
+                }
+                let right = to_bool(evaluate(right, row)?)?;
+                Ok(match (left, right) {
+                    (_, Some(true)) => Value::Boolean(true),
+                    (Some(false), Some(false)) => Value::Boolean(false),
+                    _ => Value::Null,
+                })
+            }
+            _ => binary(*op, evaluate(left, row)?, evaluate(right, row)?),
+        },
+        BoundExpr::IsNull { expr, negated } => {
+            Ok(Value::Boolean(evaluate(expr, row)?.is_null() != *negated))
+        }
+        BoundExpr::Between {
+            expr,
+            low,
+            high,
+            negated,
+        } => {
+            let value = evaluate(expr, row)?;
+            let low = binary(BinaryOp::GtEq, value.clone(), evaluate(low, row)?)?;
+            let high = binary(BinaryOp::LtEq, value, evaluate(high, row)?)?;
+            let result = match (to_bool(low)?, to_bool(high)?) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            };
+            Ok(negate(result, *negated))
+        }
+        BoundExpr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let value = evaluate(expr, row)?;
+            if value.is_null() {
+                return Ok(Value::Null);
+            }
+
+            // No match but a NULL in the list: unknown
+            let mut result = Some(false);
+            for item in list {
+                match value.compare(&evaluate(item, row)?) {
+                    Some(Ordering::Equal) => {
+                        result = Some(true);
+                        break;
+                    }
+                    Some(_) => {}
+                    None => result = None,
+                }
+            }
+            Ok(negate(result, *negated))
+        }
+        BoundExpr::Like {
+            expr,
+            pattern,
+            negated,
+        } => match (evaluate(expr, row)?, evaluate(pattern, row)?) {
+            (Value::Text(text), Value::Text(pattern)) => {
+                Ok(negate(Some(like(&text, &pattern)), *negated))
+            }
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (value, _) => Err(type_error("LIKE", &value)),
+        },
+        BoundExpr::Aggregate(call) => Err(format!(
+            "{} can only be computed by an aggregation",
+            call.func
+        )),
+    }
+}
+
+// Only TRUE passes a WHERE clause, FALSE and NULL don't
+pub fn is_true(value: &Value) -> bool {
+    matches!(value, Value::Boolean(true))
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, String> {
+    if left.is_null() || right.is_null() {
+        return Ok(Value::Null);
+    }
+
+    let ordering = || {
+        left.compare(&right)
+            .ok_or_else(|| format!("Cannot compare {} with {}", left, right))
+    };
+
+    let value = match op {
+        BinaryOp::Eq => Value::Boolean(ordering()? == Ordering::Equal),
+        BinaryOp::NotEq => Value::Boolean(ordering()? != Ordering::Equal),
+        BinaryOp::Lt => Value::Boolean(ordering()? == Ordering::Less),
+        BinaryOp::LtEq => Value::Boolean(ordering()? != Ordering::Greater),
+        BinaryOp::Gt => Value::Boolean(ordering()? == Ordering::Greater),
+        BinaryOp::GtEq => Value::Boolean(ordering()? != Ordering::Less),
+        BinaryOp::Concat => match (&left, &right) {
+            (Value::Text(a), Value::Text(b)) => Value::Text(format!("{}{}", a, b)),
+            (Value::Text(_), value) | (value, _) => return Err(type_error("||", value)),
+        },
+        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => {
+            arithmetic(op, left, right)?
+        }
+        BinaryOp::And | BinaryOp::Or => unreachable!("AND / OR are evaluated lazily"),
+    };
+    Ok(value)
+}
+
+// INTEGER op INTEGER stays INTEGER (integer division), anything with a REAL is REAL
+// Division by zero gives NULL, like in SQLite
+fn arithmetic(op: BinaryOp, left: Value, right: Value) -> Result<Value, String> {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => {
+            let result = match op {
+                BinaryOp::Add => a.checked_add(b),
+                BinaryOp::Sub => a.checked_sub(b),
+                BinaryOp::Mul => a.checked_mul(b),
+                BinaryOp::Div if b == 0 => return Ok(Value::Null),
+                BinaryOp::Div => a.checked_div(b),
+                BinaryOp::Mod if b == 0 => return Ok(Value::Null),
+                BinaryOp::Mod => a.checked_rem(b),
+                _ => unreachable!(),
+            };
+            result
+                .map(Value::Integer)
+                .ok_or_else(|| "Integer overflow".to_string())
+        }
+        (left, right) => {
+            let (Some(a), Some(b)) = (to_real(&left), to_real(&right)) else {
+                let bad = if to_real(&left).is_none() {
+                    left
+                } else {
+                    right
+                };
+                return Err(type_error(&op.to_string(), &bad));
+            };
+            let result = match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                BinaryOp::Div if b == 0.0 => return Ok(Value::Null),
+                BinaryOp::Div => a / b,
+                BinaryOp::Mod if b == 0.0 => return Ok(Value::Null),
+                BinaryOp::Mod => a % b,
+                _ => unreachable!(),
+            };
+            Ok(Value::Real(result))
+        }
+    }
+}
+
+fn to_real(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(v) => Some(*v as f64),
+        Value::Real(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn to_bool(value: Value) -> Result<Option<bool>, String> {
+    match value {
+        Value::Boolean(v) => Ok(Some(v)),
+        Value::Null => Ok(None),
+        value => Err(type_error("A condition", &value)),
+    }
+}
+
+fn negate(result: Option<bool>, negated: bool) -> Value {
+    match result {
+        Some(v) => Value::Boolean(v != negated),
+        None => Value::Null,
+    }
+}
+
+// SQL LIKE: `%` matches any run of characters, `_` exactly one
+// ASCII letters match case-insensitively, like in SQLite
+pub fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+
+    // Greedy matching, backtracking to the last `%`
+    let (mut t, mut p) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '_' || pattern[p].eq_ignore_ascii_case(&text[t])) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '%' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+fn type_error(context: &str, value: &Value) -> String {
+    match value.data_type() {
+        Some(data_type) => format!("{} can't be applied to {}", context, data_type),
+        None => format!("{} can't be applied to NULL", context),
+    }
+}
@@ -0,0 +1,34 @@
+use crate::frontend::{
+    binder::BoundExpr,
+    executor::{
+        Executor, Tuple,
+        expression::{evaluate, is_true},
+    },
+};
+
+// Pass through the rows for which the predicate is TRUE
+pub struct Filter {
+    child: Box<dyn Executor>,
+    predicate: BoundExpr,
+}
+
+impl Filter {
+    pub fn new(child: Box<dyn Executor>, predicate: BoundExpr) -> Self {
+        Self { child, predicate }
+    }
+}
+
+impl Executor for Filter {
+    fn init(&mut self) -> Result<(), String> {
+        self.child.init()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        while let Some(tuple) = self.child.next()? {
+            if is_true(&evaluate(&self.predicate, &tuple.row)?) {
+                return Ok(Some(tuple));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    frontend::{
+        binder::{AggregateCall, AggregateFunc, BoundExpr},
+        executor::{Executor, Tuple, expression::evaluate, hash_key, values_size},
+    },
+    row::Row,
+    types::Value,
+};
+
+// GROUP BY and aggregates: rows are hashed into groups on the GROUP BY values,
+// and every group keeps one running state per aggregate.
+// Output: one row per group, its GROUP BY values followed by the aggregate results.
+// Without GROUP BY there is exactly one group, even for an empty input.
+//
+// NULL inputs are skipped by every aggregate except COUNT(*).
+// SUM, AVG, MIN and MAX of no values are NULL, COUNT is 0.
+// GROUP BY puts all NULLs in the same group.
+pub struct HashAggregate {
+    child: Box<dyn Executor>,
+    group_by: Vec<BoundExpr>,
+    aggregates: Vec<AggregateCall>,
+    memory_budget: usize,
+    output: Vec<Row>,
+    position: usize,
+}
+
+impl HashAggregate {
+    pub fn new(
+        child: Box<dyn Executor>,
+        group_by: Vec<BoundExpr>,
+        aggregates: Vec<AggregateCall>,
+        memory_budget: usize,
+    ) -> Self {
+        Self {
+            child,
+            group_by,
+            aggregates,
+            memory_budget,
+            output: Vec::new(),
+            position: 0,
+        }
+    }
+
+    fn new_group(&self) -> Vec<Accumulator> {
+        self.aggregates.iter().map(Accumulator::new).collect()
+    }
+}
+
+impl Executor for HashAggregate {
+    fn init(&mut self) -> Result<(), String> {
+        self.child.init()?;
+
+        // Groups in the order they were first seen
+        let mut positions: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut groups: Vec<(Vec<Value>, Vec<Accumulator>)> = Vec::new();
+        let mut used = 0;
+
+        while let Some(tuple) = self.child.next()? {
+            let keys = self
+                .group_by
+                .iter()
+                .map(|expr| evaluate(expr, &tuple.row))
+                .collect::<Result<Vec<_>, _>>()?;
+            let hash = hash_key(&keys);
+
+            let position = match positions.get(&hash) {
+                Some(&position) => position,
+                None => {
+                    used += hash.len() + values_size(&keys);
+                    positions.insert(hash, groups.len());
+                    groups.push((keys, self.new_group()));
+                    groups.len() - 1
+                }
+            };
+
+            let (_, accumulators) = &mut groups[position];
+            for (accumulator, call) in accumulators.iter_mut().zip(&self.aggregates) {
+                used += accumulator.update(call, &tuple.row)?;
+            }
+
+            if used > self.memory_budget {
+                return Err(format!(
+                    "Aggregation ran out of memory: the groups need more than {} bytes",
+                    self.memory_budget
+                ));
+            }
+        }
+
+        if groups.is_empty() && self.group_by.is_empty() {
+            groups.push((Vec::new(), self.new_group()));
+        }
+
+        self.output = groups
+            .into_iter()
+            .map(|(mut values, accumulators)| {
+                values.extend(accumulators.iter().map(Accumulator::finish));
+                Row::new(values)
+            })
+            .collect();
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        let row = self.output.get(self.position).cloned();
+        self.position += 1;
+        Ok(row.map(|row| Tuple::new(row, None)))
+    }
+}
+
+enum State {
+    Count(i64),
+    // None until the first value
+    Sum(Option<Value>),
+    Avg { sum: f64, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+}
+
+struct Accumulator {
+    state: State,
+    // Values already seen, for DISTINCT
+    seen: Option<HashSet<Vec<u8>>>,
+}
+
+impl Accumulator {
+    fn new(call: &AggregateCall) -> Self {
+        let state = match call.func {
+            AggregateFunc::Count => State::Count(0),
+            AggregateFunc::Sum => State::Sum(None),
+            AggregateFunc::Avg => State::Avg { sum: 0.0, count: 0 },
+            AggregateFunc::Min => State::Min(None),
+            AggregateFunc::Max => State::Max(None),
+        };
+        Self {
+            state,
+            seen: call.distinct.then(HashSet::new),
+        }
+    }
+
+    // Add one input row, returns the bytes of memory it took
+    fn update(&mut self, call: &AggregateCall, row: &Row) -> Result<usize, String> {
+        let Some(arg) = &call.arg else {
+            // COUNT(*)
+            if let State::Count(count) = &mut self.state {
+                *count += 1;
+            }
+            return Ok(0);
+        };
+
+        let value = evaluate(arg, row)?;
+        if value.is_null() {
+            return Ok(0);
+        }
+
+        let mut used = 0;
+        if let Some(seen) = &mut self.seen {
+            let key = hash_key(std::slice::from_ref(&value));
+            used = key.len();
+            if !seen.insert(key) {
+                return Ok(0);
+            }
+        }
+
+        match &mut self.state {
+            State::Count(count) => *count += 1,
+            State::Sum(sum) => {
+                *sum = Some(match (sum.take(), value) {
+                    (None, value) => value,
+                    (Some(Value::Integer(a)), Value::Integer(b)) => Value::Integer(
+                        a.checked_add(b)
+                            .ok_or_else(|| "Integer overflow in SUM".to_string())?,
+                    ),
+                    (Some(a), b) => Value::Real(to_real(&a)? + to_real(&b)?),
+                });
+            }
+            State::Avg { sum, count } => {
+                *sum += to_real(&value)?;
+                *count += 1;
+            }
+            State::Min(min) => {
+                if min
+                    .as_ref()
+                    .is_none_or(|m| value.compare(m) == Some(Ordering::Less))
+                {
+                    *min = Some(value);
+                }
+            }
+            State::Max(max) => {
+                if max
+                    .as_ref()
+                    .is_none_or(|m| value.compare(m) == Some(Ordering::Greater))
+                {
+                    *max = Some(value);
+                }
+            }
+        }
+        Ok(used)
+    }
+
+    fn finish(&self) -> Value {
+        match &self.state {
+            State::Count(count) => Value::Integer(*count),
+            State::Avg { count: 0, .. } => Value::Null,
+            State::Avg { sum, count } => Value::Real(sum / *count as f64),
+            State::Sum(value) | State::Min(value) | State::Max(value) => {
+                value.clone().unwrap_or(Value::Null)
+            }
+        }
+    }
+}
+
+fn to_real(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Integer(v) => Ok(*v as f64),
+        Value::Real(v) => Ok(*v),
+        value => Err(format!("Cannot add up {} values", value)),
+    }
+}
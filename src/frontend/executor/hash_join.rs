@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use crate::{
+    frontend::{
+        ast::JoinKind,
+        binder::BoundExpr,
+        executor::{
+            Executor, Tuple,
+            expression::{evaluate, is_true},
+            hash_key, joined_row, values_size,
+        },
+    },
+    row::Row,
+    types::Value,
+};
+
+// Equi-join: load the right side into a hash table keyed on `right_keys`,
+// then probe it with `left_keys` computed from each left row.
+// Key pairs have the same type on both sides, so equal values encode to equal bytes.
+// When the right side does not fit in `memory_budget` bytes, it is loaded in chunks that do,
+// and the left side is scanned again for every chunk (a block nested loop join).
+pub struct HashJoin {
+    left: Box<dyn Executor>,
+    right: Box<dyn Executor>,
+    // Over the left row
+    left_keys: Vec<BoundExpr>,
+    // Over the right row alone
+    right_keys: Vec<BoundExpr>,
+    kind: JoinKind,
+    // Checked on the joined row, for the parts of ON that are not key equalities
+    condition: Option<BoundExpr>,
+    right_width: usize,
+    memory_budget: usize,
+    table: HashMap<Vec<u8>, Vec<Row>>,
+    // Right rows read before the chunk in `table`
+    chunk_start: usize,
+    // Whether `table` holds the last chunk of the right side
+    last_chunk: bool,
+    // Left rows read in this pass, and for a LEFT JOIN over several chunks
+    // whether each of them matched a previous chunk
+    left_position: usize,
+    left_matched: Vec<bool>,
+    current: Option<Row>,
+    // Bucket of the current left row, and the position in it
+    bucket: Option<Vec<u8>>,
+    position: usize,
+    matched: bool,
+}
+
+impl HashJoin {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        left: Box<dyn Executor>,
+        right: Box<dyn Executor>,
+        left_keys: Vec<BoundExpr>,
+        right_keys: Vec<BoundExpr>,
+        kind: JoinKind,
+        condition: Option<BoundExpr>,
+        right_width: usize,
+        memory_budget: usize,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            left_keys,
+            right_keys,
+            kind,
+            condition,
+            right_width,
+            memory_budget,
+            table: HashMap::new(),
+            chunk_start: 0,
+            last_chunk: true,
+            left_position: 0,
+            left_matched: Vec::new(),
+            current: None,
+            bucket: None,
+            position: 0,
+            matched: false,
+        }
+    }
+
+    // Load the next chunk of the right side, starting at `chunk_start`
+    // A chunk holds at least one row, even one larger than the budget.
+    fn build(&mut self) -> Result<(), String> {
+        self.table.clear();
+        self.right.init()?;
+
+        let mut read = 0;
+        let mut used = 0;
+        while let Some(tuple) = self.right.next()? {
+            read += 1;
+            if read <= self.chunk_start {
+                continue;
+            }
+
+            // A NULL key never equals anything, the row can't match
+            let Some(key) = join_key(&self.right_keys, &tuple.row)? else {
+                continue;
+            };
+
+            let size = values_size(&tuple.row.values) + key.len();
+            if used + size > self.memory_budget && !self.table.is_empty() {
+                // This row starts the next chunk
+                self.chunk_start = read - 1;
+                self.last_chunk = false;
+                return Ok(());
+            }
+            used += size;
+            self.table.entry(key).or_default().push(tuple.row);
+        }
+        self.last_chunk = true;
+        Ok(())
+    }
+}
+
+// Encoded key values, None if one of them is NULL
+fn join_key(keys: &[BoundExpr], row: &Row) -> Result<Option<Vec<u8>>, String> {
+    let mut values = Vec::with_capacity(keys.len());
+    for key in keys {
+        match evaluate(key, row)? {
+            Value::Null => return Ok(None),
+            value => values.push(value),
+        }
+    }
+    Ok(Some(hash_key(&values)))
+}
+
+impl Executor for HashJoin {
+    fn init(&mut self) -> Result<(), String> {
+        self.current = None;
+        self.chunk_start = 0;
+        self.left_position = 0;
+        self.left_matched.clear();
+        self.build()?;
+        self.left.init()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        loop {
+            let Some(left) = &self.current else {
+                match self.left.next()? {
+                    Some(tuple) => {
+                        self.bucket = join_key(&self.left_keys, &tuple.row)?;
+                        self.position = 0;
+                        self.current = Some(tuple.row);
+                        self.matched = self.left_matched.get(self.left_position) == Some(&true);
+                        self.left_position += 1;
+                    }
+                    None if self.last_chunk => return Ok(None),
+                    None => {
+                        // Join the whole left side again with the next chunk
+                        self.build()?;
+                        self.left.init()?;
+                        self.left_position = 0;
+                    }
+                }
+                continue;
+            };
+
+            let right = self
+                .bucket
+                .as_ref()
+                .and_then(|key| self.table.get(key))
+                .and_then(|rows| rows.get(self.position));
+
+            match right {
+                Some(right) => {
+                    self.position += 1;
+                    let tuple = joined_row(left, Some(right), self.right_width);
+                    let keep = match &self.condition {
+                        Some(condition) => is_true(&evaluate(condition, &tuple.row)?),
+                        None => true,
+                    };
+                    if keep {
+                        self.matched = true;
+                        return Ok(Some(tuple));
+                    }
+                }
+                None => {
+                    let left = self.current.take().unwrap();
+                    if self.kind == JoinKind::Left && !self.last_chunk {
+                        // Only known to be unmatched after the last chunk
+                        let i = self.left_position - 1;
+                        if i < self.left_matched.len() {
+                            self.left_matched[i] = self.matched;
+                        } else {
+                            self.left_matched.push(self.matched);
+                        }
+                    } else if self.kind == JoinKind::Left && !self.matched {
+                        return Ok(Some(joined_row(&left, None, self.right_width)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frontend::{ast::BinaryOp, executor::values::Values},
+        types::DataType,
+    };
+
+    fn column(index: usize) -> BoundExpr {
+        BoundExpr::Column {
+            index,
+            name: format!("c{}", index),
+            data_type: DataType::Integer,
+        }
+    }
+
+    fn values(rows: Vec<Vec<Value>>) -> Box<dyn Executor> {
+        let rows = rows
+            .into_iter()
+            .map(|row| row.into_iter().map(BoundExpr::Literal).collect())
+            .collect();
+        Box::new(Values::new(rows))
+    }
+
+    // (key, id): keys repeat on both sides, and some are NULL
+    fn side(count: i64, modulo: i64) -> Vec<Vec<Value>> {
+        (0..count)
+            .map(|id| {
+                let key = match id % 7 {
+                    0 => Value::Null,
+                    _ => Value::Integer(id % modulo),
+                };
+                vec![key, Value::Integer(id)]
+            })
+            .collect()
+    }
+
+    fn join(kind: JoinKind, condition: Option<BoundExpr>, memory_budget: usize) -> Vec<Vec<Value>> {
+        let mut join = HashJoin::new(
+            values(side(60, 9)),
+            values(side(80, 12)),
+            vec![column(0)],
+            vec![column(0)],
+            kind,
+            condition,
+            2,
+            memory_budget,
+        );
+        join.init().unwrap();
+
+        let mut rows = Vec::new();
+        while let Some(tuple) = join.next().unwrap() {
+            rows.push(tuple.row.values);
+        }
+        rows.sort_by(|a, b| format!("{:?}", a).cmp(&format!("{:?}", b)));
+        rows
+    }
+
+    #[test]
+    fn over_the_budget_gives_the_same_rows_as_in_memory() {
+        // Left id < right id, checked on the joined row
+        let condition = BoundExpr::Binary {
+            left: Box::new(column(1)),
+            op: BinaryOp::Lt,
+            right: Box::new(column(3)),
+        };
+
+        for kind in [JoinKind::Inner, JoinKind::Left] {
+            for condition in [None, Some(condition.clone())] {
+                let in_memory = join(kind, condition.clone(), usize::MAX);
+                assert!(!in_memory.is_empty());
+                // One row per chunk, then a few
+                for budget in [1, 100, 1000] {
+                    assert_eq!(join(kind, condition.clone(), budget), in_memory);
+                }
+            }
+        }
+    }
+}
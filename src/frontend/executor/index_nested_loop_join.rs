@@ -0,0 +1,132 @@
+use std::{ops::Bound, sync::Arc};
+
+use crate::{
+    catalog::schema::Schema,
+    frontend::{
+        ast::JoinKind,
+        binder::BoundExpr,
+        executor::{
+            Executor, Tuple,
+            expression::{evaluate, is_true},
+            joined_row,
+        },
+    },
+    indexing::{
+        btree::index::{BTreeIndex, IndexIterator},
+        table_heap::TableHeap,
+    },
+    row::Row,
+    types::{DataType, Value},
+};
+
+// For every left row, look up the matching right tuples in an index of the right table
+// `probe` computes the key from the left row, one expression per leading index column,
+// converted to the column type. The whole condition is still checked on each pair.
+pub struct IndexNestedLoopJoin {
+    left: Box<dyn Executor>,
+    table_heap: Arc<TableHeap>,
+    schema: Schema,
+    index: BTreeIndex,
+    probe: Vec<(BoundExpr, DataType)>,
+    kind: JoinKind,
+    condition: Option<BoundExpr>,
+    current: Option<Row>,
+    // RIDs of the right tuples with the current key, None if the key has a NULL
+    matches: Option<IndexIterator>,
+    matched: bool,
+}
+
+impl IndexNestedLoopJoin {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        left: Box<dyn Executor>,
+        table_heap: Arc<TableHeap>,
+        schema: Schema,
+        index: BTreeIndex,
+        probe: Vec<(BoundExpr, DataType)>,
+        kind: JoinKind,
+        condition: Option<BoundExpr>,
+    ) -> Self {
+        Self {
+            left,
+            table_heap,
+            schema,
+            index,
+            probe,
+            kind,
+            condition,
+            current: None,
+            matches: None,
+            matched: false,
+        }
+    }
+
+    // Index entries for a left row, NULL keys match nothing
+    fn lookup(&self, row: &Row) -> Result<Option<IndexIterator>, String> {
+        let mut key = Vec::with_capacity(self.probe.len());
+        for (expr, data_type) in &self.probe {
+            let value = evaluate(expr, row)?;
+            if value.is_null() {
+                return Ok(None);
+            }
+            key.push(value.coerce(*data_type)?);
+        }
+        let key: &[Value] = &key;
+        Ok(Some(
+            self.index
+                .scan(Bound::Included(key), Bound::Included(key))?,
+        ))
+    }
+}
+
+impl Executor for IndexNestedLoopJoin {
+    fn init(&mut self) -> Result<(), String> {
+        self.current = None;
+        self.matches = None;
+        self.left.init()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        let right_width = self.schema.columns.len();
+        loop {
+            let Some(left) = &self.current else {
+                match self.left.next()? {
+                    Some(tuple) => {
+                        self.matches = self.lookup(&tuple.row)?;
+                        self.current = Some(tuple.row);
+                        self.matched = false;
+                    }
+                    None => return Ok(None),
+                }
+                continue;
+            };
+
+            match self.matches.as_mut().and_then(|matches| matches.next()) {
+                Some(rid) => {
+                    let (page_id, slot_id) = rid?;
+                    // An entry for a deleted tuple, or for another version of it
+                    let Some(bytes) = self.table_heap.read_visible(page_id, slot_id)? else {
+                        continue;
+                    };
+                    let right = Row::deserialize(&bytes, &self.schema)?;
+
+                    let tuple = joined_row(left, Some(&right), right_width);
+                    let keep = match &self.condition {
+                        Some(condition) => is_true(&evaluate(condition, &tuple.row)?),
+                        None => true,
+                    };
+                    if keep {
+                        self.matched = true;
+                        return Ok(Some(tuple));
+                    }
+                }
+                None => {
+                    let left = self.current.take().unwrap();
+                    if self.kind == JoinKind::Left && !self.matched {
+                        return Ok(Some(joined_row(&left, None, right_width)));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::{collections::HashSet, ops::Bound, sync::Arc};
+
+use crate::{
+    catalog::schema::Schema,
+    frontend::executor::{Executor, Tuple},
+    indexing::{
+        btree::index::{BTreeIndex, IndexIterator, Rid},
+        table_heap::TableHeap,
+    },
+    row::Row,
+};
+
+// Walk a key range of an index, and fetch each matching tuple by RID
+// Bounds are encoded tree keys (see `index::key_bounds`)
+// Under MVCC the index also has entries for the old keys of a tuple,
+// so a RID may come up twice and its visible version may not match the key.
+pub struct IndexScan {
+    table_heap: Arc<TableHeap>,
+    schema: Schema,
+    index: BTreeIndex,
+    lower: Bound<Vec<u8>>,
+    upper: Bound<Vec<u8>>,
+    iterator: Option<IndexIterator>,
+    seen: HashSet<Rid>,
+}
+
+impl IndexScan {
+    pub fn new(
+        table_heap: Arc<TableHeap>,
+        schema: Schema,
+        index: BTreeIndex,
+        lower: Bound<Vec<u8>>,
+        upper: Bound<Vec<u8>>,
+    ) -> Self {
+        Self {
+            table_heap,
+            schema,
+            index,
+            lower,
+            upper,
+            iterator: None,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl Executor for IndexScan {
+    fn init(&mut self) -> Result<(), String> {
+        let iterator = self
+            .index
+            .scan_encoded(self.lower.clone(), self.upper.clone())?;
+        self.iterator = Some(iterator);
+        self.seen.clear();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        let iterator = self
+            .iterator
+            .as_mut()
+            .ok_or("IndexScan is not initialized")?;
+
+        for rid in iterator.by_ref() {
+            let (page_id, slot_id) = rid?;
+            if !self.seen.insert((page_id, slot_id)) {
+                continue;
+            }
+
+            if let Some(bytes) = self.table_heap.read_visible(page_id, slot_id)? {
+                let row = Row::deserialize(&bytes, &self.schema)?;
+                return Ok(Some(Tuple::new(row, Some((page_id, slot_id)))));
+            }
+        }
+        Ok(None)
+    }
+}
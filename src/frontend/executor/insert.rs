@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use crate::{
+    acid::lock_manager::LockMode,
+    catalog::schema::Schema,
+    frontend::executor::{Executor, TableIndex, Tuple},
+    indexing::table_heap::TableHeap,
+    row::Row,
+    types::Value,
+};
+
+// Insert every row of the child into the table, and into its indexes
+// Returns a single row: the number of inserted rows
+pub struct Insert {
+    table_heap: Arc<TableHeap>,
+    schema: Schema,
+    indexes: Vec<TableIndex>,
+    child: Box<dyn Executor>,
+    done: bool,
+}
+
+impl Insert {
+    pub fn new(
+        table_heap: Arc<TableHeap>,
+        schema: Schema,
+        indexes: Vec<TableIndex>,
+        child: Box<dyn Executor>,
+    ) -> Self {
+        Self {
+            table_heap,
+            schema,
+            indexes,
+            child,
+            done: false,
+        }
+    }
+}
+
+impl Executor for Insert {
+    fn init(&mut self) -> Result<(), String> {
+        self.done = false;
+        self.table_heap.lock_table(LockMode::IntentionExclusive)?;
+        self.child.init()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+
+        let mut count = 0;
+        while let Some(tuple) = self.child.next()? {
+            let row = conform(tuple.row, &self.schema)?;
+            let rid = self.table_heap.insert(&row.serialize(&self.schema)?)?;
+            for index in &self.indexes {
+                index.index.insert(&index.key(&row), rid)?;
+            }
+            count += 1;
+        }
+        Ok(Some(Tuple::new(
+            Row::new(vec![Value::Integer(count)]),
+            None,
+        )))
+    }
+}
+
+// Convert each value to its column type (INTEGER into a REAL column)
+pub fn conform(row: Row, schema: &Schema) -> Result<Row, String> {
+    let values = row
+        .values
+        .into_iter()
+        .zip(&schema.columns)
+        .map(|(value, column)| {
+            value
+                .coerce(column.data_type)
+                .map_err(|e| format!("{} ('{}')", e, column.name))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Row::new(values))
+}
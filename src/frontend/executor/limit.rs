@@ -0,0 +1,46 @@
+use crate::frontend::executor::{Executor, Tuple};
+
+// Skip `offset` rows, then return at most `limit` rows
+pub struct Limit {
+    child: Box<dyn Executor>,
+    limit: Option<usize>,
+    offset: usize,
+    emitted: usize,
+}
+
+impl Limit {
+    pub fn new(child: Box<dyn Executor>, limit: Option<usize>, offset: usize) -> Self {
+        Self {
+            child,
+            limit,
+            offset,
+            emitted: 0,
+        }
+    }
+}
+
+impl Executor for Limit {
+    fn init(&mut self) -> Result<(), String> {
+        self.emitted = 0;
+        self.child.init()?;
+
+        for _ in 0..self.offset {
+            if self.child.next()?.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        if self.limit.is_some_and(|limit| self.emitted >= limit) {
+            return Ok(None);
+        }
+
+        let tuple = self.child.next()?;
+        if tuple.is_some() {
+            self.emitted += 1;
+        }
+        Ok(tuple)
+    }
+}
@@ -0,0 +1,104 @@
+pub mod delete;
+pub mod expression;
+pub mod filter;
+pub mod hash_aggregate;
+pub mod hash_join;
+pub mod index_nested_loop_join;
+pub mod index_scan;
+pub mod insert;
+pub mod limit;
+pub mod nested_loop_join;
+pub mod profile;
+pub mod projection;
+pub mod seq_scan;
+pub mod sort;
+pub mod update;
+pub mod values;
+
+use crate::{
+    indexing::btree::{
+        index::{BTreeIndex, Rid},
+        key::encode_key,
+    },
+    row::Row,
+    types::Value,
+};
+
+// A row flowing between operators
+// `rid` is set while the row still maps to a stored tuple, so DML can find it again
+#[derive(Debug, Clone)]
+pub struct Tuple {
+    pub row: Row,
+    pub rid: Option<Rid>,
+}
+
+impl Tuple {
+    pub fn new(row: Row, rid: Option<Rid>) -> Self {
+        Self { row, rid }
+    }
+}
+
+// Volcano model: a plan is a tree of executors,
+// each one pulls rows from its children one at a time
+pub trait Executor {
+    // Prepare the operator (and its children), called once before `next`
+    fn init(&mut self) -> Result<(), String>;
+
+    // The next row, None once the operator is exhausted
+    fn next(&mut self) -> Result<Option<Tuple>, String>;
+}
+
+// An index of the table a DML operator writes to
+pub struct TableIndex {
+    pub index: BTreeIndex,
+    // Positions of the indexed columns in the table schema
+    pub columns: Vec<usize>,
+}
+
+impl TableIndex {
+    pub fn key(&self, row: &Row) -> Vec<Value> {
+        self.columns
+            .iter()
+            .map(|&i| row.values[i].clone())
+            .collect()
+    }
+}
+
+// Output of a join: the left row followed by the right one,
+// or by NULLs when a LEFT join found no match
+pub fn joined_row(left: &Row, right: Option<&Row>, right_width: usize) -> Tuple {
+    let mut values = Vec::with_capacity(left.values.len() + right_width);
+    values.extend(left.values.iter().cloned());
+    match right {
+        Some(right) => values.extend(right.values.iter().cloned()),
+        None => values.resize(left.values.len() + right_width, Value::Null),
+    }
+    Tuple::new(Row::new(values), None)
+}
+
+// Rough number of bytes values take in memory, for operators with a memory budget
+pub fn values_size(values: &[Value]) -> usize {
+    let data: usize = values
+        .iter()
+        .map(|value| match value {
+            Value::Text(s) => s.len(),
+            Value::Blob(b) => b.len(),
+            _ => 0,
+        })
+        .sum();
+    std::mem::size_of::<Row>() + std::mem::size_of_val(values) + data
+}
+
+// Bytes identifying a list of values in a hash table: equal values give equal bytes
+// Values of one key position must all have the same type
+pub fn hash_key(values: &[Value]) -> Vec<u8> {
+    let values: Vec<Value> = values
+        .iter()
+        .map(|value| match value {
+            // -0.0 = 0.0, but their encodings differ
+            Value::Real(0.0) => Value::Real(0.0),
+            value => value.clone(),
+        })
+        .collect();
+    encode_key(&values)
+}
@@ -0,0 +1,90 @@
+use crate::{
+    frontend::{
+        ast::JoinKind,
+        binder::BoundExpr,
+        executor::{
+            Executor, Tuple,
+            expression::{evaluate, is_true},
+            joined_row,
+        },
+    },
+    row::Row,
+};
+
+// For every left row, run the right side again from the start
+// and keep the pairs matching the condition.
+// Needs no memory beyond the current left row, the right side is usually a SeqScan.
+pub struct NestedLoopJoin {
+    left: Box<dyn Executor>,
+    right: Box<dyn Executor>,
+    kind: JoinKind,
+    // None joins every pair
+    condition: Option<BoundExpr>,
+    right_width: usize,
+    current: Option<Row>,
+    // Whether the current left row matched any right row
+    matched: bool,
+}
+
+impl NestedLoopJoin {
+    pub fn new(
+        left: Box<dyn Executor>,
+        right: Box<dyn Executor>,
+        kind: JoinKind,
+        condition: Option<BoundExpr>,
+        right_width: usize,
+    ) -> Self {
+        Self {
+            left,
+            right,
+            kind,
+            condition,
+            right_width,
+            current: None,
+            matched: false,
+        }
+    }
+}
+
+impl Executor for NestedLoopJoin {
+    fn init(&mut self) -> Result<(), String> {
+        self.current = None;
+        self.left.init()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        loop {
+            let Some(left) = &self.current else {
+                match self.left.next()? {
+                    Some(tuple) => {
+                        self.current = Some(tuple.row);
+                        self.matched = false;
+                        self.right.init()?;
+                    }
+                    None => return Ok(None),
+                }
+                continue;
+            };
+
+            match self.right.next()? {
+                Some(right) => {
+                    let tuple = joined_row(left, Some(&right.row), self.right_width);
+                    let keep = match &self.condition {
+                        Some(condition) => is_true(&evaluate(condition, &tuple.row)?),
+                        None => true,
+                    };
+                    if keep {
+                        self.matched = true;
+                        return Ok(Some(tuple));
+                    }
+                }
+                None => {
+                    let left = self.current.take().unwrap();
+                    if self.kind == JoinKind::Left && !self.matched {
+                        return Ok(Some(joined_row(&left, None, self.right_width)));
+                    }
+                }
+            }
+        }
+    }
+}
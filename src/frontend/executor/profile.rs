@@ -0,0 +1,71 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    backend::cache::Cache,
+    frontend::executor::{Executor, Tuple},
+};
+
+// What an operator did while the query ran, for EXPLAIN ANALYZE
+// Time and buffer pool requests include the operator's children.
+#[derive(Debug, Default)]
+pub struct Profile {
+    // Calls to `init`: more than 1 when a join rescans its right side
+    pub loops: usize,
+    pub rows: usize,
+    pub elapsed: Duration,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+// Passes rows through unchanged, measuring the wrapped executor
+pub struct Profiled {
+    child: Box<dyn Executor>,
+    profile: Arc<Mutex<Profile>>,
+    cache: Arc<Cache>,
+}
+
+impl Profiled {
+    pub fn new(child: Box<dyn Executor>, profile: Arc<Mutex<Profile>>, cache: Arc<Cache>) -> Self {
+        Self {
+            child,
+            profile,
+            cache,
+        }
+    }
+
+    fn measure<T>(
+        &mut self,
+        f: impl FnOnce(&mut dyn Executor) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let before = self.cache.stats();
+        let start = Instant::now();
+        let result = f(self.child.as_mut());
+        let elapsed = start.elapsed();
+        let after = self.cache.stats();
+
+        let mut profile = self.profile.lock().unwrap();
+        profile.elapsed += elapsed;
+        profile.hits += after.hits - before.hits;
+        profile.misses += after.misses - before.misses;
+        result
+    }
+}
+
+impl Executor for Profiled {
+    fn init(&mut self) -> Result<(), String> {
+        self.measure(|child| child.init())?;
+        self.profile.lock().unwrap().loops += 1;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        let tuple = self.measure(|child| child.next())?;
+        if tuple.is_some() {
+            self.profile.lock().unwrap().rows += 1;
+        }
+        Ok(tuple)
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{
+    frontend::{
+        binder::BoundExpr,
+        executor::{Executor, Tuple, expression::evaluate},
+    },
+    row::Row,
+};
+
+// Compute the output columns of each row
+pub struct Projection {
+    child: Box<dyn Executor>,
+    exprs: Vec<BoundExpr>,
+}
+
+impl Projection {
+    pub fn new(child: Box<dyn Executor>, exprs: Vec<BoundExpr>) -> Self {
+        Self { child, exprs }
+    }
+}
+
+impl Executor for Projection {
+    fn init(&mut self) -> Result<(), String> {
+        self.child.init()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        let Some(tuple) = self.child.next()? else {
+            return Ok(None);
+        };
+
+        let values = self
+            .exprs
+            .iter()
+            .map(|expr| evaluate(expr, &tuple.row))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(Tuple::new(Row::new(values), tuple.rid)))
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use crate::{
+    acid::lock_manager::LockMode,
+    catalog::schema::Schema,
+    frontend::executor::{Executor, Tuple},
+    indexing::{table_heap::TableHeap, table_iterator::TableIterator},
+    row::Row,
+};
+
+// Full scan of a table heap, in storage order
+pub struct SeqScan {
+    table_heap: Arc<TableHeap>,
+    schema: Schema,
+    iterator: Option<TableIterator>,
+}
+
+impl SeqScan {
+    pub fn new(table_heap: Arc<TableHeap>, schema: Schema) -> Self {
+        Self {
+            table_heap,
+            schema,
+            iterator: None,
+        }
+    }
+}
+
+impl Executor for SeqScan {
+    fn init(&mut self) -> Result<(), String> {
+        // Rows are S-locked one by one as they are read
+        self.table_heap.lock_table(LockMode::IntentionShared)?;
+        let first_page_id = self.table_heap.first_page_id();
+        self.iterator = Some(TableIterator::new(self.table_heap.clone(), first_page_id));
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        let iterator = self.iterator.as_mut().ok_or("SeqScan is not initialized")?;
+
+        match iterator.try_next()? {
+            Some((rid, bytes)) => {
+                let row = Row::deserialize(&bytes, &self.schema)?;
+                Ok(Some(Tuple::new(row, Some(rid))))
+            }
+            None => Ok(None),
+        }
+    }
+}
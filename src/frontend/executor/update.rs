@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use crate::{
+    acid::lock_manager::LockMode,
+    catalog::schema::Schema,
+    frontend::{
+        binder::BoundExpr,
+        executor::{Executor, TableIndex, Tuple, expression::evaluate, insert::conform},
+    },
+    indexing::table_heap::TableHeap,
+    row::Row,
+    types::Value,
+};
+
+// Rewrite every row produced by the child (which must carry RIDs)
+// Index entries whose key changed are moved, the RID itself never changes
+// Under MVCC the old entry stays for the old version, until the garbage collector
+// Returns a single row: the number of updated rows
+pub struct Update {
+    table_heap: Arc<TableHeap>,
+    schema: Schema,
+    indexes: Vec<TableIndex>,
+    child: Box<dyn Executor>,
+    // (column index, new value computed from the old row)
+    assignments: Vec<(usize, BoundExpr)>,
+    done: bool,
+}
+
+impl Update {
+    pub fn new(
+        table_heap: Arc<TableHeap>,
+        schema: Schema,
+        indexes: Vec<TableIndex>,
+        child: Box<dyn Executor>,
+        assignments: Vec<(usize, BoundExpr)>,
+    ) -> Self {
+        Self {
+            table_heap,
+            schema,
+            indexes,
+            child,
+            assignments,
+            done: false,
+        }
+    }
+}
+
+impl Executor for Update {
+    fn init(&mut self) -> Result<(), String> {
+        self.done = false;
+        self.table_heap.lock_table(LockMode::IntentionExclusive)?;
+        self.child.init()
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+
+        // Compute every new row before writing any,
+        // so the scan never sees an updated row again
+        let mut updates = Vec::new();
+        while let Some(tuple) = self.child.next()? {
+            let rid = tuple.rid.ok_or("Update needs rows read from the table")?;
+            // Hold the row until the end of the transaction, nobody may change it meanwhile
+            self.table_heap.lock_tuple(rid, LockMode::Exclusive)?;
+
+            let mut values = tuple.row.values.clone();
+            for (index, expr) in &self.assignments {
+                values[*index] = evaluate(expr, &tuple.row)?;
+            }
+            let row = conform(Row::new(values), &self.schema)?;
+            updates.push((rid, tuple.row, row));
+        }
+
+        for (rid, old_row, new_row) in &updates {
+            self.table_heap
+                .update(rid.0, rid.1, &new_row.serialize(&self.schema)?)?;
+
+            for index in &self.indexes {
+                let old_key = index.key(old_row);
+                let new_key = index.key(new_row);
+                if old_key == new_key {
+                    continue;
+                }
+                if !self.table_heap.keeps_versions() {
+                    index.index.delete(&old_key, *rid)?;
+                } else if index.index.contains(&new_key, *rid)? {
+                    // Back to the key of an older version
+                    continue;
+                }
+                index.index.insert(&new_key, *rid)?;
+            }
+        }
+        let count = updates.len() as i64;
+        Ok(Some(Tuple::new(
+            Row::new(vec![Value::Integer(count)]),
+            None,
+        )))
+    }
+}
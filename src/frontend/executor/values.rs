@@ -0,0 +1,40 @@
+use crate::{
+    frontend::{
+        binder::BoundExpr,
+        executor::{Executor, Tuple, expression::evaluate},
+    },
+    row::Row,
+};
+
+// Rows of constant expressions (INSERT ... VALUES)
+pub struct Values {
+    rows: Vec<Vec<BoundExpr>>,
+    position: usize,
+}
+
+impl Values {
+    pub fn new(rows: Vec<Vec<BoundExpr>>) -> Self {
+        Self { rows, position: 0 }
+    }
+}
+
+impl Executor for Values {
+    fn init(&mut self) -> Result<(), String> {
+        self.position = 0;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Tuple>, String> {
+        let Some(exprs) = self.rows.get(self.position) else {
+            return Ok(None);
+        };
+        self.position += 1;
+
+        let empty = Row::new(Vec::new());
+        let values = exprs
+            .iter()
+            .map(|expr| evaluate(expr, &empty))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(Tuple::new(Row::new(values), None)))
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::{Arc, Mutex};
+
+use crate::frontend::{
+    executor::profile::Profile,
+    logical_plan::{Access, JoinStrategy, LogicalPlan},
+    optimizer::Optimizer,
+};
+
+// One operator of an EXPLAIN output
+pub struct PlanLine {
+    pub depth: usize,
+    pub text: String,
+    pub estimated_rows: f64,
+}
+
+// The operators of a plan in pre-order, the order `Planner::build_profiled` measures them in
+// The right table of an index lookup join is part of the join, not an operator of its own.
+pub fn describe(plan: &LogicalPlan, optimizer: &Optimizer) -> Result<Vec<PlanLine>, String> {
+    let mut lines = Vec::new();
+    describe_node(plan, optimizer, 0, &mut lines)?;
+    Ok(lines)
+}
+
+fn describe_node(
+    plan: &LogicalPlan,
+    optimizer: &Optimizer,
+    depth: usize,
+    lines: &mut Vec<PlanLine>,
+) -> Result<(), String> {
+    lines.push(PlanLine {
+        depth,
+        text: operator_text(plan),
+        estimated_rows: optimizer.estimate_rows(plan)?,
+    });
+
+    let inputs = match plan {
+        LogicalPlan::Join {
+            left,
+            strategy: JoinStrategy::IndexLookup { .. },
+            ..
+        } => vec![left.as_ref()],
+        plan => plan.inputs(),
+    };
+    for input in inputs {
+        describe_node(input, optimizer, depth + 1, lines)?;
+    }
+    Ok(())
+}
+
+fn operator_text(plan: &LogicalPlan) -> String {
+    let list = |items: Vec<String>| items.join(", ");
+    match plan {
+        LogicalPlan::Scan {
+            table,
+            filter,
+            access,
+        } => {
+            let mut text = match access {
+                Access::Seq => format!("Seq Scan on {}", table.name),
+                Access::Index { index, .. } => {
+                    format!("Index Scan on {} using {}", table.name, index.name)
+                }
+            };
+            if let Some(filter) = filter {
+                text += &format!(", filter: {}", filter);
+            }
+            text
+        }
+        LogicalPlan::Values { rows } => format!("Values: {} rows", rows.len()),
+        LogicalPlan::Filter { predicate, .. } => format!("Filter: {}", predicate),
+        LogicalPlan::Project { exprs, .. } => format!(
+            "Projection: {}",
+            list(exprs.iter().map(|e| e.to_string()).collect())
+        ),
+        LogicalPlan::Join {
+            right,
+            kind,
+            on,
+            strategy,
+            ..
+        } => {
+            // The condition checked on each pair: a hash join already matched the keys
+            let condition = match strategy {
+                JoinStrategy::Hash { residual, .. } => residual.as_ref(),
+                _ => on.as_ref(),
+            };
+            let mut text = match strategy {
+                JoinStrategy::NestedLoop => format!("Nested Loop Join ({})", kind),
+                JoinStrategy::IndexLookup { index, .. } => {
+                    let table = match right.as_ref() {
+                        LogicalPlan::Scan { table, .. } => table.name.as_str(),
+                        _ => "?",
+                    };
+                    format!(
+                        "Index Nested Loop Join ({}) with {} using {}",
+                        kind, table, index.name
+                    )
+                }
+                JoinStrategy::Hash {
+                    left_keys,
+                    right_keys,
+                    ..
+                } => {
+                    let keys = left_keys
+                        .iter()
+                        .zip(right_keys)
+                        .map(|(l, r)| format!("{} = {}", l, r))
+                        .collect();
+                    format!("Hash Join ({}), keys: {}", kind, list(keys))
+                }
+            };
+            if let Some(condition) = condition {
+                text += &format!(", on: {}", condition);
+            }
+            if let JoinStrategy::IndexLookup { .. } = strategy
+                && let LogicalPlan::Scan {
+                    filter: Some(filter),
+                    ..
+                } = right.as_ref()
+            {
+                text += &format!(", filter: {}", filter);
+            }
+            text
+        }
+        LogicalPlan::Aggregate {
+            group_by,
+            aggregates,
+            ..
+        } => {
+            let mut text = "Hash Aggregate".to_string();
+            if !group_by.is_empty() {
+                let group_by = group_by.iter().map(|e| e.to_string()).collect();
+                text += &format!(", group by: {}", list(group_by));
+            }
+            if !aggregates.is_empty() {
+                let aggregates = aggregates.iter().map(|a| a.to_string()).collect();
+                text += &format!(", aggregates: {}", list(aggregates));
+            }
+            text
+        }
+        LogicalPlan::Sort { keys, .. } => {
+            let keys = keys
+                .iter()
+                .map(|key| {
+                    let mut text = key.expr.to_string();
+                    if key.descending {
+                        text += " DESC";
+                    }
+                    // Only when it differs from the default
+                    if key.nulls_first == key.descending {
+                        text += if key.nulls_first {
+                            " NULLS FIRST"
+                        } else {
+                            " NULLS LAST"
+                        };
+                    }
+                    text
+                })
+                .collect();
+            format!("Sort: {}", list(keys))
+        }
+        LogicalPlan::Limit { limit, offset, .. } => match (limit, offset) {
+            (Some(limit), 0) => format!("Limit: {}", limit),
+            (Some(limit), offset) => format!("Limit: {}, offset: {}", limit, offset),
+            (None, offset) => format!("Offset: {}", offset),
+        },
+        LogicalPlan::Insert { table, .. } => format!("Insert into {}", table.name),
+        LogicalPlan::Update { table, .. } => format!("Update {}", table.name),
+        LogicalPlan::Delete { table, .. } => format!("Delete from {}", table.name),
+    }
+}
+
+// The plan as indented text, with what each operator did when profiles are given
+pub fn render(lines: &[PlanLine], profiles: Option<&[Arc<Mutex<Profile>>]>) -> Vec<String> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let indent = match line.depth {
+                0 => String::new(),
+                depth => format!("{}-> ", "   ".repeat(depth - 1)),
+            };
+            let mut stats = format!("estimated rows: {:.0}", line.estimated_rows);
+            if let Some(profile) = profiles.and_then(|p| p.get(i)) {
+                let profile = profile.lock().unwrap();
+                stats += &format!(
+                    ", actual rows: {}, loops: {}, time: {:.3} ms, buffer hits: {}, misses: {}",
+                    profile.rows,
+                    profile.loops,
+                    profile.elapsed.as_secs_f64() * 1000.0,
+                    profile.hits,
+                    profile.misses
+                );
+            }
+            format!("{}{}  ({})", indent, line.text, stats)
+        })
+        .collect()
+}
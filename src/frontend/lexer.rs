@@ -0,0 +1,350 @@
+use crate::frontend::token::{Keyword, Position, SyntaxError, Token, TokenKind};
+
+// Turns SQL text into tokens
+// Whitespace and comments (`-- ...` and `/* ... */`) are skipped,
+// the returned list always ends with an Eof token.
+pub struct Lexer {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer {
+    pub fn new(input: &str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            index: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    pub fn tokenize(input: &str) -> Result<Vec<Token>, SyntaxError> {
+        let mut lexer = Lexer::new(input);
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token()?;
+            let done = token.kind == TokenKind::Eof;
+            tokens.push(token);
+            if done {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    pub fn next_token(&mut self) -> Result<Token, SyntaxError> {
+        self.skip_whitespace_and_comments()?;
+
+        let pos = self.pos();
+        let Some(c) = self.peek() else {
+            return Ok(Token {
+                kind: TokenKind::Eof,
+                pos,
+            });
+        };
+
+        let kind = match c {
+            'x' | 'X' if self.peek_at(1) == Some('\'') => self.read_blob()?,
+            c if is_identifier_start(c) => self.read_word(),
+            c if c.is_ascii_digit() => self.read_number()?,
+            '.' if self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) => self.read_number()?,
+            '\'' => TokenKind::String(self.read_quoted('\'', "string")?),
+            '"' => TokenKind::Identifier(self.read_quoted('"', "identifier")?),
+            '`' => TokenKind::Identifier(self.read_quoted('`', "identifier")?),
+            _ => self.read_symbol()?,
+        };
+
+        Ok(Token { kind, pos })
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('-'), Some('-')) => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    let start = self.pos();
+                    self.bump();
+                    self.bump();
+                    loop {
+                        match (self.peek(), self.peek_at(1)) {
+                            (Some('*'), Some('/')) => {
+                                self.bump();
+                                self.bump();
+                                break;
+                            }
+                            (Some(_), _) => {
+                                self.bump();
+                            }
+                            (None, _) => {
+                                return Err(SyntaxError::new("Unterminated comment", start));
+                            }
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // Keyword or unquoted identifier
+    fn read_word(&mut self) -> TokenKind {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|&c| is_identifier_part(c)) {
+            word.push(c);
+            self.bump();
+        }
+
+        match Keyword::from_word(&word) {
+            Some(keyword) => TokenKind::Keyword(keyword),
+            None => TokenKind::Identifier(word),
+        }
+    }
+
+    // 42, 3.14, .5, 1e10, 2.5E-3
+    // Integers are lexed as their magnitude, the parser decides whether they fit in an i64
+    // Integers too large even for u64 become reals
+    fn read_number(&mut self) -> Result<TokenKind, SyntaxError> {
+        let start = self.pos();
+        let mut text = String::new();
+        let mut is_real = false;
+
+        self.read_digits(&mut text);
+        if self.peek() == Some('.') {
+            is_real = true;
+            text.push('.');
+            self.bump();
+            self.read_digits(&mut text);
+        }
+
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            is_real = true;
+            text.push(e);
+            self.bump();
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                text.push(sign);
+                self.bump();
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.unexpected_char("Malformed number"));
+            }
+            self.read_digits(&mut text);
+        }
+
+        // `12abc` is one bad token, not a number then an identifier
+        if self.peek().is_some_and(is_identifier_part) {
+            return Err(self.unexpected_char("Malformed number"));
+        }
+
+        if !is_real && let Ok(n) = text.parse::<u64>() {
+            return Ok(TokenKind::Integer(n));
+        }
+        text.parse::<f64>()
+            .map(TokenKind::Real)
+            .map_err(|_| SyntaxError::new("Malformed number", start))
+    }
+
+    fn read_digits(&mut self, text: &mut String) {
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) {
+            text.push(c);
+            self.bump();
+        }
+    }
+
+    // Text between `quote`s, a doubled quote stands for itself ('it''s')
+    fn read_quoted(&mut self, quote: char, what: &str) -> Result<String, SyntaxError> {
+        let start = self.pos();
+        self.bump();
+
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => {
+                    if self.peek() == Some(quote) {
+                        text.push(quote);
+                        self.bump();
+                    } else {
+                        return Ok(text);
+                    }
+                }
+                Some(c) => text.push(c),
+                None => return Err(SyntaxError::new(format!("Unterminated {}", what), start)),
+            }
+        }
+    }
+
+    // x'0aff', an even number of hex digits
+    fn read_blob(&mut self) -> Result<TokenKind, SyntaxError> {
+        let start = self.pos();
+        self.bump();
+        self.bump();
+
+        let mut digits = Vec::new();
+        loop {
+            match self.peek() {
+                Some('\'') => {
+                    self.bump();
+                    break;
+                }
+                Some(c) if c.is_ascii_hexdigit() => {
+                    digits.push(c.to_digit(16).unwrap() as u8);
+                    self.bump();
+                }
+                Some(_) => return Err(self.unexpected_char("Invalid hex digit in blob")),
+                None => return Err(SyntaxError::new("Unterminated blob", start)),
+            }
+        }
+
+        if digits.len() % 2 != 0 {
+            return Err(SyntaxError::new(
+                "Blob literal needs an even number of hex digits",
+                start,
+            ));
+        }
+        Ok(TokenKind::Blob(
+            digits
+                .chunks(2)
+                .map(|pair| pair[0] << 4 | pair[1])
+                .collect(),
+        ))
+    }
+
+    fn read_symbol(&mut self) -> Result<TokenKind, SyntaxError> {
+        let c = self.peek().unwrap();
+        let next = self.peek_at(1);
+
+        let (kind, len) = match (c, next) {
+            ('|', Some('|')) => (TokenKind::Concat, 2),
+            ('=', Some('=')) => (TokenKind::Eq, 2),
+            ('!', Some('=')) => (TokenKind::NotEq, 2),
+            ('<', Some('>')) => (TokenKind::NotEq, 2),
+            ('<', Some('=')) => (TokenKind::LtEq, 2),
+            ('>', Some('=')) => (TokenKind::GtEq, 2),
+            ('+', _) => (TokenKind::Plus, 1),
+            ('-', _) => (TokenKind::Minus, 1),
+            ('*', _) => (TokenKind::Star, 1),
+            ('/', _) => (TokenKind::Slash, 1),
+            ('%', _) => (TokenKind::Percent, 1),
+            ('=', _) => (TokenKind::Eq, 1),
+            ('<', _) => (TokenKind::Lt, 1),
+            ('>', _) => (TokenKind::Gt, 1),
+            ('(', _) => (TokenKind::LParen, 1),
+            (')', _) => (TokenKind::RParen, 1),
+            (',', _) => (TokenKind::Comma, 1),
+            ('.', _) => (TokenKind::Dot, 1),
+            (';', _) => (TokenKind::Semicolon, 1),
+            _ => return Err(self.unexpected_char("Unrecognized character")),
+        };
+
+        for _ in 0..len {
+            self.bump();
+        }
+        Ok(kind)
+    }
+
+    fn unexpected_char(&self, message: &str) -> SyntaxError {
+        let pos = self.pos();
+        match self.peek() {
+            Some(c) => SyntaxError::new(format!("{} '{}'", message, c), pos),
+            None => SyntaxError::new(message, pos),
+        }
+    }
+
+    fn pos(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.index + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_identifier_part(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        frontend::{ast::Expr, parser::Parser},
+        types::Value,
+    };
+
+    fn kinds(sql: &str) -> Vec<TokenKind> {
+        Lexer::tokenize(sql)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    fn literal(sql: &str) -> Expr {
+        Parser::new(Lexer::tokenize(sql).unwrap())
+            .parse_expr()
+            .unwrap()
+    }
+
+    #[test]
+    fn integers_are_lexed_as_their_magnitude() {
+        assert_eq!(
+            kinds("-9223372036854775808 18446744073709551615"),
+            vec![
+                TokenKind::Minus,
+                TokenKind::Integer(9_223_372_036_854_775_808),
+                TokenKind::Integer(u64::MAX),
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(
+            kinds("18446744073709551616"),
+            vec![TokenKind::Real(18446744073709551616.0), TokenKind::Eof]
+        );
+    }
+
+    #[test]
+    fn the_parser_applies_negation() {
+        assert_eq!(
+            literal("-9223372036854775808"),
+            Expr::Literal(Value::Integer(i64::MIN))
+        );
+        assert_eq!(
+            literal("9223372036854775808"),
+            Expr::Literal(Value::Real(9223372036854775808.0))
+        );
+        assert_eq!(
+            literal("- -9223372036854775807"),
+            Expr::Literal(Value::Integer(i64::MAX))
+        );
+    }
+}
@@ -0,0 +1,445 @@
+use std::ops::Bound;
+
+use crate::{
+    catalog::manager::{IndexInfo, TableInfo},
+    frontend::{
+        ast::JoinKind,
+        binder::{AggregateCall, BoundExpr, BoundSelect, BoundStatement, SortKey},
+        executor::expression::evaluate,
+    },
+    row::Row,
+    types::{DataType, Value},
+};
+
+// What a statement computes, as a tree of relational operators
+// Built from a bound statement, rewritten by the optimizer, then turned into executors by the planner.
+// Expressions of a node read the output row of its input
+// (for a join: the left row followed by the right one).
+#[derive(Debug, Clone)]
+pub enum LogicalPlan {
+    // Rows of a table for which the filter is TRUE
+    Scan {
+        table: TableInfo,
+        filter: Option<BoundExpr>,
+        access: Access,
+    },
+    Values {
+        rows: Vec<Vec<BoundExpr>>,
+    },
+    Filter {
+        input: Box<LogicalPlan>,
+        predicate: BoundExpr,
+    },
+    Project {
+        input: Box<LogicalPlan>,
+        exprs: Vec<BoundExpr>,
+    },
+    Join {
+        left: Box<LogicalPlan>,
+        right: Box<LogicalPlan>,
+        kind: JoinKind,
+        on: Option<BoundExpr>,
+        strategy: JoinStrategy,
+    },
+    // Output: the GROUP BY values, followed by one value per aggregate
+    Aggregate {
+        input: Box<LogicalPlan>,
+        group_by: Vec<BoundExpr>,
+        aggregates: Vec<AggregateCall>,
+    },
+    Sort {
+        input: Box<LogicalPlan>,
+        keys: Vec<SortKey>,
+    },
+    Limit {
+        input: Box<LogicalPlan>,
+        limit: Option<usize>,
+        offset: usize,
+    },
+    // DML nodes output a single row: the number of rows changed
+    Insert {
+        table: TableInfo,
+        input: Box<LogicalPlan>,
+    },
+    Update {
+        table: TableInfo,
+        input: Box<LogicalPlan>,
+        assignments: Vec<(usize, BoundExpr)>,
+    },
+    Delete {
+        table: TableInfo,
+        input: Box<LogicalPlan>,
+    },
+}
+
+// How a Scan reads its table
+#[derive(Debug, Clone)]
+pub enum Access {
+    Seq,
+    // Only the entries of an index within the bounds (encoded keys, see `index::key_bounds`)
+    Index {
+        index: IndexInfo,
+        lower: Bound<Vec<u8>>,
+        upper: Bound<Vec<u8>>,
+    },
+}
+
+// How a Join finds the right rows matching a left row
+#[derive(Debug, Clone)]
+pub enum JoinStrategy {
+    // Rescan the right side for every left row
+    NestedLoop,
+    // Look the right table up in an index, the right side must be a Scan
+    // `probe`: the key, computed from the left row, and the type of each index column
+    IndexLookup {
+        index: IndexInfo,
+        probe: Vec<(BoundExpr, DataType)>,
+    },
+    // Hash the right side on `right_keys` (over the right row),
+    // probe with `left_keys` (over the left row), then check `residual` on the pair
+    Hash {
+        left_keys: Vec<BoundExpr>,
+        right_keys: Vec<BoundExpr>,
+        residual: Option<BoundExpr>,
+    },
+}
+
+impl LogicalPlan {
+    // The plan of a statement, with the names of its output columns
+    // DDL statements have no plan
+    pub fn build(statement: BoundStatement) -> Result<(Self, Vec<String>), String> {
+        let rows = vec!["rows".to_string()];
+        match statement {
+            BoundStatement::Select(select) => Self::build_select(*select),
+            BoundStatement::Insert {
+                table,
+                rows: values,
+            } => Ok((
+                LogicalPlan::Insert {
+                    table,
+                    input: Box::new(LogicalPlan::Values { rows: values }),
+                },
+                rows,
+            )),
+            BoundStatement::Update {
+                table,
+                assignments,
+                filter,
+            } => Ok((
+                LogicalPlan::Update {
+                    input: Box::new(Self::scan(&table).filter(filter)),
+                    table,
+                    assignments,
+                },
+                rows,
+            )),
+            BoundStatement::Delete { table, filter } => Ok((
+                LogicalPlan::Delete {
+                    input: Box::new(Self::scan(&table).filter(filter)),
+                    table,
+                },
+                rows,
+            )),
+            BoundStatement::CreateTable { .. }
+            | BoundStatement::DropTable { .. }
+            | BoundStatement::CreateIndex { .. } => {
+                Err("DDL statements are not planned".to_string())
+            }
+            BoundStatement::Explain { .. } => Err("EXPLAIN is not planned".to_string()),
+            BoundStatement::Begin | BoundStatement::Commit | BoundStatement::Rollback => {
+                Err("Transaction statements are not planned".to_string())
+            }
+        }
+    }
+
+    // Scan <- Join ... <- Filter (WHERE) <- Aggregate <- Filter (HAVING) <- Sort <- Project <- Limit
+    fn build_select(select: BoundSelect) -> Result<(Self, Vec<String>), String> {
+        let mut plan = match &select.from {
+            Some(table) => Self::scan(table),
+            // SELECT without FROM reads a single empty row
+            None => LogicalPlan::Values {
+                rows: vec![Vec::new()],
+            },
+        };
+
+        for join in select.joins {
+            plan = LogicalPlan::Join {
+                left: Box::new(plan),
+                right: Box::new(Self::scan(&join.table)),
+                kind: join.kind,
+                on: join.on,
+                strategy: JoinStrategy::NestedLoop,
+            };
+        }
+        plan = plan.filter(select.filter);
+
+        if let Some(aggregation) = select.aggregation {
+            plan = LogicalPlan::Aggregate {
+                input: Box::new(plan),
+                group_by: aggregation.group_by,
+                aggregates: aggregation.aggregates,
+            };
+            plan = plan.filter(select.having);
+        }
+
+        if !select.order_by.is_empty() {
+            plan = LogicalPlan::Sort {
+                input: Box::new(plan),
+                keys: select.order_by,
+            };
+        }
+
+        let (exprs, names): (Vec<_>, Vec<_>) = select.projection.into_iter().unzip();
+        plan = LogicalPlan::Project {
+            input: Box::new(plan),
+            exprs,
+        };
+
+        if select.limit.is_some() || select.offset.is_some() {
+            let limit = select.limit.as_ref().map(constant_count).transpose()?;
+            let offset = select.offset.as_ref().map(constant_count).transpose()?;
+            plan = LogicalPlan::Limit {
+                input: Box::new(plan),
+                limit,
+                offset: offset.unwrap_or(0),
+            };
+        }
+
+        Ok((plan, names))
+    }
+
+    fn scan(table: &TableInfo) -> Self {
+        LogicalPlan::Scan {
+            table: table.clone(),
+            filter: None,
+            access: Access::Seq,
+        }
+    }
+
+    fn filter(self, predicate: Option<BoundExpr>) -> Self {
+        match predicate {
+            Some(predicate) => LogicalPlan::Filter {
+                input: Box::new(self),
+                predicate,
+            },
+            None => self,
+        }
+    }
+
+    // Number of columns in an output row
+    pub fn width(&self) -> usize {
+        match self {
+            LogicalPlan::Scan { table, .. } => table.schema.columns.len(),
+            LogicalPlan::Values { rows } => rows.first().map_or(0, |row| row.len()),
+            LogicalPlan::Project { exprs, .. } => exprs.len(),
+            LogicalPlan::Join { left, right, .. } => left.width() + right.width(),
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => group_by.len() + aggregates.len(),
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. } => input.width(),
+            LogicalPlan::Insert { .. }
+            | LogicalPlan::Update { .. }
+            | LogicalPlan::Delete { .. } => 1,
+        }
+    }
+
+    // Name and type of each output column, None for a column that is always NULL
+    pub fn columns(&self) -> Vec<(String, Option<DataType>)> {
+        let of_exprs = |exprs: &mut dyn Iterator<Item = &BoundExpr>| {
+            exprs
+                .map(|e| (expr_name(e), e.data_type()))
+                .collect::<Vec<_>>()
+        };
+        match self {
+            LogicalPlan::Scan { table, .. } => table
+                .schema
+                .columns
+                .iter()
+                .map(|c| (c.name.clone(), Some(c.data_type)))
+                .collect(),
+            LogicalPlan::Values { rows } => rows
+                .first()
+                .map_or_else(Vec::new, |row| of_exprs(&mut row.iter())),
+            LogicalPlan::Project { exprs, .. } => of_exprs(&mut exprs.iter()),
+            LogicalPlan::Join { left, right, .. } => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => {
+                let mut columns = of_exprs(&mut group_by.iter());
+                columns.extend(
+                    aggregates
+                        .iter()
+                        .map(|a| (a.func.to_string(), a.data_type())),
+                );
+                columns
+            }
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. } => input.columns(),
+            LogicalPlan::Insert { .. }
+            | LogicalPlan::Update { .. }
+            | LogicalPlan::Delete { .. } => {
+                vec![("rows".to_string(), Some(DataType::Integer))]
+            }
+        }
+    }
+
+    pub fn inputs(&self) -> Vec<&LogicalPlan> {
+        match self {
+            LogicalPlan::Scan { .. } | LogicalPlan::Values { .. } => Vec::new(),
+            LogicalPlan::Join { left, right, .. } => vec![left, right],
+            LogicalPlan::Filter { input, .. }
+            | LogicalPlan::Project { input, .. }
+            | LogicalPlan::Aggregate { input, .. }
+            | LogicalPlan::Sort { input, .. }
+            | LogicalPlan::Limit { input, .. }
+            | LogicalPlan::Insert { input, .. }
+            | LogicalPlan::Update { input, .. }
+            | LogicalPlan::Delete { input, .. } => vec![input],
+        }
+    }
+
+    // The same node with each input replaced by `f(input)`
+    pub fn map_inputs(
+        self,
+        mut f: impl FnMut(LogicalPlan) -> Result<LogicalPlan, String>,
+    ) -> Result<LogicalPlan, String> {
+        let mut map = |input: Box<LogicalPlan>| f(*input).map(Box::new);
+        let plan = match self {
+            LogicalPlan::Scan { .. } | LogicalPlan::Values { .. } => self,
+            LogicalPlan::Filter { input, predicate } => LogicalPlan::Filter {
+                input: map(input)?,
+                predicate,
+            },
+            LogicalPlan::Project { input, exprs } => LogicalPlan::Project {
+                input: map(input)?,
+                exprs,
+            },
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                strategy,
+            } => LogicalPlan::Join {
+                left: map(left)?,
+                right: map(right)?,
+                kind,
+                on,
+                strategy,
+            },
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => LogicalPlan::Aggregate {
+                input: map(input)?,
+                group_by,
+                aggregates,
+            },
+            LogicalPlan::Sort { input, keys } => LogicalPlan::Sort {
+                input: map(input)?,
+                keys,
+            },
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => LogicalPlan::Limit {
+                input: map(input)?,
+                limit,
+                offset,
+            },
+            LogicalPlan::Insert { table, input } => LogicalPlan::Insert {
+                table,
+                input: map(input)?,
+            },
+            LogicalPlan::Update {
+                table,
+                input,
+                assignments,
+            } => LogicalPlan::Update {
+                table,
+                input: map(input)?,
+                assignments,
+            },
+            LogicalPlan::Delete { table, input } => LogicalPlan::Delete {
+                table,
+                input: map(input)?,
+            },
+        };
+        Ok(plan)
+    }
+
+    // Every expression of this node (not of its inputs)
+    pub fn exprs_mut(&mut self) -> Vec<&mut BoundExpr> {
+        match self {
+            LogicalPlan::Scan { filter, .. } => filter.iter_mut().collect(),
+            LogicalPlan::Values { rows } => rows.iter_mut().flatten().collect(),
+            LogicalPlan::Filter { predicate, .. } => vec![predicate],
+            LogicalPlan::Project { exprs, .. } => exprs.iter_mut().collect(),
+            LogicalPlan::Join { on, strategy, .. } => {
+                let mut exprs: Vec<&mut BoundExpr> = on.iter_mut().collect();
+                match strategy {
+                    JoinStrategy::NestedLoop => {}
+                    JoinStrategy::IndexLookup { probe, .. } => {
+                        exprs.extend(probe.iter_mut().map(|(e, _)| e))
+                    }
+                    JoinStrategy::Hash {
+                        left_keys,
+                        right_keys,
+                        residual,
+                    } => {
+                        exprs.extend(left_keys.iter_mut());
+                        exprs.extend(right_keys.iter_mut());
+                        exprs.extend(residual.iter_mut());
+                    }
+                }
+                exprs
+            }
+            LogicalPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
+            } => group_by
+                .iter_mut()
+                .chain(aggregates.iter_mut().filter_map(|a| a.arg.as_mut()))
+                .collect(),
+            LogicalPlan::Sort { keys, .. } => keys.iter_mut().map(|k| &mut k.expr).collect(),
+            LogicalPlan::Update { assignments, .. } => {
+                assignments.iter_mut().map(|(_, e)| e).collect()
+            }
+            LogicalPlan::Limit { .. } | LogicalPlan::Insert { .. } | LogicalPlan::Delete { .. } => {
+                Vec::new()
+            }
+        }
+    }
+}
+
+fn expr_name(expr: &BoundExpr) -> String {
+    match expr {
+        BoundExpr::Column { name, .. } => name.clone(),
+        _ => "expression".to_string(),
+    }
+}
+
+// Value of a LIMIT / OFFSET expression
+fn constant_count(expr: &BoundExpr) -> Result<usize, String> {
+    match evaluate(expr, &Row::new(Vec::new()))? {
+        Value::Integer(n) if n >= 0 => Ok(n as usize),
+        value => Err(format!(
+            "LIMIT and OFFSET must be non-negative integers, got {}",
+            value
+        )),
+    }
+}
@@ -1 +1,10 @@
-
+pub mod ast;
+pub mod binder;
+pub mod executor;
+pub mod explain;
+pub mod lexer;
+pub mod logical_plan;
+pub mod optimizer;
+pub mod parser;
+pub mod planner;
+pub mod token;
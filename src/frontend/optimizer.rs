@@ -0,0 +1,1321 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::Bound,
+    sync::Arc,
+};
+
+use crate::{
+    acid::log_record::SYSTEM_TXN,
+    backend::cache::Cache,
+    catalog::manager::{Catalog, IndexInfo, TableInfo},
+    frontend::{
+        ast::{BinaryOp, JoinKind},
+        binder::BoundExpr,
+        executor::expression::evaluate,
+        logical_plan::{Access, JoinStrategy, LogicalPlan},
+    },
+    indexing::{
+        btree::{
+            index::key_bounds,
+            key::{encode_key, encode_text_prefix, prefix_successor},
+        },
+        table_heap::TableHeap,
+    },
+    row::Row,
+    types::{DataType, Value},
+};
+
+// Rewrites a logical plan into an equivalent one that is cheaper to run
+//
+// 1. Constant folding: constant subexpressions become literals, TRUE filters disappear
+// 2. Predicate pushdown: WHERE and ON conditions move down to the tables they read
+// 3. Join reordering: chains of 3+ inner joins start from the smallest table
+//    and avoid cross products, using row estimates
+// 4. Join strategy: index lookup, hash join or nested loop (see `choose_strategy`)
+// 5. Access path: an index scan when an index covers part of a table filter
+// 6. Projection pruning: rows held by joins and sorts keep only the columns used above
+pub struct Optimizer<'a> {
+    cache: Arc<Cache>,
+    catalog: &'a Catalog,
+}
+
+impl<'a> Optimizer<'a> {
+    pub fn new(cache: Arc<Cache>, catalog: &'a Catalog) -> Self {
+        Self { cache, catalog }
+    }
+
+    pub fn optimize(&self, plan: LogicalPlan) -> Result<LogicalPlan, String> {
+        let plan = fold(plan)?;
+        let plan = push_down(plan, Vec::new())?;
+        let plan = self.reorder(plan)?;
+        let plan = self.choose_strategies(plan)?;
+        let plan = self.choose_access(plan)?;
+
+        let required = (0..plan.width()).collect();
+        let (plan, _) = prune(plan, &required, false)?;
+        Ok(plan)
+    }
+
+    // Rough number of rows a plan outputs
+    // Never below 1: a filter or an offset makes rows less likely, not impossible
+    pub fn estimate_rows(&self, plan: &LogicalPlan) -> Result<f64, String> {
+        let rows = match plan {
+            LogicalPlan::Scan { table, filter, .. } => {
+                // Only reads, never writes a page
+                let heap = TableHeap::new(self.cache.clone(), table.first_page_id, SYSTEM_TXN);
+                heap.estimate_rows()? as f64 * selectivity(filter.as_ref())
+            }
+            LogicalPlan::Values { rows } => rows.len() as f64,
+            LogicalPlan::Filter { input, predicate } => {
+                self.estimate_rows(input)? * selectivity(Some(predicate))
+            }
+            LogicalPlan::Project { input, .. } | LogicalPlan::Sort { input, .. } => {
+                self.estimate_rows(input)?
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                ..
+            } => {
+                let left_rows = self.estimate_rows(left)?;
+                let right_rows = self.estimate_rows(right)?;
+                let rows = match on {
+                    None => left_rows * right_rows,
+                    // Equi-joins mostly follow a key: one match per row of the bigger side
+                    Some(on) if !equi_pairs(&split_conjunction(on), left.width()).is_empty() => {
+                        left_rows.max(right_rows)
+                    }
+                    Some(on) => left_rows * right_rows * selectivity(Some(on)),
+                };
+                match kind {
+                    JoinKind::Left => rows.max(left_rows),
+                    _ => rows,
+                }
+            }
+            LogicalPlan::Aggregate {
+                input, group_by, ..
+            } => {
+                if group_by.is_empty() {
+                    1.0
+                } else {
+                    (self.estimate_rows(input)? / 10.0).max(1.0)
+                }
+            }
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => {
+                let rows = (self.estimate_rows(input)? - *offset as f64).max(0.0);
+                match limit {
+                    Some(limit) => rows.min(*limit as f64),
+                    None => rows,
+                }
+            }
+            LogicalPlan::Insert { .. }
+            | LogicalPlan::Update { .. }
+            | LogicalPlan::Delete { .. } => 1.0,
+        };
+        Ok(rows.max(1.0))
+    }
+
+    // Reorder every chain of inner / cross joins with at least 3 tables
+    fn reorder(&self, plan: LogicalPlan) -> Result<LogicalPlan, String> {
+        if chain_length(&plan) < 3 {
+            return plan.map_inputs(|input| self.reorder(input));
+        }
+
+        let mut leaves = Vec::new();
+        let mut conditions = Vec::new();
+        flatten_joins(plan, 0, &mut leaves, &mut conditions);
+        let leaves = leaves
+            .into_iter()
+            .map(|(leaf, offset)| Ok((self.reorder(leaf)?, offset)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        // The leaves each condition reads
+        let spans: Vec<(usize, usize)> = leaves
+            .iter()
+            .map(|(leaf, offset)| (*offset, leaf.width()))
+            .collect();
+        let leaf_of = |column: usize| {
+            spans
+                .iter()
+                .position(|&(offset, width)| column >= offset && column < offset + width)
+                .unwrap()
+        };
+        let condition_leaves: Vec<BTreeSet<usize>> = conditions
+            .iter()
+            .map(|c| c.columns().into_iter().map(leaf_of).collect())
+            .collect();
+
+        // Greedy: the smallest leaf first, then the smallest leaf joined by a condition
+        let estimates = leaves
+            .iter()
+            .map(|(leaf, _)| self.estimate_rows(leaf))
+            .collect::<Result<Vec<_>, String>>()?;
+        let smallest = |candidates: &mut dyn Iterator<Item = usize>| {
+            candidates.min_by(|&a, &b| estimates[a].total_cmp(&estimates[b]))
+        };
+
+        let mut order = Vec::new();
+        order.extend(smallest(&mut (0..leaves.len())));
+        while order.len() < leaves.len() {
+            let connected = |i: usize| {
+                condition_leaves
+                    .iter()
+                    .any(|set| set.contains(&i) && set.iter().any(|l| order.contains(l)))
+            };
+            let next =
+                smallest(&mut (0..leaves.len()).filter(|i| !order.contains(i) && connected(*i)))
+                    .or_else(|| smallest(&mut (0..leaves.len()).filter(|i| !order.contains(i))))
+                    .unwrap();
+            order.push(next);
+        }
+
+        // Old column positions, and where they end up in the new joined row
+        let columns: Vec<(String, Option<DataType>)> =
+            leaves.iter().flat_map(|(leaf, _)| leaf.columns()).collect();
+        let mut new_offsets = vec![0; leaves.len()];
+        let mut width = 0;
+        for &i in &order {
+            new_offsets[i] = width;
+            width += spans[i].1;
+        }
+        let new_position = |column: usize| {
+            let leaf = leaf_of(column);
+            new_offsets[leaf] + column - spans[leaf].0
+        };
+
+        // Left-deep, each condition attached to the first join that sees all its leaves
+        let reordered = order.windows(2).any(|pair| pair[0] > pair[1]);
+        let mut leaves: Vec<Option<LogicalPlan>> =
+            leaves.into_iter().map(|(leaf, _)| Some(leaf)).collect();
+        let mut attached = vec![false; conditions.len()];
+        let mut plan = leaves[order[0]].take().unwrap();
+        for (k, &i) in order.iter().enumerate().skip(1) {
+            let placed = &order[..=k];
+            let mut on = Vec::new();
+            for (c, condition) in conditions.iter().enumerate() {
+                if !attached[c] && condition_leaves[c].iter().all(|l| placed.contains(l)) {
+                    attached[c] = true;
+                    on.push(condition.map_columns(&new_position));
+                }
+            }
+            let on = conjunction(on);
+            plan = LogicalPlan::Join {
+                left: Box::new(plan),
+                right: Box::new(leaves[i].take().unwrap()),
+                kind: if on.is_some() {
+                    JoinKind::Inner
+                } else {
+                    JoinKind::Cross
+                },
+                on,
+                strategy: JoinStrategy::NestedLoop,
+            };
+        }
+
+        if !reordered {
+            return Ok(plan);
+        }
+
+        // Put the columns back in the order the query expects
+        let exprs = columns
+            .into_iter()
+            .enumerate()
+            .map(|(i, column)| column_expr(new_position(i), column))
+            .collect();
+        Ok(LogicalPlan::Project {
+            input: Box::new(plan),
+            exprs,
+        })
+    }
+
+    fn choose_strategies(&self, plan: LogicalPlan) -> Result<LogicalPlan, String> {
+        let plan = plan.map_inputs(|input| self.choose_strategies(input))?;
+        match plan {
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                ..
+            } => {
+                let strategy = self.choose_strategy(&left, &right, on.as_ref())?;
+                Ok(LogicalPlan::Join {
+                    left,
+                    right,
+                    kind,
+                    on,
+                    strategy,
+                })
+            }
+            plan => Ok(plan),
+        }
+    }
+
+    // Look the right table up in an index when ON compares its leading index columns
+    // with the left side, hash the right side when ON has other equalities,
+    // and rescan it for every left row otherwise.
+    fn choose_strategy(
+        &self,
+        left: &LogicalPlan,
+        right: &LogicalPlan,
+        on: Option<&BoundExpr>,
+    ) -> Result<JoinStrategy, String> {
+        let Some(on) = on else {
+            return Ok(JoinStrategy::NestedLoop);
+        };
+        let left_width = left.width();
+        let conditions = split_conjunction(on);
+        let pairs = equi_pairs(&conditions, left_width);
+
+        if let LogicalPlan::Scan { table, .. } = right
+            && let Some((index, probe)) = self.choose_join_index(table, &pairs, left_width)?
+        {
+            return Ok(JoinStrategy::IndexLookup { index, probe });
+        }
+
+        // Hash keys must have the same type on both sides to encode the same
+        let keys: Vec<&(usize, &BoundExpr, &BoundExpr)> = pairs
+            .iter()
+            .filter(|(_, l, r)| l.data_type().is_some() && l.data_type() == r.data_type())
+            .collect();
+        if keys.is_empty() {
+            return Ok(JoinStrategy::NestedLoop);
+        }
+
+        let left_keys = keys.iter().map(|(_, l, _)| (*l).clone()).collect();
+        let right_keys = keys
+            .iter()
+            .map(|(_, _, r)| r.map_columns(&|i| i - left_width))
+            .collect();
+        let residual = conjunction(
+            conditions
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !keys.iter().any(|(k, ..)| k == i))
+                .map(|(_, c)| (*c).clone())
+                .collect(),
+        );
+        Ok(JoinStrategy::Hash {
+            left_keys,
+            right_keys,
+            residual,
+        })
+    }
+
+    // The index of the joined table whose leading columns are matched by the most equalities,
+    // with the expressions computing its key from the left row
+    #[allow(clippy::type_complexity)]
+    fn choose_join_index(
+        &self,
+        table: &TableInfo,
+        pairs: &[(usize, &BoundExpr, &BoundExpr)],
+        left_width: usize,
+    ) -> Result<Option<(IndexInfo, Vec<(BoundExpr, DataType)>)>, String> {
+        let mut best: Option<(IndexInfo, Vec<(BoundExpr, DataType)>)> = None;
+        for info in self.catalog.table_indexes(&table.name) {
+            let mut probe = Vec::new();
+            for column in index_columns(info, table)? {
+                let data_type = table.schema.columns[column].data_type;
+                // The left value must convert to the column type without changing
+                let found = pairs.iter().find(|(_, l, r)| {
+                    matches!(r, BoundExpr::Column { index, .. } if *index == left_width + column)
+                        && matches!(
+                            (l.data_type(), data_type),
+                            (Some(a), b) if a == b || (a == DataType::Integer && b == DataType::Real)
+                        )
+                });
+                match found {
+                    Some((_, l, _)) => probe.push(((*l).clone(), data_type)),
+                    None => break,
+                }
+            }
+
+            if !probe.is_empty() && best.as_ref().is_none_or(|(_, p)| probe.len() > p.len()) {
+                best = Some((info.clone(), probe));
+            }
+        }
+        Ok(best)
+    }
+
+    // Index scans for the filtered tables
+    // The right table of an index lookup join is read through the join's index instead
+    fn choose_access(&self, plan: LogicalPlan) -> Result<LogicalPlan, String> {
+        match plan {
+            LogicalPlan::Scan {
+                table,
+                filter: Some(filter),
+                access: Access::Seq,
+            } => {
+                let access = match self.choose_index(&table, &filter)? {
+                    Some((index, lower, upper)) => Access::Index {
+                        index,
+                        lower,
+                        upper,
+                    },
+                    None => Access::Seq,
+                };
+                Ok(LogicalPlan::Scan {
+                    table,
+                    filter: Some(filter),
+                    access,
+                })
+            }
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                strategy: strategy @ JoinStrategy::IndexLookup { .. },
+            } => Ok(LogicalPlan::Join {
+                left: Box::new(self.choose_access(*left)?),
+                right,
+                kind,
+                on,
+                strategy,
+            }),
+            plan => plan.map_inputs(|input| self.choose_access(input)),
+        }
+    }
+
+    // The index covering the most of the filter, with the key range to scan
+    // Leading index columns compared with `=` narrow the range most,
+    // then a range (<, <=, >, >=, BETWEEN, LIKE 'prefix%') on the next column.
+    #[allow(clippy::type_complexity)]
+    fn choose_index(
+        &self,
+        table: &TableInfo,
+        filter: &BoundExpr,
+    ) -> Result<Option<(IndexInfo, Bound<Vec<u8>>, Bound<Vec<u8>>)>, String> {
+        let ranges = column_ranges(filter, table)?;
+        if ranges.is_empty() {
+            return Ok(None);
+        }
+
+        let mut best: Option<(usize, IndexInfo, Bound<Vec<u8>>, Bound<Vec<u8>>)> = None;
+        for info in self.catalog.table_indexes(&table.name) {
+            let columns = index_columns(info, table)?;
+
+            let mut equal = Vec::new();
+            for column in &columns {
+                match ranges.get(column).and_then(|r| r.equal.clone()) {
+                    Some(value) => equal.push(value),
+                    None => break,
+                }
+            }
+            let next = columns.get(equal.len()).and_then(|c| ranges.get(c));
+
+            let (score, lower, upper) = match next {
+                Some(range) if range.has_bounds() => {
+                    let lower = range.lower.as_ref().map(|v| with_prefix(&equal, v));
+                    let upper = range.upper.as_ref().map(|v| with_prefix(&equal, v));
+                    let (lower, upper) = key_bounds(
+                        prefix_bound(&equal, lower.as_ref().map(|k| k.as_slice())),
+                        prefix_bound(&equal, upper.as_ref().map(|k| k.as_slice())),
+                    );
+                    (equal.len() * 2 + 1, lower, upper)
+                }
+                Some(ColumnRange {
+                    prefix: Some(prefix),
+                    ..
+                }) => {
+                    let (lower, upper) = like_bounds(&equal, prefix);
+                    (equal.len() * 2 + 1, lower, upper)
+                }
+                _ if !equal.is_empty() => {
+                    let (lower, upper) =
+                        key_bounds(Bound::Included(&equal), Bound::Included(&equal));
+                    (equal.len() * 2, lower, upper)
+                }
+                _ => continue,
+            };
+
+            if best
+                .as_ref()
+                .is_none_or(|(best_score, ..)| score > *best_score)
+            {
+                best = Some((score, info.clone(), lower, upper));
+            }
+        }
+
+        Ok(best.map(|(_, info, lower, upper)| (info, lower, upper)))
+    }
+}
+
+// Evaluate constant subexpressions once, instead of once per row
+fn fold(plan: LogicalPlan) -> Result<LogicalPlan, String> {
+    let mut plan = plan.map_inputs(fold)?;
+    for expr in plan.exprs_mut() {
+        *expr = fold_expr(expr)?;
+    }
+
+    let always =
+        |expr: &Option<BoundExpr>| matches!(expr, Some(BoundExpr::Literal(Value::Boolean(true))));
+    match plan {
+        LogicalPlan::Filter {
+            input,
+            predicate: BoundExpr::Literal(Value::Boolean(true)),
+        } => Ok(*input),
+        LogicalPlan::Scan {
+            table,
+            filter,
+            access,
+        } if always(&filter) => Ok(LogicalPlan::Scan {
+            table,
+            filter: None,
+            access,
+        }),
+        LogicalPlan::Join {
+            left,
+            right,
+            kind,
+            on,
+            strategy,
+        } if always(&on) => Ok(LogicalPlan::Join {
+            left,
+            right,
+            kind,
+            on: None,
+            strategy,
+        }),
+        plan => Ok(plan),
+    }
+}
+
+// Expressions that fail to evaluate are kept, so the error shows up when the query runs
+fn fold_expr(expr: &BoundExpr) -> Result<BoundExpr, String> {
+    if expr.is_constant()
+        && !matches!(expr, BoundExpr::Literal(_))
+        && let Ok(value) = evaluate(expr, &Row::new(Vec::new()))
+    {
+        return Ok(BoundExpr::Literal(value));
+    }
+
+    let expr = expr.try_map_children(&mut fold_expr)?;
+    let is = |expr: &BoundExpr, b: bool| matches!(expr, BoundExpr::Literal(Value::Boolean(v)) if *v == b);
+    let folded = match &expr {
+        // x AND TRUE = x, x AND FALSE = FALSE
+        BoundExpr::Binary {
+            left,
+            op: BinaryOp::And,
+            right,
+        } => {
+            if is(left, false) || is(right, false) {
+                BoundExpr::Literal(Value::Boolean(false))
+            } else if is(left, true) {
+                *right.clone()
+            } else if is(right, true) {
+                *left.clone()
+            } else {
+                expr
+            }
+        }
+        // x OR TRUE = TRUE, x OR FALSE = x
+        BoundExpr::Binary {
+            left,
+            op: BinaryOp::Or,
+            right,
+        } => {
+            if is(left, true) || is(right, true) {
+                BoundExpr::Literal(Value::Boolean(true))
+            } else if is(left, false) {
+                *right.clone()
+            } else if is(right, false) {
+                *left.clone()
+            } else {
+                expr
+            }
+        }
+        _ => expr,
+    };
+    Ok(folded)
+}
+
+// Move the AND-ed `conditions` (over the output of `plan`) as far down as they can go
+fn push_down(plan: LogicalPlan, mut conditions: Vec<BoundExpr>) -> Result<LogicalPlan, String> {
+    match plan {
+        LogicalPlan::Filter { input, predicate } => {
+            conditions.extend(split_conjunction(&predicate).into_iter().cloned());
+            push_down(*input, conditions)
+        }
+        LogicalPlan::Scan {
+            table,
+            filter,
+            access,
+        } => {
+            let mut all: Vec<BoundExpr> = filter
+                .iter()
+                .flat_map(|f| split_conjunction(f).into_iter().cloned())
+                .collect();
+            all.extend(conditions);
+            Ok(LogicalPlan::Scan {
+                table,
+                filter: conjunction(all),
+                access,
+            })
+        }
+        // Rows of an inner join are the pairs matching every condition,
+        // so WHERE and ON conditions can go wherever their columns are
+        LogicalPlan::Join {
+            left,
+            right,
+            kind: JoinKind::Inner | JoinKind::Cross,
+            on,
+            strategy,
+        } => {
+            let left_width = left.width();
+            if let Some(on) = &on {
+                conditions.extend(split_conjunction(on).into_iter().cloned());
+            }
+
+            let (mut to_left, mut to_right, mut both) = (Vec::new(), Vec::new(), Vec::new());
+            for condition in conditions {
+                match side(&condition, left_width) {
+                    Side::Left => to_left.push(condition),
+                    Side::Right => to_right.push(condition.map_columns(&|i| i - left_width)),
+                    Side::Both => both.push(condition),
+                }
+            }
+
+            let on = conjunction(both);
+            Ok(LogicalPlan::Join {
+                left: Box::new(push_down(*left, to_left)?),
+                right: Box::new(push_down(*right, to_right)?),
+                kind: if on.is_some() {
+                    JoinKind::Inner
+                } else {
+                    JoinKind::Cross
+                },
+                on,
+                strategy,
+            })
+        }
+        // A LEFT join keeps unmatched left rows: WHERE conditions on the left side
+        // and ON conditions on the right side are safe to push, the others stay put
+        LogicalPlan::Join {
+            left,
+            right,
+            kind: JoinKind::Left,
+            on,
+            strategy,
+        } => {
+            let left_width = left.width();
+
+            let (mut to_left, mut above) = (Vec::new(), Vec::new());
+            for condition in conditions {
+                match side(&condition, left_width) {
+                    Side::Left => to_left.push(condition),
+                    _ => above.push(condition),
+                }
+            }
+
+            let (mut to_right, mut kept) = (Vec::new(), Vec::new());
+            for condition in on.iter().flat_map(split_conjunction) {
+                match side(condition, left_width) {
+                    Side::Right => to_right.push(condition.map_columns(&|i| i - left_width)),
+                    _ => kept.push(condition.clone()),
+                }
+            }
+
+            let join = LogicalPlan::Join {
+                left: Box::new(push_down(*left, to_left)?),
+                right: Box::new(push_down(*right, to_right)?),
+                kind: JoinKind::Left,
+                on: conjunction(kept),
+                strategy,
+            };
+            Ok(with_filter(join, above))
+        }
+        // Sorting doesn't change which rows there are
+        LogicalPlan::Sort { input, keys } => Ok(LogicalPlan::Sort {
+            input: Box::new(push_down(*input, conditions)?),
+            keys,
+        }),
+        plan => {
+            let plan = plan.map_inputs(|input| push_down(input, Vec::new()))?;
+            Ok(with_filter(plan, conditions))
+        }
+    }
+}
+
+fn with_filter(plan: LogicalPlan, conditions: Vec<BoundExpr>) -> LogicalPlan {
+    match conjunction(conditions) {
+        Some(predicate) => LogicalPlan::Filter {
+            input: Box::new(plan),
+            predicate,
+        },
+        None => plan,
+    }
+}
+
+enum Side {
+    // Also conditions reading no column
+    Left,
+    Right,
+    Both,
+}
+
+// Which side of a join a condition over the joined row reads
+fn side(condition: &BoundExpr, left_width: usize) -> Side {
+    let columns = condition.columns();
+    if columns.iter().all(|&c| c < left_width) {
+        Side::Left
+    } else if columns.iter().all(|&c| c >= left_width) {
+        Side::Right
+    } else {
+        Side::Both
+    }
+}
+
+// Number of tables in the chain of inner / cross joins at the top of the plan
+fn chain_length(plan: &LogicalPlan) -> usize {
+    match plan {
+        LogicalPlan::Join {
+            left,
+            right,
+            kind: JoinKind::Inner | JoinKind::Cross,
+            ..
+        } => chain_length(left) + chain_length(right),
+        _ => 1,
+    }
+}
+
+// The inputs of a chain of inner / cross joins, with the position of their first column,
+// and the join conditions over the whole joined row
+fn flatten_joins(
+    plan: LogicalPlan,
+    offset: usize,
+    leaves: &mut Vec<(LogicalPlan, usize)>,
+    conditions: &mut Vec<BoundExpr>,
+) {
+    match plan {
+        LogicalPlan::Join {
+            left,
+            right,
+            kind: JoinKind::Inner | JoinKind::Cross,
+            on,
+            ..
+        } => {
+            let left_width = left.width();
+            flatten_joins(*left, offset, leaves, conditions);
+            flatten_joins(*right, offset + left_width, leaves, conditions);
+            if let Some(on) = on {
+                conditions.extend(
+                    split_conjunction(&on)
+                        .into_iter()
+                        .map(|c| c.map_columns(&|i| i + offset)),
+                );
+            }
+        }
+        plan => leaves.push((plan, offset)),
+    }
+}
+
+// Drop the columns nobody reads from the rows that joins and sorts hold
+// `required`: output columns of `plan` used above it
+// `narrow`: a Scan should only pass on the required columns
+// Returns the new plan, and the new position of each old output column (None if dropped).
+// The output of a DML statement, of the top projection and of an aggregation is kept whole.
+#[allow(clippy::type_complexity)]
+fn prune(
+    plan: LogicalPlan,
+    required: &BTreeSet<usize>,
+    narrow: bool,
+) -> Result<(LogicalPlan, Vec<Option<usize>>), String> {
+    let identity = |plan: &LogicalPlan| (0..plan.width()).map(Some).collect::<Vec<_>>();
+    let with = |set: &BTreeSet<usize>, exprs: &mut dyn Iterator<Item = &BoundExpr>| {
+        let mut set = set.clone();
+        exprs.for_each(|e| set.extend(e.columns()));
+        set
+    };
+
+    match plan {
+        LogicalPlan::Scan { .. } if narrow && required.len() < plan.width() => {
+            let columns = plan.columns();
+            let exprs = required
+                .iter()
+                .map(|&i| column_expr(i, columns[i].clone()))
+                .collect();
+            let mapping = positions(required, plan.width());
+            Ok((
+                LogicalPlan::Project {
+                    input: Box::new(plan),
+                    exprs,
+                },
+                mapping,
+            ))
+        }
+        LogicalPlan::Filter { input, predicate } => {
+            let needed = with(required, &mut std::iter::once(&predicate));
+            let (input, mapping) = prune(*input, &needed, narrow)?;
+            let plan = LogicalPlan::Filter {
+                input: Box::new(input),
+                predicate: remap(&predicate, &mapping),
+            };
+            Ok((plan, mapping))
+        }
+        LogicalPlan::Project { input, exprs } => {
+            let kept: Vec<BoundExpr> = required.iter().map(|&i| exprs[i].clone()).collect();
+            let needed = with(&BTreeSet::new(), &mut kept.iter());
+            let (input, mapping) = prune(*input, &needed, false)?;
+            let plan = LogicalPlan::Project {
+                input: Box::new(input),
+                exprs: kept.iter().map(|e| remap(e, &mapping)).collect(),
+            };
+            Ok((plan, positions(required, exprs.len())))
+        }
+        LogicalPlan::Join {
+            left,
+            right,
+            kind,
+            on,
+            strategy,
+        } => {
+            let left_width = left.width();
+            let mut needed = with(required, &mut on.iter());
+            match &strategy {
+                JoinStrategy::NestedLoop => {}
+                JoinStrategy::IndexLookup { probe, .. } => {
+                    needed = with(&needed, &mut probe.iter().map(|(e, _)| e))
+                }
+                JoinStrategy::Hash {
+                    left_keys,
+                    right_keys,
+                    residual,
+                } => {
+                    needed = with(&needed, &mut left_keys.iter().chain(residual));
+                    // Right keys read the right row
+                    needed.extend(
+                        right_keys
+                            .iter()
+                            .flat_map(|e| e.columns())
+                            .map(|c| c + left_width),
+                    );
+                }
+            }
+
+            let left_needed = needed.iter().copied().filter(|&c| c < left_width).collect();
+            let right_needed = needed
+                .iter()
+                .filter(|&&c| c >= left_width)
+                .map(|c| c - left_width)
+                .collect();
+            let (left, left_mapping) = prune(*left, &left_needed, true)?;
+            // An index lookup reads whole tuples of the right table
+            let (right, right_mapping) = match &strategy {
+                JoinStrategy::IndexLookup { .. } => {
+                    let mapping = identity(&right);
+                    (*right, mapping)
+                }
+                _ => prune(*right, &right_needed, true)?,
+            };
+
+            let new_left_width = left.width();
+            let mut mapping = left_mapping.clone();
+            mapping.extend(right_mapping.iter().map(|m| m.map(|c| c + new_left_width)));
+
+            let strategy = match strategy {
+                JoinStrategy::NestedLoop => JoinStrategy::NestedLoop,
+                JoinStrategy::IndexLookup { index, probe } => JoinStrategy::IndexLookup {
+                    index,
+                    probe: probe
+                        .iter()
+                        .map(|(e, t)| (remap(e, &left_mapping), *t))
+                        .collect(),
+                },
+                JoinStrategy::Hash {
+                    left_keys,
+                    right_keys,
+                    residual,
+                } => JoinStrategy::Hash {
+                    left_keys: left_keys.iter().map(|e| remap(e, &left_mapping)).collect(),
+                    right_keys: right_keys
+                        .iter()
+                        .map(|e| remap(e, &right_mapping))
+                        .collect(),
+                    residual: residual.as_ref().map(|e| remap(e, &mapping)),
+                },
+            };
+            let plan = LogicalPlan::Join {
+                left: Box::new(left),
+                right: Box::new(right),
+                kind,
+                on: on.as_ref().map(|e| remap(e, &mapping)),
+                strategy,
+            };
+            Ok((plan, mapping))
+        }
+        LogicalPlan::Aggregate {
+            input,
+            group_by,
+            aggregates,
+        } => {
+            let needed = with(
+                &BTreeSet::new(),
+                &mut group_by
+                    .iter()
+                    .chain(aggregates.iter().filter_map(|a| a.arg.as_ref())),
+            );
+            let (input, mapping) = prune(*input, &needed, false)?;
+            let mut aggregates = aggregates;
+            for call in &mut aggregates {
+                call.arg = call.arg.as_ref().map(|e| remap(e, &mapping));
+            }
+            let plan = LogicalPlan::Aggregate {
+                input: Box::new(input),
+                group_by: group_by.iter().map(|e| remap(e, &mapping)).collect(),
+                aggregates,
+            };
+            let mapping = identity(&plan);
+            Ok((plan, mapping))
+        }
+        LogicalPlan::Sort { input, mut keys } => {
+            let needed = with(required, &mut keys.iter().map(|k| &k.expr));
+            let (input, mapping) = prune(*input, &needed, true)?;
+            for key in &mut keys {
+                key.expr = remap(&key.expr, &mapping);
+            }
+            let plan = LogicalPlan::Sort {
+                input: Box::new(input),
+                keys,
+            };
+            Ok((plan, mapping))
+        }
+        LogicalPlan::Limit {
+            input,
+            limit,
+            offset,
+        } => {
+            let (input, mapping) = prune(*input, required, narrow)?;
+            let plan = LogicalPlan::Limit {
+                input: Box::new(input),
+                limit,
+                offset,
+            };
+            Ok((plan, mapping))
+        }
+        plan => {
+            let mapping = identity(&plan);
+            Ok((plan, mapping))
+        }
+    }
+}
+
+// Position of each column among the kept ones
+fn positions(kept: &BTreeSet<usize>, width: usize) -> Vec<Option<usize>> {
+    (0..width)
+        .map(|i| kept.iter().position(|&k| k == i))
+        .collect()
+}
+
+fn remap(expr: &BoundExpr, mapping: &[Option<usize>]) -> BoundExpr {
+    expr.map_columns(&|i| mapping[i].expect("column read above was pruned"))
+}
+
+// A column of a row, or NULL if it is always NULL
+fn column_expr(index: usize, (name, data_type): (String, Option<DataType>)) -> BoundExpr {
+    match data_type {
+        Some(data_type) => BoundExpr::Column {
+            index,
+            name,
+            data_type,
+        },
+        None => BoundExpr::Literal(Value::Null),
+    }
+}
+
+// Fraction of the rows a condition keeps, from the shape of the condition alone
+fn selectivity(condition: Option<&BoundExpr>) -> f64 {
+    let Some(condition) = condition else {
+        return 1.0;
+    };
+    split_conjunction(condition)
+        .into_iter()
+        .map(|c| match c {
+            BoundExpr::Literal(Value::Boolean(true)) => 1.0,
+            BoundExpr::Literal(_) => 0.0,
+            BoundExpr::Binary {
+                left,
+                op: BinaryOp::Or,
+                right,
+            } => (selectivity(Some(left)) + selectivity(Some(right))).min(1.0),
+            BoundExpr::Binary { op, .. } => match op {
+                BinaryOp::Eq => 0.1,
+                BinaryOp::NotEq => 0.9,
+                BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => 0.3,
+                _ => 0.5,
+            },
+            BoundExpr::Between { negated: false, .. } => 0.3,
+            BoundExpr::Like { negated: false, .. } => 0.25,
+            BoundExpr::IsNull { negated: false, .. } => 0.1,
+            BoundExpr::IsNull { negated: true, .. } => 0.9,
+            _ => 0.5,
+        })
+        .product()
+}
+
+pub fn split_conjunction(expr: &BoundExpr) -> Vec<&BoundExpr> {
+    let mut out = Vec::new();
+    collect_conjuncts(expr, &mut out);
+    out
+}
+
+fn collect_conjuncts<'e>(expr: &'e BoundExpr, out: &mut Vec<&'e BoundExpr>) {
+    match expr {
+        BoundExpr::Binary {
+            left,
+            op: BinaryOp::And,
+            right,
+        } => {
+            collect_conjuncts(left, out);
+            collect_conjuncts(right, out);
+        }
+        expr => out.push(expr),
+    }
+}
+
+// The conditions AND-ed together, None if there are none
+pub fn conjunction(conditions: Vec<BoundExpr>) -> Option<BoundExpr> {
+    conditions.into_iter().reduce(|a, b| BoundExpr::Binary {
+        left: Box::new(a),
+        op: BinaryOp::And,
+        right: Box::new(b),
+    })
+}
+
+// What the AND-ed conditions of a filter say about one column
+struct ColumnRange {
+    equal: Option<Value>,
+    lower: Bound<Value>,
+    upper: Bound<Value>,
+    // LIKE 'prefix%'
+    prefix: Option<String>,
+}
+
+impl ColumnRange {
+    fn new() -> Self {
+        Self {
+            equal: None,
+            lower: Bound::Unbounded,
+            upper: Bound::Unbounded,
+            prefix: None,
+        }
+    }
+
+    fn has_bounds(&self) -> bool {
+        !matches!(
+            (&self.lower, &self.upper),
+            (Bound::Unbounded, Bound::Unbounded)
+        )
+    }
+
+    // Keep the tighter of two bounds
+    fn restrict_lower(&mut self, bound: Bound<Value>) {
+        if tighter(&bound, &self.lower, std::cmp::Ordering::Greater) {
+            self.lower = bound;
+        }
+    }
+
+    fn restrict_upper(&mut self, bound: Bound<Value>) {
+        if tighter(&bound, &self.upper, std::cmp::Ordering::Less) {
+            self.upper = bound;
+        }
+    }
+}
+
+// `wanted`: how a tighter value compares to the current one
+fn tighter(new: &Bound<Value>, current: &Bound<Value>, wanted: std::cmp::Ordering) -> bool {
+    let (Bound::Included(a) | Bound::Excluded(a)) = new else {
+        return false;
+    };
+    let (Bound::Included(b) | Bound::Excluded(b)) = current else {
+        return true;
+    };
+    match a.compare(b) {
+        Some(ordering) if ordering == wanted => true,
+        Some(std::cmp::Ordering::Equal) => matches!(new, Bound::Excluded(_)),
+        _ => false,
+    }
+}
+
+// Ranges of the table columns that the filter restricts to constants
+fn column_ranges(
+    filter: &BoundExpr,
+    table: &TableInfo,
+) -> Result<HashMap<usize, ColumnRange>, String> {
+    let mut ranges: HashMap<usize, ColumnRange> = HashMap::new();
+    let empty = Row::new(Vec::new());
+
+    for condition in split_conjunction(filter) {
+        match condition {
+            BoundExpr::Binary { left, op, right } => {
+                let (column, op, constant) = match (left.as_ref(), right.as_ref()) {
+                    (BoundExpr::Column { index, .. }, constant) if constant.is_constant() => {
+                        (*index, *op, constant)
+                    }
+                    (constant, BoundExpr::Column { index, .. }) if constant.is_constant() => {
+                        let Some(op) = flip(*op) else { continue };
+                        (*index, op, constant)
+                    }
+                    _ => continue,
+                };
+                let Some(value) = key_value(evaluate(constant, &empty)?, table, column) else {
+                    continue;
+                };
+
+                let range = ranges.entry(column).or_insert_with(ColumnRange::new);
+                match op {
+                    BinaryOp::Eq => {
+                        range.equal.get_or_insert(value);
+                    }
+                    BinaryOp::Lt => range.restrict_upper(Bound::Excluded(value)),
+                    BinaryOp::LtEq => range.restrict_upper(Bound::Included(value)),
+                    BinaryOp::Gt => range.restrict_lower(Bound::Excluded(value)),
+                    BinaryOp::GtEq => range.restrict_lower(Bound::Included(value)),
+                    _ => {}
+                }
+            }
+            BoundExpr::Between {
+                expr,
+                low,
+                high,
+                negated: false,
+            } => {
+                let BoundExpr::Column { index, .. } = expr.as_ref() else {
+                    continue;
+                };
+                if !low.is_constant() || !high.is_constant() {
+                    continue;
+                }
+                let low = key_value(evaluate(low, &empty)?, table, *index);
+                let high = key_value(evaluate(high, &empty)?, table, *index);
+                let (Some(low), Some(high)) = (low, high) else {
+                    continue;
+                };
+
+                let range = ranges.entry(*index).or_insert_with(ColumnRange::new);
+                range.restrict_lower(Bound::Included(low));
+                range.restrict_upper(Bound::Included(high));
+            }
+            BoundExpr::Like {
+                expr,
+                pattern,
+                negated: false,
+            } => {
+                let (BoundExpr::Column { index, .. }, BoundExpr::Literal(Value::Text(pattern))) =
+                    (expr.as_ref(), pattern.as_ref())
+                else {
+                    continue;
+                };
+                let prefix: String = pattern
+                    .chars()
+                    .take_while(|&c| c != '%' && c != '_')
+                    .collect();
+                if !prefix.is_empty() {
+                    let range = ranges.entry(*index).or_insert_with(ColumnRange::new);
+                    range.prefix.get_or_insert(prefix);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(ranges)
+}
+
+// `left = right` conditions of an ON clause, as (position in `conditions`, left, right)
+// where one side reads only the left row and the other only the joined table
+fn equi_pairs<'e>(
+    conditions: &[&'e BoundExpr],
+    left_width: usize,
+) -> Vec<(usize, &'e BoundExpr, &'e BoundExpr)> {
+    let side = |expr: &BoundExpr| {
+        let columns = expr.columns();
+        if columns.is_empty() {
+            None
+        } else if columns.iter().all(|&c| c < left_width) {
+            Some(true)
+        } else if columns.iter().all(|&c| c >= left_width) {
+            Some(false)
+        } else {
+            None
+        }
+    };
+
+    let mut pairs = Vec::new();
+    for (i, condition) in conditions.iter().enumerate() {
+        if let BoundExpr::Binary {
+            left,
+            op: BinaryOp::Eq,
+            right,
+        } = condition
+        {
+            match (side(left), side(right)) {
+                (Some(true), Some(false)) => pairs.push((i, left.as_ref(), right.as_ref())),
+                (Some(false), Some(true)) => pairs.push((i, right.as_ref(), left.as_ref())),
+                _ => {}
+            }
+        }
+    }
+    pairs
+}
+
+// `constant op column` as `column op constant`
+fn flip(op: BinaryOp) -> Option<BinaryOp> {
+    match op {
+        BinaryOp::Eq => Some(BinaryOp::Eq),
+        BinaryOp::Lt => Some(BinaryOp::Gt),
+        BinaryOp::LtEq => Some(BinaryOp::GtEq),
+        BinaryOp::Gt => Some(BinaryOp::Lt),
+        BinaryOp::GtEq => Some(BinaryOp::LtEq),
+        _ => None,
+    }
+}
+
+// The constant as it is stored in the index, None if no index entry can match it
+// (NULL never compares true, and keys are encoded with the column type)
+fn key_value(value: Value, table: &TableInfo, column: usize) -> Option<Value> {
+    if value.is_null() {
+        return None;
+    }
+    value.coerce(table.schema.columns[column].data_type).ok()
+}
+
+// Positions of the index columns in the table row
+pub fn index_columns(info: &IndexInfo, table: &TableInfo) -> Result<Vec<usize>, String> {
+    info.columns
+        .iter()
+        .map(|name| {
+            table
+                .schema
+                .column_index(name)
+                .ok_or_else(|| format!("Index '{}' uses unknown column '{}'", info.name, name))
+        })
+        .collect()
+}
+
+fn with_prefix(equal: &[Value], value: &Value) -> Vec<Value> {
+    let mut key = equal.to_vec();
+    key.push(value.clone());
+    key
+}
+
+// An open end of a range stops at the end of the `=` prefix
+fn prefix_bound<'k>(equal: &'k [Value], bound: Bound<&'k [Value]>) -> Bound<&'k [Value]> {
+    match bound {
+        Bound::Unbounded if !equal.is_empty() => Bound::Included(equal),
+        bound => bound,
+    }
+}
+
+// Keys that may match LIKE 'prefix%'
+// LIKE ignores ASCII case, and uppercase sorts before lowercase,
+// so the range runs from the uppercase prefix to past the lowercase one.
+fn like_bounds(equal: &[Value], prefix: &str) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    let mut lower = encode_key(equal);
+    lower.extend(encode_text_prefix(&prefix.to_ascii_uppercase()));
+
+    let mut upper = encode_key(equal);
+    upper.extend(encode_text_prefix(&prefix.to_ascii_lowercase()));
+
+    let upper = match prefix_successor(&upper) {
+        Some(successor) => Bound::Excluded(successor),
+        None => Bound::Unbounded,
+    };
+    (Bound::Included(lower), upper)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::database::{Database, QueryResult};
+
+    // The EXPLAIN lines of each query, on tables a(id, name) and b(id, a_id, score)
+    fn plans(name: &str, queries: &[&str]) -> Vec<Vec<String>> {
+        let path = env::temp_dir()
+            .join(format!("mysqlite-optimizer-{}-{}.db", process::id(), name))
+            .to_string_lossy()
+            .into_owned();
+        let remove = || {
+            let _ = fs::remove_file(&path);
+            let _ = fs::remove_file(format!("{}-wal", path));
+        };
+        remove();
+
+        let mut db = Database::open(&path).unwrap();
+        let sql = "CREATE TABLE a (id INTEGER, name TEXT); \
+                   CREATE TABLE b (id INTEGER, a_id INTEGER, score REAL); \
+                   CREATE INDEX b_a_id ON b (a_id);";
+        for result in db.execute(sql) {
+            result.unwrap();
+        }
+
+        let plans = queries
+            .iter()
+            .map(
+                |query| match db.execute(&format!("EXPLAIN {}", query)).pop() {
+                    Some(Ok(QueryResult::Plan(lines))) => lines,
+                    _ => panic!("EXPLAIN {} returned no plan", query),
+                },
+            )
+            .collect();
+        drop(db);
+        remove();
+        plans
+    }
+
+    fn operators(plan: &[String]) -> Vec<&str> {
+        plan.iter()
+            .map(|line| line.trim_start_matches([' ', '-', '>']))
+            .map(|line| line.split("  (estimated").next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn predicates_are_pushed_to_the_tables_they_read() {
+        let plans = plans(
+            "push-down",
+            &[
+                "SELECT name, score FROM a JOIN b ON a.id = b.a_id \
+                 WHERE a.name = 'x' AND b.score > 1 AND 1 + 1 = 2",
+                "SELECT * FROM a LEFT JOIN b ON a.id = b.a_id WHERE b.score IS NULL",
+            ],
+        );
+
+        // The constant condition is folded away, each filter goes to its own table
+        assert_eq!(
+            operators(&plans[0]),
+            vec![
+                "Projection: name, score",
+                "Index Nested Loop Join (INNER) with b using b_a_id, on: (id = a_id), filter: (score > 1)",
+                "Seq Scan on a, filter: (name = 'x')",
+            ]
+        );
+
+        // Below a LEFT JOIN the filter would drop the unmatched rows it is looking for
+        assert_eq!(operators(&plans[1])[1], "Filter: score IS NULL");
+    }
+
+    #[test]
+    fn equality_on_an_indexed_column_uses_the_index() {
+        let plans = plans(
+            "access",
+            &[
+                "SELECT * FROM b WHERE a_id = 3 AND score < 2",
+                "SELECT * FROM b WHERE a_id + 1 = 3",
+            ],
+        );
+
+        assert_eq!(
+            operators(&plans[0])[1],
+            "Index Scan on b using b_a_id, filter: ((a_id = 3) AND (score < 2))"
+        );
+        assert!(operators(&plans[1])[1].starts_with("Seq Scan on b"));
+    }
+}
@@ -0,0 +1,856 @@
+use crate::{
+    frontend::{
+        ast::{
+            BinaryOp, ColumnDef, Expr, FunctionArgs, Join, JoinKind, OrderByItem, Select,
+            SelectItem, Statement, TableRef, UnaryOp,
+        },
+        lexer::Lexer,
+        token::{Keyword, SyntaxError, Token, TokenKind},
+    },
+    types::{DataType, Value},
+};
+
+// Recursive-descent parser, one method per grammar rule
+//
+// Expression precedence, loosest first:
+// OR
+// AND
+// NOT
+// =, !=, IS [NOT] NULL, [NOT] IN, [NOT] LIKE, [NOT] BETWEEN
+// <, <=, >, >=
+// +, -
+// *, /, %
+// ||
+// unary -, +
+pub struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, index: 0 }
+    }
+
+    // Parse every statement of the input, stopping at the first error
+    pub fn parse(sql: &str) -> Result<Vec<Statement>, SyntaxError> {
+        Self::parse_recoverable(sql).into_iter().collect()
+    }
+
+    // Parse every statement of the input, in order
+    // A bad statement gives an Err and parsing resumes after its `;`
+    pub fn parse_recoverable(sql: &str) -> Vec<Result<Statement, SyntaxError>> {
+        let tokens = match Lexer::tokenize(sql) {
+            Ok(tokens) => tokens,
+            Err(e) => return vec![Err(e)],
+        };
+
+        let mut parser = Parser::new(tokens);
+        let mut results = Vec::new();
+        loop {
+            while parser.eat(&TokenKind::Semicolon) {}
+            if parser.at(&TokenKind::Eof) {
+                return results;
+            }
+
+            let result = parser.parse_statement();
+            if result.is_err() {
+                parser.skip_statement();
+            }
+            results.push(result);
+        }
+    }
+
+    pub fn parse_statement(&mut self) -> Result<Statement, SyntaxError> {
+        let statement = self.parse_statement_body()?;
+
+        if !self.eat(&TokenKind::Semicolon) && !self.at(&TokenKind::Eof) {
+            return Err(self.expected("';' or end of input"));
+        }
+        Ok(statement)
+    }
+
+    fn parse_statement_body(&mut self) -> Result<Statement, SyntaxError> {
+        let statement = match self.peek().kind {
+            TokenKind::Keyword(Keyword::Create) => match self.peek_at(1).kind {
+                TokenKind::Keyword(Keyword::Index) => self.parse_create_index()?,
+                _ => self.parse_create_table()?,
+            },
+            TokenKind::Keyword(Keyword::Drop) => self.parse_drop_table()?,
+            TokenKind::Keyword(Keyword::Insert) => self.parse_insert()?,
+            TokenKind::Keyword(Keyword::Select) => {
+                Statement::Select(Box::new(self.parse_select()?))
+            }
+            TokenKind::Keyword(Keyword::Update) => self.parse_update()?,
+            TokenKind::Keyword(Keyword::Delete) => self.parse_delete()?,
+            TokenKind::Keyword(Keyword::Explain) => self.parse_explain()?,
+            TokenKind::Keyword(Keyword::Begin | Keyword::Commit | Keyword::Rollback) => {
+                self.parse_transaction_control()
+            }
+            _ => return Err(self.expected("a statement")),
+        };
+        Ok(statement)
+    }
+
+    // BEGIN / COMMIT / ROLLBACK [TRANSACTION]
+    // TRANSACTION is only a keyword here, so it stays usable as a name
+    fn parse_transaction_control(&mut self) -> Statement {
+        let statement = match self.advance().kind {
+            TokenKind::Keyword(Keyword::Begin) => Statement::Begin,
+            TokenKind::Keyword(Keyword::Commit) => Statement::Commit,
+            _ => Statement::Rollback,
+        };
+        self.eat_word("TRANSACTION");
+        statement
+    }
+
+    // EXPLAIN [ANALYZE] statement
+    // ANALYZE is only a keyword here, so it stays usable as a name
+    fn parse_explain(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Explain)?;
+        let analyze = self.eat_word("ANALYZE");
+        let statement = self.parse_statement_body()?;
+        Ok(Statement::Explain {
+            statement: Box::new(statement),
+            analyze,
+        })
+    }
+
+    // CREATE TABLE [IF NOT EXISTS] name (column type, ...)
+    fn parse_create_table(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Create)?;
+        self.expect_keyword(Keyword::Table)?;
+        let if_not_exists = if self.eat_keyword(Keyword::If) {
+            self.expect_keyword(Keyword::Not)?;
+            self.expect_keyword(Keyword::Exists)?;
+            true
+        } else {
+            false
+        };
+        let name = self.expect_identifier()?;
+
+        self.expect(&TokenKind::LParen)?;
+        let mut columns = Vec::new();
+        loop {
+            let column_name = self.expect_identifier()?;
+            let data_type = self.parse_data_type()?;
+            columns.push(ColumnDef {
+                name: column_name,
+                data_type,
+            });
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RParen)?;
+
+        Ok(Statement::CreateTable {
+            name,
+            columns,
+            if_not_exists,
+        })
+    }
+
+    // Type names are identifiers, common aliases map to the storage types
+    // A length such as VARCHAR(255) is accepted and ignored
+    fn parse_data_type(&mut self) -> Result<DataType, SyntaxError> {
+        let token = self.peek().clone();
+        let TokenKind::Identifier(name) = &token.kind else {
+            return Err(self.expected("a column type"));
+        };
+
+        let data_type = match name.to_ascii_uppercase().as_str() {
+            "INTEGER" | "INT" | "BIGINT" | "SMALLINT" => DataType::Integer,
+            "REAL" | "FLOAT" | "DOUBLE" => DataType::Real,
+            "TEXT" | "VARCHAR" | "CHAR" | "STRING" => DataType::Text,
+            "BLOB" => DataType::Blob,
+            "BOOLEAN" | "BOOL" => DataType::Boolean,
+            _ => {
+                return Err(SyntaxError::new(
+                    format!("Unknown column type '{}'", name),
+                    token.pos,
+                ));
+            }
+        };
+        self.advance();
+
+        if self.eat(&TokenKind::LParen) {
+            match self.peek().kind {
+                TokenKind::Integer(_) => self.advance(),
+                _ => return Err(self.expected("a type length")),
+            };
+            self.expect(&TokenKind::RParen)?;
+        }
+        Ok(data_type)
+    }
+
+    // CREATE INDEX [IF NOT EXISTS] name ON table (column, ...)
+    fn parse_create_index(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Create)?;
+        self.expect_keyword(Keyword::Index)?;
+        let if_not_exists = if self.eat_keyword(Keyword::If) {
+            self.expect_keyword(Keyword::Not)?;
+            self.expect_keyword(Keyword::Exists)?;
+            true
+        } else {
+            false
+        };
+        let name = self.expect_identifier()?;
+
+        self.expect_keyword(Keyword::On)?;
+        let table = self.expect_identifier()?;
+        self.expect(&TokenKind::LParen)?;
+        let columns = self.parse_comma_list(Self::expect_identifier)?;
+        self.expect(&TokenKind::RParen)?;
+
+        Ok(Statement::CreateIndex {
+            name,
+            table,
+            columns,
+            if_not_exists,
+        })
+    }
+
+    // DROP TABLE [IF EXISTS] name
+    fn parse_drop_table(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Drop)?;
+        self.expect_keyword(Keyword::Table)?;
+        let if_exists = if self.eat_keyword(Keyword::If) {
+            self.expect_keyword(Keyword::Exists)?;
+            true
+        } else {
+            false
+        };
+        let name = self.expect_identifier()?;
+
+        Ok(Statement::DropTable { name, if_exists })
+    }
+
+    // INSERT INTO name [(column, ...)] VALUES (expr, ...), ...
+    fn parse_insert(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Insert)?;
+        self.expect_keyword(Keyword::Into)?;
+        let table = self.expect_identifier()?;
+
+        let columns = if self.eat(&TokenKind::LParen) {
+            let columns = self.parse_comma_list(Self::expect_identifier)?;
+            self.expect(&TokenKind::RParen)?;
+            Some(columns)
+        } else {
+            None
+        };
+
+        self.expect_keyword(Keyword::Values)?;
+        let mut rows = Vec::new();
+        loop {
+            self.expect(&TokenKind::LParen)?;
+            rows.push(self.parse_comma_list(Self::parse_expr)?);
+            self.expect(&TokenKind::RParen)?;
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        Ok(Statement::Insert {
+            table,
+            columns,
+            rows,
+        })
+    }
+
+    // SELECT items [FROM table [[AS] alias] [joins]] [WHERE expr]
+    //        [GROUP BY exprs [HAVING expr]]
+    // [ORDER BY expr [ASC | DESC], ...] [LIMIT expr [OFFSET expr]]
+    fn parse_select(&mut self) -> Result<Select, SyntaxError> {
+        self.expect_keyword(Keyword::Select)?;
+        let projection = self.parse_comma_list(Self::parse_select_item)?;
+
+        let mut from = None;
+        let mut joins = Vec::new();
+        if self.eat_keyword(Keyword::From) {
+            from = Some(self.parse_table_ref()?);
+            while let Some(join) = self.parse_join()? {
+                joins.push(join);
+            }
+        }
+
+        let where_clause = self.parse_where()?;
+
+        let mut group_by = Vec::new();
+        let mut having = None;
+        if self.eat_keyword(Keyword::Group) {
+            self.expect_keyword(Keyword::By)?;
+            group_by = self.parse_comma_list(Self::parse_expr)?;
+        }
+        if self.eat_keyword(Keyword::Having) {
+            having = Some(self.parse_expr()?);
+        }
+
+        let mut order_by = Vec::new();
+        if self.eat_keyword(Keyword::Order) {
+            self.expect_keyword(Keyword::By)?;
+            order_by = self.parse_comma_list(|p| {
+                let expr = p.parse_expr()?;
+                let descending = if p.eat_keyword(Keyword::Desc) {
+                    true
+                } else {
+                    p.eat_keyword(Keyword::Asc);
+                    false
+                };
+                let nulls_first = p.parse_nulls_order()?;
+                Ok(OrderByItem {
+                    expr,
+                    descending,
+                    nulls_first,
+                })
+            })?;
+        }
+
+        let mut limit = None;
+        let mut offset = None;
+        if self.eat_keyword(Keyword::Limit) {
+            limit = Some(self.parse_expr()?);
+            if self.eat_keyword(Keyword::Offset) {
+                offset = Some(self.parse_expr()?);
+            }
+        }
+
+        Ok(Select {
+            projection,
+            from,
+            joins,
+            where_clause,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+
+    // [NULLS FIRST | NULLS LAST]
+    // Not keywords, so columns can still be called "first" or "last"
+    fn parse_nulls_order(&mut self) -> Result<Option<bool>, SyntaxError> {
+        if !self.eat_word("NULLS") {
+            return Ok(None);
+        }
+        if self.eat_word("FIRST") {
+            Ok(Some(true))
+        } else if self.eat_word("LAST") {
+            Ok(Some(false))
+        } else {
+            Err(self.expected("FIRST or LAST"))
+        }
+    }
+
+    fn parse_select_item(&mut self) -> Result<SelectItem, SyntaxError> {
+        if self.eat(&TokenKind::Star) {
+            return Ok(SelectItem::Wildcard);
+        }
+
+        // t.*
+        if let TokenKind::Identifier(table) = &self.peek().kind
+            && self.peek_at(1).kind == TokenKind::Dot
+            && self.peek_at(2).kind == TokenKind::Star
+        {
+            let table = table.clone();
+            self.index += 3;
+            return Ok(SelectItem::QualifiedWildcard(table));
+        }
+
+        let expr = self.parse_expr()?;
+        let alias = self.parse_alias()?;
+        Ok(SelectItem::Expr { expr, alias })
+    }
+
+    fn parse_table_ref(&mut self) -> Result<TableRef, SyntaxError> {
+        let name = self.expect_identifier()?;
+        let alias = self.parse_alias()?;
+        Ok(TableRef { name, alias })
+    }
+
+    // , table
+    // CROSS JOIN table
+    // [INNER] JOIN table ON expr
+    // LEFT [OUTER] JOIN table ON expr
+    fn parse_join(&mut self) -> Result<Option<Join>, SyntaxError> {
+        let kind = if self.eat(&TokenKind::Comma) {
+            let table = self.parse_table_ref()?;
+            return Ok(Some(Join {
+                kind: JoinKind::Cross,
+                table,
+                on: None,
+            }));
+        } else if self.eat_keyword(Keyword::Cross) {
+            JoinKind::Cross
+        } else if self.eat_keyword(Keyword::Left) {
+            self.eat_keyword(Keyword::Outer);
+            JoinKind::Left
+        } else if self.eat_keyword(Keyword::Inner) || self.at(&TokenKind::Keyword(Keyword::Join)) {
+            JoinKind::Inner
+        } else {
+            return Ok(None);
+        };
+
+        self.expect_keyword(Keyword::Join)?;
+        let table = self.parse_table_ref()?;
+        let on = if kind == JoinKind::Cross {
+            None
+        } else {
+            self.expect_keyword(Keyword::On)?;
+            Some(self.parse_expr()?)
+        };
+
+        Ok(Some(Join { kind, table, on }))
+    }
+
+    // [AS] name
+    fn parse_alias(&mut self) -> Result<Option<String>, SyntaxError> {
+        if self.eat_keyword(Keyword::As) {
+            return Ok(Some(self.expect_identifier()?));
+        }
+        if let TokenKind::Identifier(name) = &self.peek().kind {
+            let name = name.clone();
+            self.advance();
+            return Ok(Some(name));
+        }
+        Ok(None)
+    }
+
+    // UPDATE name SET column = expr, ... [WHERE expr]
+    fn parse_update(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Update)?;
+        let table = self.expect_identifier()?;
+        self.expect_keyword(Keyword::Set)?;
+
+        let assignments = self.parse_comma_list(|p| {
+            let column = p.expect_identifier()?;
+            p.expect(&TokenKind::Eq)?;
+            Ok((column, p.parse_expr()?))
+        })?;
+        let where_clause = self.parse_where()?;
+
+        Ok(Statement::Update {
+            table,
+            assignments,
+            where_clause,
+        })
+    }
+
+    // DELETE FROM name [WHERE expr]
+    fn parse_delete(&mut self) -> Result<Statement, SyntaxError> {
+        self.expect_keyword(Keyword::Delete)?;
+        self.expect_keyword(Keyword::From)?;
+        let table = self.expect_identifier()?;
+        let where_clause = self.parse_where()?;
+
+        Ok(Statement::Delete {
+            table,
+            where_clause,
+        })
+    }
+
+    fn parse_where(&mut self) -> Result<Option<Expr>, SyntaxError> {
+        if self.eat_keyword(Keyword::Where) {
+            Ok(Some(self.parse_expr()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn parse_expr(&mut self) -> Result<Expr, SyntaxError> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.parse_and()?;
+        while self.eat_keyword(Keyword::Or) {
+            let right = self.parse_and()?;
+            left = binary(left, BinaryOp::Or, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.parse_not()?;
+        while self.eat_keyword(Keyword::And) {
+            let right = self.parse_not()?;
+            left = binary(left, BinaryOp::And, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, SyntaxError> {
+        if self.eat_keyword(Keyword::Not) {
+            let expr = self.parse_not()?;
+            return Ok(Expr::Unary {
+                op: UnaryOp::Not,
+                expr: Box::new(expr),
+            });
+        }
+        self.parse_equality()
+    }
+
+    fn parse_equality(&mut self) -> Result<Expr, SyntaxError> {
+        let mut left = self.parse_binary(PRECEDENCE_COMPARISON)?;
+
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Eq => Some(BinaryOp::Eq),
+                TokenKind::NotEq => Some(BinaryOp::NotEq),
+                _ => None,
+            };
+            if let Some(op) = op {
+                self.advance();
+                let right = self.parse_binary(PRECEDENCE_COMPARISON)?;
+                left = binary(left, op, right);
+                continue;
+            }
+
+            // expr IS [NOT] NULL
+            if self.eat_keyword(Keyword::Is) {
+                let negated = self.eat_keyword(Keyword::Not);
+                self.expect_keyword(Keyword::Null)?;
+                left = Expr::IsNull {
+                    expr: Box::new(left),
+                    negated,
+                };
+                continue;
+            }
+
+            // expr [NOT] (IN | LIKE | BETWEEN) ...
+            let negated = self.peek().kind == TokenKind::Keyword(Keyword::Not)
+                && matches!(
+                    self.peek_at(1).kind,
+                    TokenKind::Keyword(Keyword::In | Keyword::Like | Keyword::Between)
+                );
+            if negated {
+                self.advance();
+            }
+
+            if self.eat_keyword(Keyword::In) {
+                self.expect(&TokenKind::LParen)?;
+                let list = self.parse_comma_list(Self::parse_expr)?;
+                self.expect(&TokenKind::RParen)?;
+                left = Expr::InList {
+                    expr: Box::new(left),
+                    list,
+                    negated,
+                };
+            } else if self.eat_keyword(Keyword::Like) {
+                let pattern = self.parse_binary(PRECEDENCE_COMPARISON)?;
+                left = Expr::Like {
+                    expr: Box::new(left),
+                    pattern: Box::new(pattern),
+                    negated,
+                };
+            } else if self.eat_keyword(Keyword::Between) {
+                // The AND here belongs to BETWEEN, so bounds stop below AND
+                let low = self.parse_binary(PRECEDENCE_COMPARISON)?;
+                self.expect_keyword(Keyword::And)?;
+                let high = self.parse_binary(PRECEDENCE_COMPARISON)?;
+                left = Expr::Between {
+                    expr: Box::new(left),
+                    low: Box::new(low),
+                    high: Box::new(high),
+                    negated,
+                };
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    // Precedence climbing over the left-associative binary operators
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, SyntaxError> {
+        let mut left = self.parse_unary()?;
+
+        while let Some((op, precedence)) = binary_operator(&self.peek().kind) {
+            if precedence < min_precedence {
+                break;
+            }
+            self.advance();
+            let right = self.parse_binary(precedence + 1)?;
+            left = binary(left, op, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, SyntaxError> {
+        let op = match self.peek().kind {
+            TokenKind::Minus => UnaryOp::Neg,
+            TokenKind::Plus => UnaryOp::Plus,
+            _ => return self.parse_primary(),
+        };
+        self.advance();
+
+        // `-9223372036854775808` is i64::MIN, the literal alone does not fit in an i64
+        if op == UnaryOp::Neg && self.peek().kind == TokenKind::Integer(i64::MIN.unsigned_abs()) {
+            self.advance();
+            return Ok(Expr::Literal(Value::Integer(i64::MIN)));
+        }
+        let expr = self.parse_unary()?;
+
+        // Fold signs into numeric literals, so `-5` is the literal -5
+        Ok(match (op, expr) {
+            (UnaryOp::Neg, Expr::Literal(Value::Integer(n))) => match n.checked_neg() {
+                Some(n) => Expr::Literal(Value::Integer(n)),
+                None => Expr::Literal(Value::Real(-(n as f64))),
+            },
+            (UnaryOp::Neg, Expr::Literal(Value::Real(n))) => Expr::Literal(Value::Real(-n)),
+            (op, expr) => Expr::Unary {
+                op,
+                expr: Box::new(expr),
+            },
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, SyntaxError> {
+        let token = self.peek().clone();
+        let expr = match token.kind {
+            // Integers too large for i64 become reals, like in SQLite
+            TokenKind::Integer(n) => match i64::try_from(n) {
+                Ok(n) => Expr::Literal(Value::Integer(n)),
+                Err(_) => Expr::Literal(Value::Real(n as f64)),
+            },
+            TokenKind::Real(n) => Expr::Literal(Value::Real(n)),
+            TokenKind::String(s) => Expr::Literal(Value::Text(s)),
+            TokenKind::Blob(b) => Expr::Literal(Value::Blob(b)),
+            TokenKind::Keyword(Keyword::True) => Expr::Literal(Value::Boolean(true)),
+            TokenKind::Keyword(Keyword::False) => Expr::Literal(Value::Boolean(false)),
+            TokenKind::Keyword(Keyword::Null) => Expr::Literal(Value::Null),
+            TokenKind::Identifier(name) => {
+                self.advance();
+                if self.at(&TokenKind::LParen) {
+                    return self.parse_function(name);
+                }
+                if self.eat(&TokenKind::Dot) {
+                    let column = self.expect_identifier()?;
+                    return Ok(Expr::Column {
+                        table: Some(name),
+                        name: column,
+                    });
+                }
+                return Ok(Expr::Column { table: None, name });
+            }
+            TokenKind::LParen => {
+                self.advance();
+                let expr = self.parse_expr()?;
+                self.expect(&TokenKind::RParen)?;
+                return Ok(expr);
+            }
+            _ => return Err(self.expected("an expression")),
+        };
+        self.advance();
+        Ok(expr)
+    }
+
+    // name( * | [DISTINCT] expr, ... ), after the name
+    fn parse_function(&mut self, name: String) -> Result<Expr, SyntaxError> {
+        self.expect(&TokenKind::LParen)?;
+        let args = if self.eat(&TokenKind::Star) {
+            FunctionArgs::Star
+        } else {
+            let distinct = self.eat_keyword(Keyword::Distinct);
+            let args = if !distinct && self.at(&TokenKind::RParen) {
+                Vec::new()
+            } else {
+                self.parse_comma_list(Self::parse_expr)?
+            };
+            FunctionArgs::List { distinct, args }
+        };
+        self.expect(&TokenKind::RParen)?;
+        Ok(Expr::Function { name, args })
+    }
+
+    fn parse_comma_list<T>(
+        &mut self,
+        mut parse_item: impl FnMut(&mut Self) -> Result<T, SyntaxError>,
+    ) -> Result<Vec<T>, SyntaxError> {
+        let mut items = vec![parse_item(self)?];
+        while self.eat(&TokenKind::Comma) {
+            items.push(parse_item(self)?);
+        }
+        Ok(items)
+    }
+
+    // Move past the rest of a bad statement, up to and including its `;`
+    fn skip_statement(&mut self) {
+        while !self.at(&TokenKind::Eof) {
+            let kind = self.advance().kind.clone();
+            if kind == TokenKind::Semicolon {
+                return;
+            }
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        self.peek_at(0)
+    }
+
+    // The token stream always ends with Eof, which is never consumed
+    fn peek_at(&self, offset: usize) -> &Token {
+        let last = self.tokens.len() - 1;
+        &self.tokens[(self.index + offset).min(last)]
+    }
+
+    fn advance(&mut self) -> &Token {
+        let index = self.index;
+        if index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+        &self.tokens[index]
+    }
+
+    fn at(&self, kind: &TokenKind) -> bool {
+        &self.peek().kind == kind
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.at(kind) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: Keyword) -> bool {
+        self.eat(&TokenKind::Keyword(keyword))
+    }
+
+    // An identifier used as a keyword in one place only
+    fn eat_word(&mut self, word: &str) -> bool {
+        match &self.peek().kind {
+            TokenKind::Identifier(name) if name.eq_ignore_ascii_case(word) => {
+                self.advance();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind) -> Result<(), SyntaxError> {
+        if self.eat(kind) {
+            Ok(())
+        } else {
+            Err(self.expected(&format!("'{}'", kind)))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), SyntaxError> {
+        self.expect(&TokenKind::Keyword(keyword))
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, SyntaxError> {
+        if let TokenKind::Identifier(name) = &self.peek().kind {
+            let name = name.clone();
+            self.advance();
+            Ok(name)
+        } else {
+            Err(self.expected("a name"))
+        }
+    }
+
+    fn expected(&self, what: &str) -> SyntaxError {
+        let token = self.peek();
+        SyntaxError::new(
+            format!("Expected {}, found {}", what, token.kind),
+            token.pos,
+        )
+    }
+}
+
+const PRECEDENCE_COMPARISON: u8 = 1;
+
+fn binary_operator(kind: &TokenKind) -> Option<(BinaryOp, u8)> {
+    let op = match kind {
+        TokenKind::Lt => (BinaryOp::Lt, PRECEDENCE_COMPARISON),
+        TokenKind::LtEq => (BinaryOp::LtEq, PRECEDENCE_COMPARISON),
+        TokenKind::Gt => (BinaryOp::Gt, PRECEDENCE_COMPARISON),
+        TokenKind::GtEq => (BinaryOp::GtEq, PRECEDENCE_COMPARISON),
+        TokenKind::Plus => (BinaryOp::Add, 2),
+        TokenKind::Minus => (BinaryOp::Sub, 2),
+        TokenKind::Star => (BinaryOp::Mul, 3),
+        TokenKind::Slash => (BinaryOp::Div, 3),
+        TokenKind::Percent => (BinaryOp::Mod, 3),
+        TokenKind::Concat => (BinaryOp::Concat, 4),
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+    Expr::Binary {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::token::Position;
+
+    // The expression with every binary operation in parentheses
+    fn grouped(sql: &str) -> String {
+        let mut parser = Parser::new(Lexer::tokenize(sql).unwrap());
+        let expr = parser.parse_expr().unwrap();
+        assert!(parser.at(&TokenKind::Eof), "{} was not fully parsed", sql);
+        expr.to_string()
+    }
+
+    fn error_at(sql: &str) -> (String, Position) {
+        let error = Parser::parse(sql).unwrap_err();
+        (error.message, error.pos)
+    }
+
+    fn pos(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(grouped("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(grouped("1 - 2 - 3"), "((1 - 2) - 3)");
+        assert_eq!(grouped("a || b * 2"), "((a || b) * 2)");
+        assert_eq!(grouped("a + 1 < b * 2"), "((a + 1) < (b * 2))");
+        assert_eq!(grouped("a < b = c > d"), "((a < b) = (c > d))");
+        assert_eq!(
+            grouped("a = 1 OR b = 2 AND c = 3"),
+            "((a = 1) OR ((b = 2) AND (c = 3)))"
+        );
+        assert_eq!(grouped("(a OR b) AND c"), "((a OR b) AND c)");
+        assert_eq!(grouped("-a * 2"), "(-a * 2)");
+        assert_eq!(grouped("a + 1 IS NULL"), "(a + 1) IS NULL");
+        assert_eq!(
+            grouped("a BETWEEN 1 AND 2 AND b"),
+            "(a BETWEEN 1 AND 2 AND b)"
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        assert_eq!(
+            error_at("SELECT a FROM"),
+            (
+                "Expected a name, found end of input".to_string(),
+                pos(1, 14)
+            )
+        );
+        assert_eq!(error_at("SELECT a,\n  FROM t").1, pos(2, 3));
+        assert_eq!(error_at("SELECT 1 + ;").1, pos(1, 12));
+        assert_eq!(error_at("SELECT 12abc").1, pos(1, 10));
+        assert_eq!(error_at("SELECT 'open").1, pos(1, 8));
+        assert_eq!(error_at("INSERT INTO t VALUES (1,, 2)").1, pos(1, 25));
+    }
+
+    #[test]
+    fn a_bad_statement_does_not_stop_the_next_ones() {
+        let results = Parser::parse_recoverable("SELECT 1; SELEC 2; SELECT 3");
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok() && results[2].is_ok());
+        assert_eq!(results[1].as_ref().unwrap_err().pos, pos(1, 11));
+    }
+}
@@ -0,0 +1,276 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    acid::transaction::Transaction,
+    backend::cache::Cache,
+    catalog::manager::{Catalog, TableInfo},
+    frontend::{
+        binder::BoundStatement,
+        executor::{
+            Executor, TableIndex,
+            delete::Delete,
+            filter::Filter,
+            hash_aggregate::HashAggregate,
+            hash_join::HashJoin,
+            index_nested_loop_join::IndexNestedLoopJoin,
+            index_scan::IndexScan,
+            insert::Insert,
+            limit::Limit,
+            nested_loop_join::NestedLoopJoin,
+            profile::{Profile, Profiled},
+            projection::Projection,
+            seq_scan::SeqScan,
+            sort::Sort,
+            update::Update,
+            values::Values,
+        },
+        logical_plan::{Access, JoinStrategy, LogicalPlan},
+        optimizer::{Optimizer, conjunction, index_columns, split_conjunction},
+    },
+    indexing::{btree::index::BTreeIndex, table_heap::TableHeap},
+};
+
+// Turns a bound statement into a tree of executors:
+// builds its logical plan, lets the optimizer rewrite it,
+// then maps every logical operator to the executor implementing it.
+//
+// Scan:       SeqScan or IndexScan, with a Filter on top when the scan has a filter
+//             (an IndexScan only narrows the rows, the whole filter is still checked)
+// Join:       NestedLoopJoin, IndexNestedLoopJoin or HashJoin, as the optimizer chose
+// Aggregate:  HashAggregate, Sort: external merge Sort
+pub struct Planner<'a> {
+    cache: Arc<Cache>,
+    catalog: &'a Catalog,
+    // Bytes an operator may hold in memory
+    memory_budget: usize,
+    // The statement runs in it, table heaps record their changes there
+    txn: Arc<Transaction>,
+}
+
+impl<'a> Planner<'a> {
+    pub fn new(
+        cache: Arc<Cache>,
+        catalog: &'a Catalog,
+        memory_budget: usize,
+        txn: Arc<Transaction>,
+    ) -> Self {
+        Self {
+            cache,
+            catalog,
+            memory_budget,
+            txn,
+        }
+    }
+
+    // The root executor, with the names of its output columns
+    // DDL statements have no plan
+    pub fn plan(
+        &self,
+        statement: BoundStatement,
+    ) -> Result<(Box<dyn Executor>, Vec<String>), String> {
+        let (plan, names) = self.optimize(statement)?;
+        Ok((self.build(plan)?, names))
+    }
+
+    // The optimized logical plan, with the names of its output columns
+    pub fn optimize(
+        &self,
+        statement: BoundStatement,
+    ) -> Result<(LogicalPlan, Vec<String>), String> {
+        let (plan, names) = LogicalPlan::build(statement)?;
+        let plan = self.optimizer().optimize(plan)?;
+        Ok((plan, names))
+    }
+
+    pub fn optimizer(&self) -> Optimizer<'a> {
+        Optimizer::new(self.cache.clone(), self.catalog)
+    }
+
+    pub fn build(&self, plan: LogicalPlan) -> Result<Box<dyn Executor>, String> {
+        self.build_node(plan, None)
+    }
+
+    // Like `build`, with every operator measured for EXPLAIN ANALYZE
+    // `profiles` gets one entry per operator, in pre-order (see `explain::describe`)
+    pub fn build_profiled(
+        &self,
+        plan: LogicalPlan,
+        profiles: &mut Vec<Arc<Mutex<Profile>>>,
+    ) -> Result<Box<dyn Executor>, String> {
+        self.build_node(plan, Some(profiles))
+    }
+
+    fn build_node(
+        &self,
+        plan: LogicalPlan,
+        mut profiles: Option<&mut Vec<Arc<Mutex<Profile>>>>,
+    ) -> Result<Box<dyn Executor>, String> {
+        let profile = profiles.as_mut().map(|profiles| {
+            let profile = Arc::new(Mutex::new(Profile::default()));
+            profiles.push(profile.clone());
+            profile
+        });
+
+        let root: Box<dyn Executor> = match plan {
+            LogicalPlan::Scan {
+                table,
+                filter,
+                access,
+            } => {
+                let scan: Box<dyn Executor> = match access {
+                    Access::Seq => {
+                        Box::new(SeqScan::new(self.table_heap(&table), table.schema.clone()))
+                    }
+                    Access::Index {
+                        index,
+                        lower,
+                        upper,
+                    } => Box::new(IndexScan::new(
+                        self.table_heap(&table),
+                        table.schema.clone(),
+                        BTreeIndex::open(self.cache.clone(), index.root_page_id, self.txn.id()),
+                        lower,
+                        upper,
+                    )),
+                };
+                match filter {
+                    Some(filter) => Box::new(Filter::new(scan, filter)),
+                    None => scan,
+                }
+            }
+            LogicalPlan::Values { rows } => Box::new(Values::new(rows)),
+            LogicalPlan::Filter { input, predicate } => Box::new(Filter::new(
+                self.build_node(*input, profiles.as_deref_mut())?,
+                predicate,
+            )),
+            LogicalPlan::Project { input, exprs } => Box::new(Projection::new(
+                self.build_node(*input, profiles.as_deref_mut())?,
+                exprs,
+            )),
+            LogicalPlan::Join {
+                left,
+                right,
+                kind,
+                on,
+                strategy,
+            } => {
+                let left_width = left.width();
+                let right_width = right.width();
+                match strategy {
+                    JoinStrategy::NestedLoop => Box::new(NestedLoopJoin::new(
+                        self.build_node(*left, profiles.as_deref_mut())?,
+                        self.build_node(*right, profiles.as_deref_mut())?,
+                        kind,
+                        on,
+                        right_width,
+                    )),
+                    JoinStrategy::IndexLookup { index, probe } => {
+                        let LogicalPlan::Scan { table, filter, .. } = *right else {
+                            return Err("An index lookup join needs a table on the right".into());
+                        };
+                        // The right table's own filter is checked on the joined row
+                        let mut conditions: Vec<_> =
+                            on.iter().flat_map(split_conjunction).cloned().collect();
+                        conditions.extend(filter.map(|f| f.map_columns(&|i| i + left_width)));
+                        Box::new(IndexNestedLoopJoin::new(
+                            self.build_node(*left, profiles.as_deref_mut())?,
+                            self.table_heap(&table),
+                            table.schema.clone(),
+                            BTreeIndex::open(self.cache.clone(), index.root_page_id, self.txn.id()),
+                            probe,
+                            kind,
+                            conjunction(conditions),
+                        ))
+                    }
+                    JoinStrategy::Hash {
+                        left_keys,
+                        right_keys,
+                        residual,
+                    } => Box::new(HashJoin::new(
+                        self.build_node(*left, profiles.as_deref_mut())?,
+                        self.build_node(*right, profiles.as_deref_mut())?,
+                        left_keys,
+                        right_keys,
+                        kind,
+                        residual,
+                        right_width,
+                        self.memory_budget,
+                    )),
+                }
+            }
+            LogicalPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => Box::new(HashAggregate::new(
+                self.build_node(*input, profiles.as_deref_mut())?,
+                group_by,
+                aggregates,
+                self.memory_budget,
+            )),
+            LogicalPlan::Sort { input, keys } => Box::new(Sort::new(
+                self.build_node(*input, profiles.as_deref_mut())?,
+                keys,
+                self.cache.clone(),
+                self.memory_budget,
+            )),
+            LogicalPlan::Limit {
+                input,
+                limit,
+                offset,
+            } => Box::new(Limit::new(
+                self.build_node(*input, profiles.as_deref_mut())?,
+                limit,
+                offset,
+            )),
+            LogicalPlan::Insert { table, input } => Box::new(Insert::new(
+                self.table_heap(&table),
+                table.schema.clone(),
+                self.table_indexes(&table)?,
+                self.build_node(*input, profiles.as_deref_mut())?,
+            )),
+            LogicalPlan::Update {
+                table,
+                input,
+                assignments,
+            } => Box::new(Update::new(
+                self.table_heap(&table),
+                table.schema.clone(),
+                self.table_indexes(&table)?,
+                self.build_node(*input, profiles.as_deref_mut())?,
+                assignments,
+            )),
+            LogicalPlan::Delete { table, input } => Box::new(Delete::new(
+                self.table_heap(&table),
+                self.table_indexes(&table)?,
+                self.build_node(*input, profiles)?,
+            )),
+        };
+
+        Ok(match profile {
+            Some(profile) => Box::new(Profiled::new(root, profile, self.cache.clone())),
+            None => root,
+        })
+    }
+
+    // Indexes a DML statement must keep up to date
+    pub fn table_indexes(&self, table: &TableInfo) -> Result<Vec<TableIndex>, String> {
+        self.catalog
+            .table_indexes(&table.name)
+            .into_iter()
+            .map(|info| {
+                Ok(TableIndex {
+                    index: BTreeIndex::open(self.cache.clone(), info.root_page_id, self.txn.id()),
+                    columns: index_columns(info, table)?,
+                })
+            })
+            .collect()
+    }
+
+    fn table_heap(&self, table: &TableInfo) -> Arc<TableHeap> {
+        Arc::new(
+            TableHeap::new(self.cache.clone(), table.first_page_id, self.txn.id())
+                .in_transaction(self.txn.clone()),
+        )
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt;
+
+// Where a token starts in the input (both 1-based, column counts characters)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+// Error found while reading SQL text, points at the offending character
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub pos: Position,
+}
+
+impl SyntaxError {
+    pub fn new(message: impl Into<String>, pos: Position) -> Self {
+        Self {
+            message: message.into(),
+            pos,
+        }
+    }
+
+    // The error, followed by the offending line with a caret under the character
+    pub fn render(&self, input: &str) -> String {
+        let line = input.lines().nth(self.pos.line - 1).unwrap_or("");
+        format!(
+            "{}\n{}\n{}^",
+            self,
+            line,
+            " ".repeat(self.pos.column.saturating_sub(1))
+        )
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.pos)
+    }
+}
+
+// Reserved words, matched case-insensitively
+// Type names (INTEGER, TEXT, ...) are plain identifiers, like in SQLite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Keyword {
+    And,
+    As,
+    Asc,
+    Begin,
+    Between,
+    By,
+    Commit,
+    Create,
+    Cross,
+    Delete,
+    Desc,
+    Distinct,
+    Drop,
+    Exists,
+    Explain,
+    False,
+    From,
+    Group,
+    Having,
+    If,
+    In,
+    Index,
+    Inner,
+    Insert,
+    Into,
+    Is,
+    Join,
+    Left,
+    Like,
+    Limit,
+    Not,
+    Null,
+    Offset,
+    On,
+    Or,
+    Order,
+    Outer,
+    Rollback,
+    Select,
+    Set,
+    Table,
+    True,
+    Update,
+    Values,
+    Where,
+}
+
+impl Keyword {
+    pub fn from_word(word: &str) -> Option<Self> {
+        let keyword = match word.to_ascii_uppercase().as_str() {
+            "AND" => Keyword::And,
+            "AS" => Keyword::As,
+            "ASC" => Keyword::Asc,
+            "BEGIN" => Keyword::Begin,
+            "BETWEEN" => Keyword::Between,
+            "BY" => Keyword::By,
+            "COMMIT" => Keyword::Commit,
+            "CREATE" => Keyword::Create,
+            "CROSS" => Keyword::Cross,
+            "DELETE" => Keyword::Delete,
+            "DESC" => Keyword::Desc,
+            "DISTINCT" => Keyword::Distinct,
+            "DROP" => Keyword::Drop,
+            "EXISTS" => Keyword::Exists,
+            "EXPLAIN" => Keyword::Explain,
+            "FALSE" => Keyword::False,
+            "FROM" => Keyword::From,
+            "GROUP" => Keyword::Group,
+            "HAVING" => Keyword::Having,
+            "IF" => Keyword::If,
+            "IN" => Keyword::In,
+            "INDEX" => Keyword::Index,
+            "INNER" => Keyword::Inner,
+            "INSERT" => Keyword::Insert,
+            "INTO" => Keyword::Into,
+            "IS" => Keyword::Is,
+            "JOIN" => Keyword::Join,
+            "LEFT" => Keyword::Left,
+            "LIKE" => Keyword::Like,
+            "LIMIT" => Keyword::Limit,
+            "NOT" => Keyword::Not,
+            "NULL" => Keyword::Null,
+            "OFFSET" => Keyword::Offset,
+            "ON" => Keyword::On,
+            "OR" => Keyword::Or,
+            "ORDER" => Keyword::Order,
+            "OUTER" => Keyword::Outer,
+            "ROLLBACK" => Keyword::Rollback,
+            "SELECT" => Keyword::Select,
+            "SET" => Keyword::Set,
+            "TABLE" => Keyword::Table,
+            "TRUE" => Keyword::True,
+            "UPDATE" => Keyword::Update,
+            "VALUES" => Keyword::Values,
+            "WHERE" => Keyword::Where,
+            _ => return None,
+        };
+        Some(keyword)
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let word = format!("{:?}", self).to_ascii_uppercase();
+        write!(f, "{}", word)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Keyword(Keyword),
+    // Unquoted identifiers keep their spelling, quoted ones may also be keywords
+    Identifier(String),
+
+    // Unsigned magnitude, a leading `-` is a separate token the parser folds in
+    Integer(u64),
+    Real(f64),
+    String(String),
+    Blob(Vec<u8>),
+
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Concat,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+
+    LParen,
+    RParen,
+    Comma,
+    Dot,
+    Semicolon,
+
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Keyword(keyword) => write!(f, "{}", keyword),
+            TokenKind::Identifier(name) => write!(f, "\"{}\"", name),
+            TokenKind::Integer(n) => write!(f, "{}", n),
+            TokenKind::Real(n) => write!(f, "{}", n),
+            TokenKind::String(s) => write!(f, "'{}'", s),
+            TokenKind::Blob(bytes) => {
+                write!(f, "x'")?;
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                write!(f, "'")
+            }
+            TokenKind::Plus => write!(f, "+"),
+            TokenKind::Minus => write!(f, "-"),
+            TokenKind::Star => write!(f, "*"),
+            TokenKind::Slash => write!(f, "/"),
+            TokenKind::Percent => write!(f, "%"),
+            TokenKind::Concat => write!(f, "||"),
+            TokenKind::Eq => write!(f, "="),
+            TokenKind::NotEq => write!(f, "!="),
+            TokenKind::Lt => write!(f, "<"),
+            TokenKind::LtEq => write!(f, "<="),
+            TokenKind::Gt => write!(f, ">"),
+            TokenKind::GtEq => write!(f, ">="),
+            TokenKind::LParen => write!(f, "("),
+            TokenKind::RParen => write!(f, ")"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Dot => write!(f, "."),
+            TokenKind::Semicolon => write!(f, ";"),
+            TokenKind::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: Position,
+}
@@ -0,0 +1,160 @@
+use std::{ops::Bound, sync::Arc};
+
+use crate::{
+    acid::log_record::TxnId,
+    backend::cache::Cache,
+    indexing::btree::{
+        key::{encode_key, prefix_successor},
+        tree::{BPlusTree, BTreeIterator},
+    },
+    types::Value,
+};
+
+// (PageID, SlotID) of a tuple in a TableHeap
+pub type Rid = (usize, u16);
+
+const RID_SIZE: usize = 6;
+
+// Secondary index: key Values -> RIDs of the matching tuples
+// Several tuples may share a key, so the RID is also appended to the tree key:
+// tree key = encode_key(values) + RID, tree value = RID
+// Every tree key is unique, and all entries of one key are next to each other.
+pub struct BTreeIndex {
+    tree: BPlusTree,
+}
+
+impl BTreeIndex {
+    // Changes are logged under `txn`
+    pub fn create(cache: Arc<Cache>, txn: TxnId) -> Result<Self, String> {
+        Ok(Self {
+            tree: BPlusTree::create(cache, txn)?,
+        })
+    }
+
+    pub fn open(cache: Arc<Cache>, root_page_id: usize, txn: TxnId) -> Self {
+        Self {
+            tree: BPlusTree::open(cache, root_page_id, txn),
+        }
+    }
+
+    pub fn root_page_id(&self) -> usize {
+        self.tree.root_page_id()
+    }
+
+    pub fn insert(&self, key: &[Value], rid: Rid) -> Result<(), String> {
+        let rid_bytes = encode_rid(rid);
+        let mut tree_key = encode_key(key);
+        tree_key.extend_from_slice(&rid_bytes);
+
+        if self.tree.insert(&tree_key, &rid_bytes)? {
+            Ok(())
+        } else {
+            Err(format!("Index already has an entry for RID {:?}", rid))
+        }
+    }
+
+    // Returns false if the entry does not exist
+    pub fn delete(&self, key: &[Value], rid: Rid) -> Result<bool, String> {
+        let mut tree_key = encode_key(key);
+        tree_key.extend_from_slice(&encode_rid(rid));
+        self.tree.delete(&tree_key)
+    }
+
+    pub fn contains(&self, key: &[Value], rid: Rid) -> Result<bool, String> {
+        let mut tree_key = encode_key(key);
+        tree_key.extend_from_slice(&encode_rid(rid));
+        Ok(self.tree.get(&tree_key)?.is_some())
+    }
+
+    // RIDs of every tuple with this key
+    pub fn get(&self, key: &[Value]) -> Result<Vec<Rid>, String> {
+        self.scan(Bound::Included(key), Bound::Included(key))?
+            .collect()
+    }
+
+    // RIDs of the tuples whose key is within the bounds, in key order
+    // A bound may be a prefix of the key columns (e.g. only the first column)
+    pub fn scan(
+        &self,
+        lower: Bound<&[Value]>,
+        upper: Bound<&[Value]>,
+    ) -> Result<IndexIterator, String> {
+        let (lower, upper) = key_bounds(lower, upper);
+        self.scan_encoded(lower, upper)
+    }
+
+    // Same as `scan`, with bounds already encoded (see `key.rs`)
+    pub fn scan_encoded(
+        &self,
+        lower: Bound<Vec<u8>>,
+        upper: Bound<Vec<u8>>,
+    ) -> Result<IndexIterator, String> {
+        let inner = self.tree.range(
+            lower.as_ref().map(|k| k.as_slice()),
+            upper.as_ref().map(|k| k.as_slice()),
+        )?;
+        Ok(IndexIterator { inner })
+    }
+
+    // Free every page of the index
+    pub fn destroy(&self) -> Result<(), String> {
+        self.tree.destroy()
+    }
+}
+
+pub struct IndexIterator {
+    inner: BTreeIterator,
+}
+
+impl Iterator for IndexIterator {
+    type Item = Result<Rid, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.inner.next()?;
+        Some(entry.and_then(|(_, value)| decode_rid(&value)))
+    }
+}
+
+// Tree key bounds covering every entry whose key is within the value bounds
+pub fn key_bounds(
+    lower: Bound<&[Value]>,
+    upper: Bound<&[Value]>,
+) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    // Entries of key K are K + RID, so they sort after K itself
+    let lower = match lower {
+        Bound::Included(key) => Bound::Included(encode_key(key)),
+        Bound::Excluded(key) => match prefix_successor(&encode_key(key)) {
+            Some(successor) => Bound::Included(successor),
+            // Nothing sorts after an empty key: empty scan
+            None => return (Bound::Unbounded, Bound::Excluded(vec![])),
+        },
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    let upper = match upper {
+        Bound::Included(key) => match prefix_successor(&encode_key(key)) {
+            Some(successor) => Bound::Excluded(successor),
+            None => Bound::Unbounded,
+        },
+        Bound::Excluded(key) => Bound::Excluded(encode_key(key)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    (lower, upper)
+}
+
+// Big-endian, so entries of the same key are sorted by RID
+fn encode_rid(rid: Rid) -> [u8; RID_SIZE] {
+    let mut bytes = [0u8; RID_SIZE];
+    bytes[0..4].copy_from_slice(&(rid.0 as u32).to_be_bytes());
+    bytes[4..6].copy_from_slice(&rid.1.to_be_bytes());
+    bytes
+}
+
+fn decode_rid(bytes: &[u8]) -> Result<Rid, String> {
+    if bytes.len() != RID_SIZE {
+        return Err("Corrupted index entry: bad RID".to_string());
+    }
+
+    let page_id = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+    let slot_id = u16::from_be_bytes(bytes[4..6].try_into().unwrap());
+    Ok((page_id, slot_id))
+}
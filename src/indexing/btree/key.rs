@@ -0,0 +1,160 @@
+use crate::types::Value;
+
+// Order-preserving encoding of Values
+// memcmp on the encoded bytes gives the same order as comparing the values,
+// so the B+ tree can compare keys without knowing their types.
+//
+// Each value: Tag(1) + Body
+// Null:    0x00 (sorts first)
+// Boolean: 0x01 + 0 / 1
+// Integer: 0x02 + big-endian with the sign bit flipped
+// Real:    0x03 + big-endian IEEE bits (sign flipped for >= 0, all bits flipped for < 0)
+// Text:    0x04 + bytes with 0x00 escaped as 0x00 0xFF, terminated by 0x00 0x00
+// Blob:    0x05 + same as Text
+const TAG_NULL: u8 = 0x00;
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_REAL: u8 = 0x03;
+const TAG_TEXT: u8 = 0x04;
+const TAG_BLOB: u8 = 0x05;
+
+// Encode a (possibly multi-column) key
+pub fn encode_key(values: &[Value]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for value in values {
+        encode_value(&mut buf, value);
+    }
+    buf
+}
+
+// Every key starting with this text has an encoding starting with these bytes
+pub fn encode_text_prefix(prefix: &str) -> Vec<u8> {
+    let mut buf = vec![TAG_TEXT];
+    escape_bytes(&mut buf, prefix.as_bytes());
+    buf
+}
+
+fn encode_value(buf: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => buf.push(TAG_NULL),
+        Value::Boolean(v) => {
+            buf.push(TAG_BOOLEAN);
+            buf.push(*v as u8);
+        }
+        Value::Integer(v) => {
+            buf.push(TAG_INTEGER);
+            buf.extend_from_slice(&((*v as u64) ^ (1 << 63)).to_be_bytes());
+        }
+        Value::Real(v) => {
+            let bits = v.to_bits();
+            let ordered = if bits >> 63 == 0 {
+                bits ^ (1 << 63)
+            } else {
+                !bits
+            };
+            buf.push(TAG_REAL);
+            buf.extend_from_slice(&ordered.to_be_bytes());
+        }
+        Value::Text(v) => {
+            buf.push(TAG_TEXT);
+            escape_bytes(buf, v.as_bytes());
+            buf.extend_from_slice(&[0x00, 0x00]);
+        }
+        Value::Blob(v) => {
+            buf.push(TAG_BLOB);
+            escape_bytes(buf, v);
+            buf.extend_from_slice(&[0x00, 0x00]);
+        }
+    }
+}
+
+// Inverse of `encode_key`: the values of an encoded key
+pub fn decode_key(mut bytes: &[u8]) -> Result<Vec<Value>, String> {
+    let mut values = Vec::new();
+    while let Some((&tag, rest)) = bytes.split_first() {
+        let (value, rest) = match tag {
+            TAG_NULL => (Value::Null, rest),
+            TAG_BOOLEAN => {
+                let (&v, rest) = rest.split_first().ok_or("Truncated key")?;
+                (Value::Boolean(v != 0), rest)
+            }
+            TAG_INTEGER => {
+                let (v, rest) = read_u64(rest)?;
+                (Value::Integer((v ^ (1 << 63)) as i64), rest)
+            }
+            TAG_REAL => {
+                let (ordered, rest) = read_u64(rest)?;
+                let bits = if ordered >> 63 == 1 {
+                    ordered ^ (1 << 63)
+                } else {
+                    !ordered
+                };
+                (Value::Real(f64::from_bits(bits)), rest)
+            }
+            TAG_TEXT => {
+                let (v, rest) = unescape_bytes(rest)?;
+                let text = String::from_utf8(v).map_err(|_| "Invalid UTF-8 in key")?;
+                (Value::Text(text), rest)
+            }
+            TAG_BLOB => {
+                let (v, rest) = unescape_bytes(rest)?;
+                (Value::Blob(v), rest)
+            }
+            tag => return Err(format!("Unknown key tag {:#04x}", tag)),
+        };
+        values.push(value);
+        bytes = rest;
+    }
+    Ok(values)
+}
+
+fn read_u64(bytes: &[u8]) -> Result<(u64, &[u8]), String> {
+    if bytes.len() < 8 {
+        return Err("Truncated key".to_string());
+    }
+    let (v, rest) = bytes.split_at(8);
+    Ok((u64::from_be_bytes(v.try_into().unwrap()), rest))
+}
+
+// Bytes up to the 0x00 0x00 terminator, and what follows it
+fn unescape_bytes(bytes: &[u8]) -> Result<(Vec<u8>, &[u8]), String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (0x00, Some(0x00)) => return Ok((out, &bytes[i + 2..])),
+            (0x00, Some(0xFF)) => {
+                out.push(0x00);
+                i += 2;
+            }
+            (0x00, _) => break,
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Err("Truncated key".to_string())
+}
+
+fn escape_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    for &byte in bytes {
+        buf.push(byte);
+        if byte == 0x00 {
+            buf.push(0xFF);
+        }
+    }
+}
+
+// Smallest byte string greater than every string starting with `prefix`
+// None if there is no such string (prefix is all 0xFF)
+pub fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut successor = prefix.to_vec();
+    while let Some(last) = successor.pop() {
+        if last < 0xFF {
+            successor.push(last + 1);
+            return Some(successor);
+        }
+    }
+    None
+}
@@ -0,0 +1,7 @@
+pub mod index;
+pub mod key;
+pub mod node;
+pub mod table;
+pub mod tree;
+
+pub use table::BTreeTable;
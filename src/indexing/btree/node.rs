@@ -0,0 +1,194 @@
+use crate::backend::pager::{PageData, USABLE_SIZE};
+
+// B+ tree node page layout
+// Byte 0: Node type (1 = leaf, 2 = internal)
+// Bytes 1-2: How many keys
+// Bytes 3-6: Leaf -> ID of the next leaf (0 = last leaf)
+//            Internal -> ID of the leftmost child
+// Bytes 7-..: Entries
+//    Leaf: Key length(2) + Key + Value length(2) + Value
+//    Internal: Key length(2) + Key + Child page ID(4)
+//    (child i+1 holds the keys >= key i)
+const OFFSET_NODE_TYPE: usize = 0;
+const OFFSET_KEY_COUNT: usize = 1;
+const OFFSET_LINK: usize = 3;
+const HEADER_SIZE: usize = 7;
+
+const NODE_LEAF: u8 = 1;
+const NODE_INTERNAL: u8 = 2;
+
+// Largest key + value accepted by the tree
+// Keeps at least 4 entries per page, so a split always produces 2 valid pages
+pub const MAX_ENTRY_SIZE: usize = (USABLE_SIZE - HEADER_SIZE) / 4 - 4;
+
+// A node is decoded into memory, changed, then encoded back into its page
+// Sizes are in bytes: keys and values have variable lengths
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Leaf {
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+        next: u32,
+    },
+    Internal {
+        keys: Vec<Vec<u8>>,
+        children: Vec<u32>,
+    },
+}
+
+impl Node {
+    pub fn empty_leaf() -> Self {
+        Node::Leaf {
+            entries: Vec::new(),
+            next: 0,
+        }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, Node::Leaf { .. })
+    }
+
+    // Bytes needed to store this node
+    pub fn size(&self) -> usize {
+        match self {
+            Node::Leaf { entries, .. } => {
+                HEADER_SIZE
+                    + entries
+                        .iter()
+                        .map(|(k, v)| leaf_entry_size(k, v))
+                        .sum::<usize>()
+            }
+            Node::Internal { keys, .. } => {
+                HEADER_SIZE + keys.iter().map(|k| internal_entry_size(k)).sum::<usize>()
+            }
+        }
+    }
+
+    pub fn fits(&self) -> bool {
+        self.size() <= USABLE_SIZE
+    }
+
+    // Less than a quarter full -> merge with / borrow from a sibling
+    pub fn is_underflow(&self) -> bool {
+        self.size() < USABLE_SIZE / 4
+    }
+
+    pub fn read(data: &PageData) -> Result<Self, String> {
+        let node_type = data[OFFSET_NODE_TYPE];
+        let count = u16::from_ne_bytes(
+            data[OFFSET_KEY_COUNT..OFFSET_KEY_COUNT + 2]
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        let link = read_u32(data, OFFSET_LINK);
+        let mut pos = HEADER_SIZE;
+
+        match node_type {
+            NODE_LEAF => {
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let key = read_bytes(data, &mut pos)?;
+                    let value = read_bytes(data, &mut pos)?;
+                    entries.push((key, value));
+                }
+                Ok(Node::Leaf {
+                    entries,
+                    next: link,
+                })
+            }
+            NODE_INTERNAL => {
+                let mut keys = Vec::with_capacity(count);
+                let mut children = Vec::with_capacity(count + 1);
+                children.push(link);
+                for _ in 0..count {
+                    keys.push(read_bytes(data, &mut pos)?);
+                    if pos + 4 > USABLE_SIZE {
+                        return Err("Corrupted B+ tree node: truncated entry".to_string());
+                    }
+                    children.push(read_u32(data, pos));
+                    pos += 4;
+                }
+                Ok(Node::Internal { keys, children })
+            }
+            _ => Err(format!(
+                "Corrupted B+ tree node: unknown type {}",
+                node_type
+            )),
+        }
+    }
+
+    // Fails, leaving the page untouched, if the node does not fit in it
+    pub fn write(&self, data: &mut PageData) -> Result<(), String> {
+        if !self.fits() {
+            return Err("B+ tree node does not fit in a page".to_string());
+        }
+
+        data[..USABLE_SIZE].fill(0);
+        let mut pos = HEADER_SIZE;
+
+        match self {
+            Node::Leaf { entries, next } => {
+                data[OFFSET_NODE_TYPE] = NODE_LEAF;
+                write_count(data, entries.len());
+                write_u32(data, OFFSET_LINK, *next);
+                for (key, value) in entries {
+                    write_bytes(data, &mut pos, key);
+                    write_bytes(data, &mut pos, value);
+                }
+            }
+            Node::Internal { keys, children } => {
+                data[OFFSET_NODE_TYPE] = NODE_INTERNAL;
+                write_count(data, keys.len());
+                write_u32(data, OFFSET_LINK, children[0]);
+                for (key, child) in keys.iter().zip(&children[1..]) {
+                    write_bytes(data, &mut pos, key);
+                    write_u32(data, pos, *child);
+                    pos += 4;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn leaf_entry_size(key: &[u8], value: &[u8]) -> usize {
+    2 + key.len() + 2 + value.len()
+}
+
+pub fn internal_entry_size(key: &[u8]) -> usize {
+    2 + key.len() + 4
+}
+
+fn read_bytes(data: &PageData, pos: &mut usize) -> Result<Vec<u8>, String> {
+    if *pos + 2 > USABLE_SIZE {
+        return Err("Corrupted B+ tree node: truncated entry".to_string());
+    }
+    let len = u16::from_ne_bytes(data[*pos..*pos + 2].try_into().unwrap()) as usize;
+    *pos += 2;
+
+    if *pos + len > USABLE_SIZE {
+        return Err("Corrupted B+ tree node: truncated entry".to_string());
+    }
+    let bytes = data[*pos..*pos + len].to_vec();
+    *pos += len;
+    Ok(bytes)
+}
+
+fn write_bytes(data: &mut PageData, pos: &mut usize, bytes: &[u8]) {
+    data[*pos..*pos + 2].copy_from_slice(&(bytes.len() as u16).to_ne_bytes());
+    *pos += 2;
+    data[*pos..*pos + bytes.len()].copy_from_slice(bytes);
+    *pos += bytes.len();
+}
+
+fn write_count(data: &mut PageData, count: usize) {
+    data[OFFSET_KEY_COUNT..OFFSET_KEY_COUNT + 2].copy_from_slice(&(count as u16).to_ne_bytes());
+}
+
+fn read_u32(data: &PageData, offset: usize) -> u32 {
+    let bytes = &data[offset..offset + 4];
+    u32::from_ne_bytes(bytes.try_into().unwrap())
+}
+
+fn write_u32(data: &mut PageData, offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+}
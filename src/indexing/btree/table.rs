@@ -0,0 +1,412 @@
+use std::{
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    acid::{
+        log_record::TxnId,
+        transaction::{Dropped, Transaction},
+    },
+    backend::cache::Cache,
+    catalog::schema::Schema,
+    indexing::{
+        btree::{
+            node::MAX_ENTRY_SIZE,
+            tree::{BPlusTree, BTreeIterator},
+        },
+        overflow_page::{free_chain, read_chain, write_chain},
+    },
+    row::Row,
+};
+
+// Leaf value layout
+// Byte 0: Storage (inline / overflow)
+// Inline: the serialized row
+// Overflow: Total length(4) + First overflow page(4), the whole row is in the chain
+const STORAGE_INLINE: u8 = 0;
+const STORAGE_OVERFLOW: u8 = 1;
+
+const ROWID_KEY_SIZE: usize = 8;
+const MAX_INLINE_ROW: usize = MAX_ENTRY_SIZE - ROWID_KEY_SIZE - 1;
+
+// Clustered table (like a SQLite rowid table)
+// Rows live directly in the leaves of a B+ tree keyed by their i64 rowid,
+// so a lookup by rowid is O(log n) instead of a scan of the whole heap.
+//
+// Rows are not locked, the caller keeps concurrent writers of a row apart.
+pub struct BTreeTable {
+    tree: BPlusTree,
+    pub schema: Schema,
+
+    // The overflow chains of replaced rows are freed when it commits, if any:
+    // a rollback brings the rows back together with their chains.
+    txn: Option<Arc<Transaction>>,
+
+    // Picking the next rowid and inserting it must not interleave
+    insert_latch: Mutex<()>,
+}
+
+impl BTreeTable {
+    // Create an empty table, for `writer`
+    pub fn create(cache: Arc<Cache>, schema: Schema, writer: TxnId) -> Result<Self, String> {
+        Ok(Self {
+            tree: BPlusTree::create(cache, writer)?,
+            schema,
+            txn: None,
+            insert_latch: Mutex::new(()),
+        })
+    }
+
+    // `writer`: the transaction page changes are logged under
+    pub fn open(cache: Arc<Cache>, root_page_id: usize, schema: Schema, writer: TxnId) -> Self {
+        Self {
+            tree: BPlusTree::open(cache, root_page_id, writer),
+            schema,
+            txn: None,
+            insert_latch: Mutex::new(()),
+        }
+    }
+
+    // The same table, writing for the transaction
+    pub fn in_transaction(self, txn: Arc<Transaction>) -> Self {
+        Self {
+            tree: BPlusTree::open(self.tree.cache(), self.tree.root_page_id(), txn.id()),
+            txn: Some(txn),
+            ..self
+        }
+    }
+
+    pub fn root_page_id(&self) -> usize {
+        self.tree.root_page_id()
+    }
+
+    // Insert a row with the next rowid (largest rowid + 1)
+    // Returns the rowid
+    pub fn insert(&self, row: Row) -> Result<i64, String> {
+        let _latch = self.insert_latch.lock().unwrap();
+
+        let rowid = match self.tree.last_key()? {
+            Some(key) => decode_rowid(&key)?
+                .checked_add(1)
+                .ok_or("Rowid space exhausted")?,
+            None => 1,
+        };
+
+        self.insert_with_rowid(rowid, row)?;
+        Ok(rowid)
+    }
+
+    // Fails if the rowid is already used
+    pub fn insert_with_rowid(&self, rowid: i64, row: Row) -> Result<(), String> {
+        let key = encode_rowid(rowid);
+        if self.tree.get(&key)?.is_some() {
+            return Err(format!("Rowid {} already exists", rowid));
+        }
+
+        self.start_writing()?;
+        let value = self.encode_row(&row)?;
+        // Inserted meanwhile: a rollback frees the chain written for it
+        if !self.tree.insert(&key, &value)? {
+            return Err(format!("Rowid {} already exists", rowid));
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, rowid: i64) -> Result<Option<Row>, String> {
+        match self.tree.get(&encode_rowid(rowid))? {
+            Some(value) => Ok(Some(self.decode_row(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Returns false if the rowid does not exist
+    pub fn update(&self, rowid: i64, row: Row) -> Result<bool, String> {
+        let key = encode_rowid(rowid);
+        let Some(old_value) = self.tree.get(&key)? else {
+            return Ok(false);
+        };
+
+        self.start_writing()?;
+        let value = self.encode_row(&row)?;
+        self.tree.delete(&key)?;
+        self.tree.insert(&key, &value)?;
+        self.free_row(&old_value)?;
+        Ok(true)
+    }
+
+    // Returns false if the rowid does not exist
+    pub fn delete(&self, rowid: i64) -> Result<bool, String> {
+        let key = encode_rowid(rowid);
+        let Some(value) = self.tree.get(&key)? else {
+            return Ok(false);
+        };
+
+        self.start_writing()?;
+        self.tree.delete(&key)?;
+        self.free_row(&value)?;
+        Ok(true)
+    }
+
+    // Rows whose rowid is within the bounds, in rowid order
+    pub fn scan(&self, lower: Bound<i64>, upper: Bound<i64>) -> Result<RowIterator<'_>, String> {
+        let lower = lower.map(encode_rowid);
+        let upper = upper.map(encode_rowid);
+        let inner = self.tree.range(
+            lower.as_ref().map(|k| k.as_slice()),
+            upper.as_ref().map(|k| k.as_slice()),
+        )?;
+
+        Ok(RowIterator { table: self, inner })
+    }
+
+    pub fn select_all(&self) -> Result<Vec<Row>, String> {
+        self.scan(Bound::Unbounded, Bound::Unbounded)?
+            .map(|entry| entry.map(|(_, row)| row))
+            .collect()
+    }
+
+    fn encode_row(&self, row: &Row) -> Result<Vec<u8>, String> {
+        let bytes = row.serialize(&self.schema)?;
+
+        let mut value = Vec::with_capacity(bytes.len().min(MAX_INLINE_ROW) + 1);
+        if bytes.len() <= MAX_INLINE_ROW {
+            value.push(STORAGE_INLINE);
+            value.extend_from_slice(&bytes);
+        } else {
+            let first_page_id = write_chain(&self.tree.cache(), &bytes, self.writer())?;
+            value.push(STORAGE_OVERFLOW);
+            value.extend_from_slice(&(bytes.len() as u32).to_ne_bytes());
+            value.extend_from_slice(&first_page_id.to_ne_bytes());
+        }
+        Ok(value)
+    }
+
+    fn decode_row(&self, value: &[u8]) -> Result<Row, String> {
+        match value.first() {
+            Some(&STORAGE_INLINE) => Row::deserialize(&value[1..], &self.schema),
+            Some(&STORAGE_OVERFLOW) => {
+                let (length, first_page_id) = parse_overflow(value)?;
+                let mut bytes = Vec::with_capacity(length);
+                read_chain(&self.tree.cache(), first_page_id, &mut bytes)?;
+                if bytes.len() != length {
+                    return Err("Corrupted row: overflow chain has the wrong length".to_string());
+                }
+                Row::deserialize(&bytes, &self.schema)
+            }
+            _ => Err("Corrupted row: unknown storage type".to_string()),
+        }
+    }
+
+    // Release the overflow pages of a stored row
+    fn free_row(&self, value: &[u8]) -> Result<(), String> {
+        if value.first() != Some(&STORAGE_OVERFLOW) {
+            return Ok(());
+        }
+
+        let (_, first_page_id) = parse_overflow(value)?;
+        match &self.txn {
+            Some(txn) => {
+                txn.drop_on_commit(Dropped::Chain(first_page_id));
+                Ok(())
+            }
+            None => free_chain(&self.tree.cache(), first_page_id, self.writer()),
+        }
+    }
+
+    fn writer(&self) -> TxnId {
+        match &self.txn {
+            Some(txn) => txn.id(),
+            None => self.tree.writer(),
+        }
+    }
+
+    fn start_writing(&self) -> Result<(), String> {
+        match &self.txn {
+            Some(txn) => txn.start_writing(),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct RowIterator<'a> {
+    table: &'a BTreeTable,
+    inner: BTreeIterator,
+}
+
+impl Iterator for RowIterator<'_> {
+    type Item = Result<(i64, Row), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.inner.next()?;
+        Some(
+            entry
+                .and_then(|(key, value)| Ok((decode_rowid(&key)?, self.table.decode_row(&value)?))),
+        )
+    }
+}
+
+// Big-endian with the sign bit flipped: byte order = numeric order
+fn encode_rowid(rowid: i64) -> Vec<u8> {
+    ((rowid as u64) ^ (1 << 63)).to_be_bytes().to_vec()
+}
+
+fn decode_rowid(key: &[u8]) -> Result<i64, String> {
+    let bytes: [u8; ROWID_KEY_SIZE] = key
+        .try_into()
+        .map_err(|_| "Corrupted table key: bad rowid".to_string())?;
+    Ok((u64::from_be_bytes(bytes) ^ (1 << 63)) as i64)
+}
+
+fn parse_overflow(value: &[u8]) -> Result<(usize, usize), String> {
+    if value.len() != 9 {
+        return Err("Corrupted row: bad overflow pointer".to_string());
+    }
+    let length = u32::from_ne_bytes(value[1..5].try_into().unwrap()) as usize;
+    let first_page_id = u32::from_ne_bytes(value[5..9].try_into().unwrap()) as usize;
+    Ok((length, first_page_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{
+        acid::{
+            log_record::SYSTEM_TXN, mvcc::Concurrency, transaction::TransactionManager, wal::Wal,
+        },
+        backend::pager::{PAGE_SIZE, Pager},
+        catalog::schema::Column,
+        types::{DataType, Value},
+    };
+
+    // A table in its own database file, with a transaction manager over it
+    struct TempTable {
+        path: String,
+        transactions: TransactionManager,
+        table: BTreeTable,
+    }
+
+    impl TempTable {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir()
+                .join(format!(
+                    "mysqlite-btree-table-{}-{}.db",
+                    process::id(),
+                    name
+                ))
+                .to_string_lossy()
+                .into_owned();
+            remove(&path);
+
+            let wal = Arc::new(Wal::open(&format!("{}-wal", path)).unwrap());
+            let cache = Arc::new(Cache::new(Pager::new(&path).unwrap(), 16, wal.clone()));
+            let transactions = TransactionManager::new(cache.clone(), wal, Concurrency::Locking);
+            let table = BTreeTable::create(cache, schema(), SYSTEM_TXN).unwrap();
+            Self {
+                path,
+                transactions,
+                table,
+            }
+        }
+
+        fn in_transaction(&self, txn: &Arc<Transaction>) -> BTreeTable {
+            BTreeTable::open(
+                self.table.tree.cache(),
+                self.table.root_page_id(),
+                schema(),
+                SYSTEM_TXN,
+            )
+            .in_transaction(txn.clone())
+        }
+    }
+
+    impl Drop for TempTable {
+        fn drop(&mut self) {
+            remove(&self.path);
+        }
+    }
+
+    fn remove(path: &str) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    fn schema() -> Schema {
+        Schema::new(vec![
+            Column::new("id", DataType::Integer),
+            Column::new("name", DataType::Text),
+        ])
+    }
+
+    fn row(id: i64, name: &str) -> Row {
+        Row::new(vec![Value::Integer(id), Value::Text(name.to_string())])
+    }
+
+    #[test]
+    fn rows_are_found_by_rowid() {
+        let temp = TempTable::new("lookup");
+        let table = &temp.table;
+        assert_eq!(table.schema.columns.len(), 2);
+
+        // Enough rows for a tree of several levels
+        for n in 1..=500 {
+            assert_eq!(table.insert(row(n, &format!("row {}", n))).unwrap(), n);
+        }
+        assert_eq!(table.get(250).unwrap(), Some(row(250, "row 250")));
+        assert_eq!(table.get(501).unwrap(), None);
+        assert!(table.insert_with_rowid(7, row(0, "again")).is_err());
+
+        assert!(table.update(7, row(7, "seven")).unwrap());
+        assert!(table.delete(8).unwrap());
+        assert!(!table.delete(8).unwrap());
+
+        let rows = table.select_all().unwrap();
+        assert_eq!(rows.len(), 499);
+        assert_eq!(rows[6], row(7, "seven"));
+        assert_eq!(rows[7], row(9, "row 9"));
+
+        let ids: Vec<i64> = table
+            .scan(Bound::Included(-5), Bound::Excluded(4))
+            .unwrap()
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn big_rows_go_to_overflow_pages() {
+        let temp = TempTable::new("overflow");
+        let table = &temp.table;
+        let big = "x".repeat(3 * PAGE_SIZE);
+
+        let rowid = table.insert(row(1, &big)).unwrap();
+        assert_eq!(table.get(rowid).unwrap(), Some(row(1, &big)));
+
+        let bigger = "y".repeat(5 * PAGE_SIZE);
+        assert!(table.update(rowid, row(1, &bigger)).unwrap());
+        assert_eq!(table.get(rowid).unwrap(), Some(row(1, &bigger)));
+    }
+
+    // The replaced row comes back with its overflow chain
+    #[test]
+    fn rollback_restores_replaced_rows() {
+        let temp = TempTable::new("rollback");
+        let big = "x".repeat(3 * PAGE_SIZE);
+        temp.table.insert(row(1, &big)).unwrap();
+        temp.table.insert(row(2, "small")).unwrap();
+
+        let txn = temp.transactions.begin().unwrap();
+        let table = temp.in_transaction(&txn);
+        assert!(table.update(1, row(1, "short")).unwrap());
+        assert_eq!(table.insert(row(3, &big)).unwrap(), 3);
+        assert!(table.delete(2).unwrap());
+        temp.transactions.abort(&txn).unwrap();
+
+        assert_eq!(
+            temp.table.select_all().unwrap(),
+            vec![row(1, &big), row(2, "small")]
+        );
+    }
+}
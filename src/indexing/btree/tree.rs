@@ -0,0 +1,795 @@
+use std::{
+    ops::Bound,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    acid::log_record::{Lsn, OperationEnd, TxnId, Undo},
+    backend::cache::Cache,
+    indexing::btree::node::{MAX_ENTRY_SIZE, Node, internal_entry_size, leaf_entry_size},
+};
+
+// The (key, value) entries of a leaf, in key order
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+// Result of inserting into a subtree
+enum InsertResult {
+    Done,
+    Duplicate,
+    // The node was split: (separator key, new right page)
+    Split(Vec<u8>, usize),
+}
+
+// Disk-backed B+ tree
+// Keys and values are byte strings, keys are compared with memcmp
+// (see `key.rs` for an order-preserving encoding of Values)
+//
+// The root page never moves:
+// - Root split: the root content is copied to a new page, the root becomes its parent
+// - Root with a single child: the child is copied back into the root
+// So whoever stores the root page id (the catalog) never has to update it.
+pub struct BPlusTree {
+    cache: Arc<Cache>,
+    root_page_id: usize,
+
+    // Readers share, writers (insert / delete) are exclusive
+    // Shared by every handle on the same root page, see `Cache::tree_latch`
+    latch: Arc<RwLock<()>>,
+
+    // Page changes made through this handle are logged under it
+    txn: TxnId,
+}
+
+impl BPlusTree {
+    // Create an empty tree (a single empty leaf)
+    pub fn create(cache: Arc<Cache>, txn: TxnId) -> Result<Self, String> {
+        let root_page_id = cache
+            .allocate_page(txn)
+            .map_err(|e| format!("Failed to allocate B+ tree root: {}", e))?;
+
+        let tree = Self::open(cache, root_page_id, txn);
+        tree.write_node(root_page_id, &Node::empty_leaf())?;
+        Ok(tree)
+    }
+
+    pub fn open(cache: Arc<Cache>, root_page_id: usize, txn: TxnId) -> Self {
+        Self {
+            latch: cache.tree_latch(root_page_id),
+            cache,
+            root_page_id,
+            txn,
+        }
+    }
+
+    pub fn root_page_id(&self) -> usize {
+        self.root_page_id
+    }
+
+    pub fn cache(&self) -> Arc<Cache> {
+        self.cache.clone()
+    }
+
+    pub fn writer(&self) -> TxnId {
+        self.txn
+    }
+
+    // Point lookup
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let _latch = self.latch.read().unwrap();
+
+        let (_, node) = self.find_leaf(key)?;
+        let Node::Leaf { entries, .. } = node else {
+            unreachable!()
+        };
+
+        Ok(entries
+            .binary_search_by(|(k, _)| k.as_slice().cmp(key))
+            .ok()
+            .map(|i| entries[i].1.clone()))
+    }
+
+    // Returns false (and changes nothing) if the key already exists
+    pub fn insert(&self, key: &[u8], value: &[u8]) -> Result<bool, String> {
+        self.insert_ending(key, value, self.cache.start_operation(self.txn))
+    }
+
+    // Returns false if the key does not exist
+    pub fn delete(&self, key: &[u8]) -> Result<bool, String> {
+        self.delete_ending(key, self.cache.start_operation(self.txn))
+    }
+
+    // Undo of an insert (see `Undo::TreeDelete`), rollback goes on at `undo_next`
+    pub fn undo_insert(&self, key: &[u8], undo_next: Lsn) -> Result<(), String> {
+        self.delete_ending(key, OperationEnd::Compensating(undo_next))
+            .map(|_| ())
+    }
+
+    // Undo of a delete (see `Undo::TreeInsert`), rollback goes on at `undo_next`
+    pub fn undo_delete(&self, key: &[u8], value: &[u8], undo_next: Lsn) -> Result<(), String> {
+        self.insert_ending(key, value, OperationEnd::Compensating(undo_next))
+            .map(|_| ())
+    }
+
+    // Each insert and delete is one operation, logged as such before the latch is released:
+    // rollback takes back the entry, not the pages, other writers may have changed them since.
+    fn insert_ending(&self, key: &[u8], value: &[u8], end: OperationEnd) -> Result<bool, String> {
+        if key.len() + value.len() > MAX_ENTRY_SIZE {
+            return Err(format!(
+                "B+ tree entry too large: {} bytes (max {})",
+                key.len() + value.len(),
+                MAX_ENTRY_SIZE
+            ));
+        }
+
+        let _latch = self.latch.write().unwrap();
+
+        let inserted = match self.insert_into(self.root_page_id, key, value)? {
+            InsertResult::Done => true,
+            InsertResult::Duplicate => false,
+            InsertResult::Split(separator, right_page_id) => {
+                // Move the old root into a new page, the root becomes their parent
+                let root = self.read_node(self.root_page_id)?;
+                let left_page_id = self.allocate()?;
+                self.write_node(left_page_id, &root)?;
+
+                let new_root = Node::Internal {
+                    keys: vec![separator],
+                    children: vec![left_page_id as u32, right_page_id as u32],
+                };
+                self.write_node(self.root_page_id, &new_root)?;
+                true
+            }
+        };
+
+        let undo = match inserted {
+            true => Undo::TreeDelete {
+                root: self.root_page_id as u32,
+                key: key.to_vec(),
+            },
+            false => Undo::Nothing,
+        };
+        self.cache.end_operation(self.txn, end, undo);
+        Ok(inserted)
+    }
+
+    // Pages emptied by merges are freed once the operation is logged,
+    // a rollback of the delete does not bring them back
+    fn delete_ending(&self, key: &[u8], end: OperationEnd) -> Result<bool, String> {
+        let _latch = self.latch.write().unwrap();
+
+        let mut freed = Vec::new();
+        let Some(value) = self.delete_from(self.root_page_id, key, &mut freed)? else {
+            self.cache.end_operation(self.txn, end, Undo::Nothing);
+            return Ok(false);
+        };
+
+        // Shrink the tree: an internal root with a single child takes the child's place
+        let root = self.read_node(self.root_page_id)?;
+        if let Node::Internal { keys, children } = &root
+            && keys.is_empty()
+        {
+            let child_page_id = children[0] as usize;
+            let child = self.read_node(child_page_id)?;
+            self.write_node(self.root_page_id, &child)?;
+            freed.push(child_page_id);
+        }
+
+        let undo = Undo::TreeInsert {
+            root: self.root_page_id as u32,
+            key: key.to_vec(),
+            value,
+        };
+        self.cache.end_operation(self.txn, end, undo);
+        for page_id in freed {
+            self.deallocate(page_id)?;
+        }
+        Ok(true)
+    }
+
+    // Largest key in the tree, None if the tree is empty
+    pub fn last_key(&self) -> Result<Option<Vec<u8>>, String> {
+        let _latch = self.latch.read().unwrap();
+
+        let mut page_id = self.root_page_id;
+        loop {
+            match self.read_node(page_id)? {
+                Node::Leaf { entries, .. } => return Ok(entries.last().map(|(k, _)| k.clone())),
+                Node::Internal { children, .. } => page_id = *children.last().unwrap() as usize,
+            }
+        }
+    }
+
+    // Iterate over the entries within the bounds, in key order
+    pub fn range(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Result<BTreeIterator, String> {
+        let (entries, position) = {
+            let _latch = self.latch.read().unwrap();
+            seek(&self.cache, self.root_page_id, lower)?
+        };
+
+        Ok(BTreeIterator {
+            cache: self.cache.clone(),
+            latch: self.latch.clone(),
+            root_page_id: self.root_page_id,
+            entries,
+            position,
+            upper: match upper {
+                Bound::Included(key) => Bound::Included(key.to_vec()),
+                Bound::Excluded(key) => Bound::Excluded(key.to_vec()),
+                Bound::Unbounded => Bound::Unbounded,
+            },
+        })
+    }
+
+    // Give every page of the tree back to the allocator
+    // The tree must not be used afterwards
+    pub fn destroy(&self) -> Result<(), String> {
+        let _latch = self.latch.write().unwrap();
+
+        let mut pending = vec![self.root_page_id];
+        while let Some(page_id) = pending.pop() {
+            if let Node::Internal { children, .. } = self.read_node(page_id)? {
+                pending.extend(children.iter().map(|&c| c as usize));
+            }
+            self.deallocate(page_id)?;
+        }
+        Ok(())
+    }
+
+    fn insert_into(
+        &self,
+        page_id: usize,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<InsertResult, String> {
+        let mut node = self.read_node(page_id)?;
+
+        match &mut node {
+            Node::Leaf { entries, .. } => {
+                match entries.binary_search_by(|(k, _)| k.as_slice().cmp(key)) {
+                    Ok(_) => return Ok(InsertResult::Duplicate),
+                    Err(i) => entries.insert(i, (key.to_vec(), value.to_vec())),
+                }
+            }
+            Node::Internal { keys, children } => {
+                let i = child_index(keys, key);
+                match self.insert_into(children[i] as usize, key, value)? {
+                    InsertResult::Split(separator, right_page_id) => {
+                        keys.insert(i, separator);
+                        children.insert(i + 1, right_page_id as u32);
+                    }
+                    other => return Ok(other),
+                }
+            }
+        }
+
+        if node.fits() {
+            self.write_node(page_id, &node)?;
+            return Ok(InsertResult::Done);
+        }
+
+        // Overflow: keep the left half here, move the right half to a new page
+        let right_page_id = self.allocate()?;
+        let (left, separator, right) = split_node(node, right_page_id as u32);
+        self.write_node(page_id, &left)?;
+        self.write_node(right_page_id, &right)?;
+
+        Ok(InsertResult::Split(separator, right_page_id))
+    }
+
+    // Returns the value of the key, None if it was not found
+    // Pages merged away are added to `freed`
+    fn delete_from(
+        &self,
+        page_id: usize,
+        key: &[u8],
+        freed: &mut Vec<usize>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let mut node = self.read_node(page_id)?;
+
+        let value = match &mut node {
+            Node::Leaf { entries, .. } => {
+                match entries.binary_search_by(|(k, _)| k.as_slice().cmp(key)) {
+                    Ok(i) => entries.remove(i).1,
+                    Err(_) => return Ok(None),
+                }
+            }
+            Node::Internal { keys, children } => {
+                let i = child_index(keys, key);
+                let Some(value) = self.delete_from(children[i] as usize, key, freed)? else {
+                    return Ok(None);
+                };
+                self.rebalance_child(keys, children, i, freed)?;
+                value
+            }
+        };
+
+        self.write_node(page_id, &node)?;
+        Ok(Some(value))
+    }
+
+    // Fix an underflowing child by merging it with a sibling,
+    // or redistributing the entries of both when they do not fit in one page
+    fn rebalance_child(
+        &self,
+        keys: &mut Vec<Vec<u8>>,
+        children: &mut Vec<u32>,
+        i: usize,
+        freed: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        if children.len() < 2 {
+            return Ok(());
+        }
+
+        let child = self.read_node(children[i] as usize)?;
+        if !child.is_underflow() {
+            return Ok(());
+        }
+
+        // Work on the pair (left, right) around separator `left_index`
+        let left_index = if i > 0 { i - 1 } else { i };
+        let left_page_id = children[left_index] as usize;
+        let right_page_id = children[left_index + 1] as usize;
+        let (left, right) = if i > 0 {
+            (self.read_node(left_page_id)?, child)
+        } else {
+            (child, self.read_node(right_page_id)?)
+        };
+
+        let merged = merge_nodes(left, keys[left_index].clone(), right);
+
+        if merged.fits() {
+            // Merge: right page goes away, with its separator
+            self.write_node(left_page_id, &merged)?;
+            freed.push(right_page_id);
+            keys.remove(left_index);
+            children.remove(left_index + 1);
+        } else {
+            // Redistribute: split the combined node again, evenly
+            let (left, separator, right) = split_node(merged, right_page_id as u32);
+            self.write_node(left_page_id, &left)?;
+            self.write_node(right_page_id, &right)?;
+            keys[left_index] = separator;
+        }
+
+        Ok(())
+    }
+
+    // Walk down to the leaf that may hold the key
+    fn find_leaf(&self, key: &[u8]) -> Result<(usize, Node), String> {
+        let mut page_id = self.root_page_id;
+        loop {
+            let node = self.read_node(page_id)?;
+            match &node {
+                Node::Leaf { .. } => return Ok((page_id, node)),
+                Node::Internal { keys, children } => {
+                    page_id = children[child_index(keys, key)] as usize;
+                }
+            }
+        }
+    }
+
+    fn read_node(&self, page_id: usize) -> Result<Node, String> {
+        read_node(&self.cache, page_id)
+    }
+
+    fn write_node(&self, page_id: usize, node: &Node) -> Result<(), String> {
+        let frame_arc = self
+            .cache
+            .fetch_page(page_id)
+            .map_err(|_| "Failed to fetch page")?;
+        let written = {
+            let mut frame = frame_arc.write().unwrap();
+            match frame.page {
+                Some(ref mut page) => node.write(&mut page.data),
+                None => Ok(()),
+            }
+        };
+        self.cache
+            .unpin_page(page_id, written.is_ok().then_some(self.txn));
+        written
+    }
+
+    fn allocate(&self) -> Result<usize, String> {
+        self.cache
+            .allocate_page(self.txn)
+            .map_err(|e| format!("Failed to allocate B+ tree page: {}", e))
+    }
+
+    fn deallocate(&self, page_id: usize) -> Result<(), String> {
+        self.cache
+            .deallocate_page(page_id, self.txn)
+            .map_err(|e| format!("Failed to free B+ tree page: {}", e))
+    }
+}
+
+// Scans the tree leaf by leaf
+// Each leaf is copied out when reached, no page stays pinned between calls.
+// The tree latch is only held while finding the next leaf: it is found again
+// from the root with the last key returned, so a leaf freed or merged
+// by a delete in the meantime is never read.
+pub struct BTreeIterator {
+    cache: Arc<Cache>,
+    latch: Arc<RwLock<()>>,
+    root_page_id: usize,
+    // Empty once the scan is over
+    entries: Entries,
+    position: usize,
+    upper: Bound<Vec<u8>>,
+}
+
+impl Iterator for BTreeIterator {
+    type Item = Result<(Vec<u8>, Vec<u8>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.entries.len() {
+            let (last_key, _) = self.entries.last()?;
+            let found = {
+                let _latch = self.latch.read().unwrap();
+                seek(&self.cache, self.root_page_id, Bound::Excluded(last_key))
+            };
+            match found {
+                Ok((entries, position)) if position < entries.len() => {
+                    self.entries = entries;
+                    self.position = position;
+                }
+                Ok(_) => {
+                    self.entries.clear();
+                    return None;
+                }
+                Err(e) => {
+                    self.entries.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        let (key, value) = &self.entries[self.position];
+        let in_range = match &self.upper {
+            Bound::Included(upper) => key <= upper,
+            Bound::Excluded(upper) => key < upper,
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            self.entries.clear();
+            return None;
+        }
+
+        self.position += 1;
+        Some(Ok((key.clone(), value.clone())))
+    }
+}
+
+// The first leaf with an entry within the lower bound, and the position of that entry
+// No entries if there is none. The caller holds the tree latch.
+fn seek(
+    cache: &Cache,
+    root_page_id: usize,
+    lower: Bound<&[u8]>,
+) -> Result<(Entries, usize), String> {
+    // Down to the leaf that may hold the bound
+    let mut page_id = root_page_id;
+    let (mut entries, mut next) = loop {
+        match read_node(cache, page_id)? {
+            Node::Leaf { entries, next } => break (entries, next),
+            Node::Internal { keys, children } => {
+                let i = match lower {
+                    Bound::Included(key) | Bound::Excluded(key) => child_index(&keys, key),
+                    Bound::Unbounded => 0,
+                };
+                page_id = children[i] as usize;
+            }
+        }
+    };
+
+    // Everything in that leaf may be below the bound: go on with its siblings
+    loop {
+        let position = match lower {
+            Bound::Included(key) => entries.partition_point(|(k, _)| k.as_slice() < key),
+            Bound::Excluded(key) => entries.partition_point(|(k, _)| k.as_slice() <= key),
+            Bound::Unbounded => 0,
+        };
+        if position < entries.len() {
+            return Ok((entries, position));
+        }
+        if next == 0 {
+            return Ok((Vec::new(), 0));
+        }
+
+        match read_node(cache, next as usize)? {
+            Node::Leaf {
+                entries: next_entries,
+                next: next_leaf,
+            } => {
+                entries = next_entries;
+                next = next_leaf;
+            }
+            Node::Internal { .. } => {
+                return Err("Corrupted B+ tree: leaf links to an internal node".into());
+            }
+        }
+    }
+}
+
+fn read_node(cache: &Cache, page_id: usize) -> Result<Node, String> {
+    let frame_arc = cache
+        .fetch_page(page_id)
+        .map_err(|_| "Failed to fetch page")?;
+    let frame = frame_arc.read().unwrap();
+
+    let node = match frame.page {
+        Some(ref page) => Node::read(&page.data),
+        None => Err("Frame empty".to_string()),
+    };
+
+    drop(frame);
+    cache.unpin_page(page_id, None);
+    node
+}
+
+// Which child of an internal node covers the key
+fn child_index(keys: &[Vec<u8>], key: &[u8]) -> usize {
+    keys.partition_point(|k| k.as_slice() <= key)
+}
+
+// Cut a node in two halves of about the same size in bytes
+// Returns (left, separator, right). The left node keeps its page,
+// `right_page_id` is where the right node will be written.
+fn split_node(node: Node, right_page_id: u32) -> (Node, Vec<u8>, Node) {
+    match node {
+        Node::Leaf { mut entries, next } => {
+            let sizes: Vec<usize> = entries.iter().map(|(k, v)| leaf_entry_size(k, v)).collect();
+            let mid = split_point(&sizes);
+
+            let right_entries = entries.split_off(mid);
+            let separator = right_entries[0].0.clone();
+            (
+                Node::Leaf {
+                    entries,
+                    next: right_page_id,
+                },
+                separator,
+                Node::Leaf {
+                    entries: right_entries,
+                    next,
+                },
+            )
+        }
+        Node::Internal {
+            mut keys,
+            mut children,
+        } => {
+            let sizes: Vec<usize> = keys.iter().map(|k| internal_entry_size(k)).collect();
+            let mid = split_point(&sizes).min(keys.len() - 1);
+
+            // The middle key moves up, it is not kept in either half
+            let mut right_keys = keys.split_off(mid);
+            let separator = right_keys.remove(0);
+            let right_children = children.split_off(mid + 1);
+            (
+                Node::Internal { keys, children },
+                separator,
+                Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                },
+            )
+        }
+    }
+}
+
+// First index of the second half, both halves are never empty
+fn split_point(sizes: &[usize]) -> usize {
+    let total: usize = sizes.iter().sum();
+    let mut acc = 0;
+    for (i, size) in sizes.iter().enumerate() {
+        acc += size;
+        if acc * 2 >= total {
+            return (i + 1).clamp(1, sizes.len() - 1);
+        }
+    }
+    sizes.len() - 1
+}
+
+// Concatenate two siblings. For internal nodes the separator comes back down.
+fn merge_nodes(left: Node, separator: Vec<u8>, right: Node) -> Node {
+    match (left, right) {
+        (
+            Node::Leaf { mut entries, .. },
+            Node::Leaf {
+                entries: right_entries,
+                next,
+            },
+        ) => {
+            entries.extend(right_entries);
+            Node::Leaf { entries, next }
+        }
+        (
+            Node::Internal {
+                mut keys,
+                mut children,
+            },
+            Node::Internal {
+                keys: right_keys,
+                children: right_children,
+            },
+        ) => {
+            keys.push(separator);
+            keys.extend(right_keys);
+            children.extend(right_children);
+            Node::Internal { keys, children }
+        }
+        _ => unreachable!("B+ tree siblings are always at the same level"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::{
+        acid::{log_record::SYSTEM_TXN, recovery::rollback, wal::Wal},
+        backend::pager::Pager,
+    };
+
+    // Keys long enough for a tree of 3 levels with a few hundred entries
+    const KEY_SIZE: usize = 200;
+    const COUNT: u32 = 600;
+
+    struct TempTree {
+        path: String,
+        wal: Arc<Wal>,
+        tree: BPlusTree,
+    }
+
+    impl TempTree {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir()
+                .join(format!("mysqlite-tree-{}-{}.db", process::id(), name))
+                .to_string_lossy()
+                .into_owned();
+            remove(&path);
+
+            let wal = Arc::new(Wal::open(&format!("{}-wal", path)).unwrap());
+            let cache = Arc::new(Cache::new(Pager::new(&path).unwrap(), 16, wal.clone()));
+            let tree = BPlusTree::create(cache, SYSTEM_TXN).unwrap();
+            for n in 0..COUNT {
+                assert!(tree.insert(&key(n), &n.to_be_bytes()).unwrap());
+            }
+            Self { path, wal, tree }
+        }
+
+        // A handle logging under a transaction of its own
+        fn writer(&self) -> (TxnId, BPlusTree) {
+            let txn = self.wal.reserve_txn();
+            self.wal.begin(txn);
+            (
+                txn,
+                BPlusTree::open(self.tree.cache(), self.tree.root_page_id, txn),
+            )
+        }
+    }
+
+    impl Drop for TempTree {
+        fn drop(&mut self) {
+            remove(&self.path);
+        }
+    }
+
+    fn remove(path: &str) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    fn key(n: u32) -> Vec<u8> {
+        let mut key = n.to_be_bytes().to_vec();
+        key.resize(KEY_SIZE, b'k');
+        key
+    }
+
+    fn numbers(iter: impl Iterator<Item = Result<(Vec<u8>, Vec<u8>), String>>) -> Vec<u32> {
+        iter.map(|entry| u32::from_be_bytes(entry.unwrap().1.try_into().unwrap()))
+            .collect()
+    }
+
+    fn height(tree: &BPlusTree) -> usize {
+        let mut page_id = tree.root_page_id;
+        let mut height = 1;
+        while let Node::Internal { children, .. } = tree.read_node(page_id).unwrap() {
+            page_id = children[0] as usize;
+            height += 1;
+        }
+        height
+    }
+
+    #[test]
+    fn deletes_down_to_an_empty_tree() {
+        let temp = TempTree::new("delete-all");
+        let tree = &temp.tree;
+        assert_eq!(height(tree), 3);
+
+        // Spread over the whole key range, so every kind of rebalance happens
+        let order: Vec<u32> = (0..COUNT).map(|i| (i * 257) % COUNT).collect();
+        let mut left: Vec<u32> = (0..COUNT).collect();
+        for (step, &n) in order.iter().enumerate() {
+            assert!(tree.delete(&key(n)).unwrap());
+            assert!(!tree.delete(&key(n)).unwrap());
+            left.retain(|&m| m != n);
+
+            if step % 7 == 0 || left.len() < 40 {
+                let all = tree.range(Bound::Unbounded, Bound::Unbounded).unwrap();
+                assert_eq!(numbers(all), left);
+
+                let (low, high) = (COUNT / 4, COUNT / 2);
+                let some = tree
+                    .range(Bound::Included(&key(low)), Bound::Excluded(&key(high)))
+                    .unwrap();
+                let expected: Vec<u32> = left
+                    .iter()
+                    .copied()
+                    .filter(|&m| (low..high).contains(&m))
+                    .collect();
+                assert_eq!(numbers(some), expected);
+            }
+        }
+
+        assert_eq!(height(tree), 1);
+        assert!(tree.get(&key(0)).unwrap().is_none());
+        let all = tree.range(Bound::Unbounded, Bound::Unbounded).unwrap();
+        assert!(numbers(all).is_empty());
+    }
+
+    #[test]
+    fn iterator_survives_deletes_between_calls() {
+        let temp = TempTree::new("delete-while-iterating");
+        let tree = &temp.tree;
+
+        let mut iter = tree.range(Bound::Unbounded, Bound::Unbounded).unwrap();
+        let mut seen = numbers(iter.by_ref().take(10));
+
+        // Free most leaves ahead of the iterator, merging what is left
+        for n in (100..COUNT).filter(|n| n % 5 != 0) {
+            assert!(tree.delete(&key(n)).unwrap());
+        }
+        seen.extend(numbers(iter));
+
+        let expected: Vec<u32> = (0..100)
+            .chain((100..COUNT).filter(|n| n % 5 == 0))
+            .collect();
+        assert_eq!(seen, expected);
+    }
+
+    // Both writers split and merge the same leaves, one of them rolls back
+    #[test]
+    fn rollback_keeps_entries_of_other_writers() {
+        let temp = TempTree::new("interleaved-rollback");
+        let (txn, rolled_back) = temp.writer();
+        let (_, other) = temp.writer();
+
+        let mut expected: Vec<u32> = (0..COUNT).collect();
+        for n in 0..COUNT / 2 {
+            let (mine, theirs) = (n * 2, n * 2 + 1);
+            assert!(rolled_back.delete(&key(mine)).unwrap());
+            assert!(
+                rolled_back
+                    .insert(&key(COUNT + mine), &(COUNT + mine).to_be_bytes())
+                    .unwrap()
+            );
+            if n % 3 == 0 {
+                assert!(other.delete(&key(theirs)).unwrap());
+                expected.retain(|&m| m != theirs);
+            } else {
+                let m = COUNT + theirs;
+                assert!(other.insert(&key(m), &m.to_be_bytes()).unwrap());
+                expected.push(m);
+            }
+        }
+
+        temp.wal.abort(txn);
+        rollback(&temp.tree.cache(), &temp.wal, txn).unwrap();
+
+        expected.sort();
+        let all = temp.tree.range(Bound::Unbounded, Bound::Unbounded).unwrap();
+        assert_eq!(numbers(all), expected);
+    }
+}
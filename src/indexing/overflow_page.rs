@@ -0,0 +1,147 @@
+use crate::{
+    acid::log_record::TxnId,
+    backend::{
+        cache::Cache,
+        pager::{PageData, USABLE_SIZE},
+    },
+};
+
+// Overflow page layout
+// Bytes 0-3: ID of the next overflow page (0 = last page of the chain)
+// Bytes 4-7: How many data bytes this page holds
+// Bytes 8-..: Data
+const OFFSET_NEXT_PAGE_ID: usize = 0;
+const OFFSET_DATA_LENGTH: usize = 4;
+const HEADER_SIZE: usize = 8;
+
+pub const OVERFLOW_CAPACITY: usize = USABLE_SIZE - HEADER_SIZE;
+
+// Holds the tail of a tuple too big for a TablePage
+// A big tuple = head (in its slot) + a linked list of overflow pages
+pub struct OverflowPage<'a> {
+    data: &'a mut PageData,
+}
+
+impl<'a> OverflowPage<'a> {
+    pub fn new(data: &'a mut PageData) -> Self {
+        Self { data }
+    }
+
+    // Fill the page with one chunk of the tuple
+    pub fn init(&mut self, next_page_id: u32, chunk: &[u8]) {
+        assert!(chunk.len() <= OVERFLOW_CAPACITY);
+
+        self.data[..USABLE_SIZE].fill(0);
+        self.write_u32(OFFSET_NEXT_PAGE_ID, next_page_id);
+        self.write_u32(OFFSET_DATA_LENGTH, chunk.len() as u32);
+        self.data[HEADER_SIZE..HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+    }
+
+    pub fn read_next_page_id(data: &PageData) -> Option<u32> {
+        let id = read_u32(data, OFFSET_NEXT_PAGE_ID);
+        if id == 0 { None } else { Some(id) }
+    }
+
+    pub fn read_chunk(data: &PageData) -> &[u8] {
+        let length = (read_u32(data, OFFSET_DATA_LENGTH) as usize).min(OVERFLOW_CAPACITY);
+        &data[HEADER_SIZE..HEADER_SIZE + length]
+    }
+
+    fn write_u32(&mut self, offset: usize, value: u32) {
+        self.data[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+    }
+}
+
+// Write bytes into a new chain of overflow pages, for `txn`
+// Returns the ID of the first page (0 if there was nothing to write)
+pub fn write_chain(cache: &Cache, bytes: &[u8], txn: TxnId) -> Result<u32, String> {
+    // Write the chain backwards, so each page already knows its successor
+    let mut next_page_id = 0;
+    for chunk in bytes.chunks(OVERFLOW_CAPACITY).rev() {
+        let page_id = allocate(cache, txn)?;
+        init_page(cache, page_id, next_page_id, chunk, txn)?;
+        next_page_id = page_id as u32;
+    }
+
+    Ok(next_page_id)
+}
+
+fn allocate(cache: &Cache, txn: TxnId) -> Result<usize, String> {
+    cache
+        .allocate_page(txn)
+        .map_err(|e| format!("Failed to allocate overflow page: {}", e))
+}
+
+fn init_page(
+    cache: &Cache,
+    page_id: usize,
+    next_page_id: u32,
+    chunk: &[u8],
+    txn: TxnId,
+) -> Result<(), String> {
+    let frame_arc = cache
+        .fetch_page(page_id)
+        .map_err(|_| "Failed to fetch page")?;
+    {
+        let mut frame = frame_arc.write().unwrap();
+        if let Some(ref mut page) = frame.page {
+            OverflowPage::new(&mut page.data).init(next_page_id, chunk);
+        }
+    }
+    cache.unpin_page(page_id, Some(txn));
+    Ok(())
+}
+
+// Append the content of a whole chain to `out`
+pub fn read_chain(cache: &Cache, first_page_id: usize, out: &mut Vec<u8>) -> Result<(), String> {
+    let mut next_page_id = Some(first_page_id);
+
+    while let Some(page_id) = next_page_id {
+        let frame_arc = cache
+            .fetch_page(page_id)
+            .map_err(|_| "Failed to fetch page")?;
+        let frame = frame_arc.read().unwrap();
+
+        next_page_id = None;
+        if let Some(ref page) = frame.page {
+            out.extend_from_slice(OverflowPage::read_chunk(&page.data));
+            next_page_id = OverflowPage::read_next_page_id(&page.data).map(|id| id as usize);
+        }
+
+        drop(frame);
+        cache.unpin_page(page_id, None);
+    }
+
+    Ok(())
+}
+
+// Give every page of a chain back to the allocator, for `txn`
+pub fn free_chain(cache: &Cache, first_page_id: usize, txn: TxnId) -> Result<(), String> {
+    let mut next_page_id = Some(first_page_id);
+
+    while let Some(page_id) = next_page_id {
+        let frame_arc = cache
+            .fetch_page(page_id)
+            .map_err(|_| "Failed to fetch page")?;
+        let frame = frame_arc.read().unwrap();
+        next_page_id = frame
+            .page
+            .as_ref()
+            .and_then(|page| OverflowPage::read_next_page_id(&page.data))
+            .map(|id| id as usize);
+
+        drop(frame);
+        cache.unpin_page(page_id, None);
+
+        cache
+            .deallocate_page(page_id, txn)
+            .map_err(|e| format!("Failed to free overflow page: {}", e))?;
+    }
+
+    Ok(())
+}
+
+fn read_u32(data: &PageData, offset: usize) -> u32 {
+    let bytes = &data[offset..offset + 4];
+    u32::from_ne_bytes(bytes.try_into().unwrap())
+}
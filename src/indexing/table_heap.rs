@@ -1,28 +1,133 @@
 use std::sync::Arc;
 
 use crate::{
-    backend::cache::Cache,
-    indexing::table_page::{HEADER_SIZE, TablePage},
+    acid::{
+        lock_manager::LockMode,
+        log_record::{Lsn, OperationEnd, SYSTEM_TXN, TxnId, Undo},
+        mvcc::Visibility,
+        transaction::{Transaction, WriteKind},
+    },
+    backend::{cache::Cache, pager::PAGE_SIZE},
+    indexing::{
+        btree::index::Rid,
+        overflow_page::{free_chain, read_chain, write_chain},
+        table_page::{Payload, Slot, TablePage, Version},
+    },
 };
 
+// Tuples bigger than this spill into overflow pages
+pub const MAX_INLINE_SIZE: usize = PAGE_SIZE / 4;
+
+// A spilled tuple keeps a stub in its slot:
+// Total length(4) + First overflow page(4) + the first bytes of the tuple
+const OVERFLOW_STUB_HEADER: usize = 8;
+const OVERFLOW_HEAD_SIZE: usize = MAX_INLINE_SIZE - OVERFLOW_STUB_HEADER;
+
+// What garbage collection removed from a tuple, and what is left
+// (the tuples of its versions, for the caller to clean up index entries)
+#[derive(Debug, Default)]
+pub struct Pruned {
+    pub removed: Vec<Vec<u8>>,
+    pub kept: Vec<Vec<u8>>,
+}
+
 // Table Heap
 // DO NOT hold data itself. Just hold the ID of the first page
+//
+// A RID always holds the latest version of its tuple.
+// Under MVCC, updating moves the latest version to a slot of its own (see `Slot::Moved`),
+// so older snapshots can still read it.
+// Deleting only stamps the latest version, the garbage collector removes it (see `prune`).
+//
+// Every change is an operation in the log, ending with the page change other transactions
+// can see (see `LogBody::Operation`): rollback undoes the tuple, not the bytes of its page.
 pub struct TableHeap {
     first_page_id: usize,
     pub cache: Arc<Cache>,
+
+    // Changes are added to its write set and tuples are locked for it, if any
+    // Under MVCC its snapshot decides which versions are read.
+    txn: Option<Arc<Transaction>>,
+
+    // Page changes are logged under it
+    writer: TxnId,
 }
 
 impl TableHeap {
-    pub fn new(cache: Arc<Cache>, first_page_id: usize) -> Self {
+    // `writer`: the transaction page changes are logged under
+    pub fn new(cache: Arc<Cache>, first_page_id: usize, writer: TxnId) -> Self {
         Self {
             first_page_id,
             cache,
+            txn: None,
+            writer,
         }
     }
 
+    // The same heap, reading and writing for the transaction
+    pub fn in_transaction(self, txn: Arc<Transaction>) -> Self {
+        Self {
+            writer: txn.id(),
+            txn: Some(txn),
+            ..self
+        }
+    }
+
+    // Create an empty table in a freshly allocated page, for `writer`
+    pub fn create(cache: Arc<Cache>, writer: TxnId) -> Result<Self, String> {
+        let first_page_id = cache
+            .allocate_page(writer)
+            .map_err(|e| format!("Failed to allocate first page: {}", e))?;
+
+        let frame_arc = cache
+            .fetch_page(first_page_id)
+            .map_err(|_| "Failed to fetch page")?;
+        {
+            let mut frame = frame_arc.write().unwrap();
+            if let Some(ref mut page) = frame.page {
+                let mut table_page = TablePage::new(&mut page.data);
+                table_page.init(first_page_id as u32, u32::MAX); // No prev page
+            }
+        }
+        cache.unpin_page(first_page_id, Some(writer));
+
+        Ok(Self::new(cache, first_page_id, writer))
+    }
+
+    pub fn first_page_id(&self) -> usize {
+        self.first_page_id
+    }
+
     /// Insert a tuple into the table.
     /// Returns (PageID, SlotID) on success.
     pub fn insert(&self, tuple: &[u8]) -> Result<(usize, u16), String> {
+        self.start_writing()?;
+        let end = self.start_operation();
+        let payload = self.store(tuple, Version::new(self.txn_id()))?;
+
+        let rid = self.insert_into_pages(&payload, false, end, |(page_id, slot_id)| {
+            Undo::RemoveTuple {
+                table: self.first_page_id as u32,
+                page_id: page_id as u32,
+                slot_id,
+            }
+        })?;
+        // Nobody else knows the RID yet, the lock cannot wait
+        self.lock_tuple(rid, LockMode::Exclusive)?;
+        self.record_write(WriteKind::Insert, rid);
+        Ok(rid)
+    }
+
+    // Find a page with room for the payload, growing the table if needed
+    // `moved`: the payload is the new location of an updated tuple
+    // Storing it ends the operation, with the undo made from its RID.
+    fn insert_into_pages(
+        &self,
+        payload: &Payload,
+        moved: bool,
+        end: OperationEnd,
+        undo: impl FnOnce(Rid) -> Undo,
+    ) -> Result<Rid, String> {
         let mut current_page_id = self.first_page_id;
 
         loop {
@@ -39,10 +144,13 @@ impl TableHeap {
                 let mut table_page = TablePage::new(&mut page.data);
 
                 // 2. Try to insert into this page
-                if let Some(slot_id) = table_page.insert_tuple(tuple) {
+                if let Some(slot_id) = table_page.insert_payload(payload, moved) {
+                    let rid = (current_page_id, slot_id);
+                    self.cache
+                        .end_operation_latched(&mut frame, self.writer, end, undo(rid));
                     drop(frame); // Release lock
-                    self.cache.unpin_page(current_page_id, true); // Mark dirty
-                    return Ok((current_page_id, slot_id));
+                    self.cache.unpin_page(current_page_id, None);
+                    return Ok(rid);
                 }
 
                 // 3. Page is full. Check for next page.
@@ -50,21 +158,32 @@ impl TableHeap {
                     Some(next_id) => {
                         // Move to next page
                         drop(frame);
-                        self.cache.unpin_page(current_page_id, false);
+                        self.cache.unpin_page(current_page_id, None);
                         current_page_id = next_id as usize;
                     }
                     None => {
                         // 4. End of list. Create a NEW page.
-                        // Unpin current (we'll re-fetch it shortly to link)
-                        drop(frame);
-                        self.cache.unpin_page(current_page_id, false);
-
-                        // Try to fetch the 'next' logical ID.
-                        let new_page_id = current_page_id + 1;
-                        let new_frame_arc = self
-                            .cache
-                            .fetch_page(new_page_id)
-                            .map_err(|_| "Failed to allocate new page")?;
+                        // The current page stays latched until it links to the new one,
+                        // so concurrent inserters cannot each append a page of their own.
+                        // Allocating only latches free-list pages, never a table page.
+                        // The page stays in the table even if the insert is rolled back.
+                        let append = self.start_operation();
+                        let new_page_id = match self.cache.allocate_page(self.writer) {
+                            Ok(new_page_id) => new_page_id,
+                            Err(e) => {
+                                drop(frame);
+                                self.cache.unpin_page(current_page_id, None);
+                                return Err(format!("Failed to allocate new page: {}", e));
+                            }
+                        };
+                        let new_frame_arc = match self.cache.fetch_page(new_page_id) {
+                            Ok(new_frame_arc) => new_frame_arc,
+                            Err(_) => {
+                                drop(frame);
+                                self.cache.unpin_page(current_page_id, None);
+                                return Err("Failed to allocate new page".to_string());
+                            }
+                        };
 
                         {
                             let mut new_frame = new_frame_arc.write().unwrap();
@@ -73,18 +192,18 @@ impl TableHeap {
                                 new_table_page.init(new_page_id as u32, current_page_id as u32);
                             }
                         }
-                        self.cache.unpin_page(new_page_id, true); // Mark new page dirty
+                        self.cache.unpin_page(new_page_id, Some(self.writer)); // Mark new page dirty
 
                         // 5. Link OLD page to NEW page
-                        let old_frame_arc = self.cache.fetch_page(current_page_id).unwrap();
-                        {
-                            let mut old_frame = old_frame_arc.write().unwrap();
-                            if let Some(ref mut old_page_data) = old_frame.page {
-                                let mut old_table_page = TablePage::new(&mut old_page_data.data);
-                                old_table_page.set_next_page_id(new_page_id as u32);
-                            }
-                        }
-                        self.cache.unpin_page(current_page_id, true); // Mark old page dirty
+                        table_page.set_next_page_id(new_page_id as u32);
+                        self.cache.end_operation_latched(
+                            &mut frame,
+                            self.writer,
+                            append,
+                            Undo::Nothing,
+                        );
+                        drop(frame);
+                        self.cache.unpin_page(current_page_id, None);
 
                         // Loop will continue, current_page_id becomes new_page_id,
                         // and we will insert into the empty new page on next iteration.
@@ -97,7 +216,669 @@ impl TableHeap {
         }
     }
 
+    // Fetch a live tuple by RID
+    // Deleted tuples are reported as missing, forwarding pointers are followed
     pub fn get_tuple(&self, page_id: usize, slot_id: u16) -> Result<Vec<u8>, String> {
+        self.read_visible(page_id, slot_id)?
+            .ok_or_else(|| not_found(page_id, slot_id))
+    }
+
+    // The version of a tuple the transaction reads, None if it has none:
+    // the tuple is deleted, or was created after its snapshot
+    pub fn read_visible(&self, page_id: usize, slot_id: u16) -> Result<Option<Vec<u8>>, String> {
+        self.lock_tuple((page_id, slot_id), LockMode::Shared)?;
+        let Some((_, mut payload)) = self.latest(page_id, slot_id)? else {
+            return Ok(None);
+        };
+
+        // Newest to oldest, until one existed when the snapshot was taken
+        loop {
+            match self.visibility(&payload.version) {
+                Visibility::Visible => break,
+                Visibility::Deleted => return Ok(None),
+                Visibility::Invisible => match payload.version.previous {
+                    Some((previous_page_id, previous_slot_id)) => {
+                        payload = self.read_version(previous_page_id, previous_slot_id)?;
+                    }
+                    None => return Ok(None),
+                },
+            }
+        }
+
+        if let Some(txn) = &self.txn {
+            txn.record_read(self.first_page_id, (page_id, slot_id));
+        }
+        self.load(&payload).map(Some)
+    }
+
+    // Delete a tuple by RID: its latest version is stamped as deleted by the writer
+    // The garbage collector removes it once nobody can read it anymore.
+    pub fn delete(&self, page_id: usize, slot_id: u16) -> Result<(), String> {
+        self.lock_tuple((page_id, slot_id), LockMode::Exclusive)?;
+        self.start_writing()?;
+
+        let Some(((latest_page_id, latest_slot_id), latest)) = self.latest(page_id, slot_id)?
+        else {
+            return Err(not_found(page_id, slot_id));
+        };
+        if !self.check_write(&latest.version)? {
+            return Err(not_found(page_id, slot_id));
+        }
+
+        let version = Version {
+            deleted_by: Some(self.writer),
+            ..latest.version
+        };
+        let undo = Undo::SetVersion {
+            table: self.first_page_id as u32,
+            page_id: latest_page_id as u32,
+            slot_id: latest_slot_id,
+            version: latest.version,
+        };
+        let end = self.start_operation();
+        self.set_version(latest_page_id, latest_slot_id, &version, end, undo)?;
+        self.record_write(WriteKind::Delete, (page_id, slot_id));
+        Ok(())
+    }
+
+    // Replace a tuple, keeping its RID
+    // 1. Try to update it in its page (in place, or in the page's free space)
+    // 2. Otherwise move it to another page, and leave a forwarding pointer behind
+    pub fn update(&self, page_id: usize, slot_id: u16, tuple: &[u8]) -> Result<(), String> {
+        self.lock_tuple((page_id, slot_id), LockMode::Exclusive)?;
+        self.start_writing()?;
+        if let Some(txn) = self.versioning_txn() {
+            return self.add_version(txn, page_id, slot_id, tuple);
+        }
+
+        let Some((_, latest)) = self.latest(page_id, slot_id)? else {
+            return Err(not_found(page_id, slot_id));
+        };
+        if !self.check_write(&latest.version)? {
+            return Err(not_found(page_id, slot_id));
+        }
+        let undo = self.restore(page_id, slot_id, latest.version, self.load(&latest)?);
+
+        let end = self.start_operation();
+        let payload = self.store(tuple, Version::new(self.txn_id()))?;
+        let replaced = self.update_payload(page_id, slot_id, &payload, end, undo)?;
+        self.record_write(WriteKind::Update, (page_id, slot_id));
+        self.free_overflow(&replaced)
+    }
+
+    // Undo of an insert (see `Undo::RemoveTuple`), rollback goes on at `undo_next`
+    // An update since may have left a forwarding pointer, the moved tuple goes too.
+    pub fn undo_insert(&self, page_id: usize, slot_id: u16, undo_next: Lsn) -> Result<(), String> {
+        let end = OperationEnd::Compensating(undo_next);
+        match self.read_slot(page_id, slot_id)? {
+            Some(Slot::Tuple(payload) | Slot::Moved(payload)) => {
+                self.delete_slot(page_id, slot_id, end)?;
+                self.free_overflow(&payload)
+            }
+            Some(Slot::Forward(target_page_id, target_slot_id)) => {
+                let payload = self.read_moved(page_id, slot_id, target_page_id, target_slot_id)?;
+                self.delete_slot(page_id, slot_id, end)?;
+                self.delete_slot(target_page_id, target_slot_id, self.start_operation())?;
+                self.free_overflow(&payload)
+            }
+            None => Err(not_found(page_id, slot_id)),
+        }
+    }
+
+    // Undo of a copy (see `Undo::EmptySlot`), rollback goes on at `undo_next`
+    pub fn undo_copy(&self, page_id: usize, slot_id: u16, undo_next: Lsn) -> Result<(), String> {
+        self.delete_slot(page_id, slot_id, OperationEnd::Compensating(undo_next))
+    }
+
+    // Undo of an update (see `Undo::RestoreTuple`), rollback goes on at `undo_next`
+    pub fn undo_update(
+        &self,
+        page_id: usize,
+        slot_id: u16,
+        version: Version,
+        tuple: &[u8],
+        undo_next: Lsn,
+    ) -> Result<(), String> {
+        let end = OperationEnd::Compensating(undo_next);
+        let payload = self.store(tuple, version)?;
+        let replaced = self.update_payload(page_id, slot_id, &payload, end, Undo::Nothing)?;
+        self.free_overflow(&replaced)
+    }
+
+    // Undo of a delete (see `Undo::SetVersion`), rollback goes on at `undo_next`
+    pub fn undo_version(
+        &self,
+        page_id: usize,
+        slot_id: u16,
+        version: Version,
+        undo_next: Lsn,
+    ) -> Result<(), String> {
+        let end = OperationEnd::Compensating(undo_next);
+        self.set_version(page_id, slot_id, &version, end, Undo::Nothing)
+    }
+
+    // Garbage collection of one tuple: remove the versions no transaction can read anymore
+    // Every transaction below `horizon` is seen by all running transactions, so
+    // the newest version created below it hides the older ones,
+    // and a tuple deleted below it is gone for everyone.
+    // Nothing removed comes back if the collecting transaction rolls back.
+    pub fn prune(&self, page_id: usize, slot_id: u16, horizon: TxnId) -> Result<Pruned, String> {
+        let Some((location, latest)) = self.latest(page_id, slot_id)? else {
+            return Ok(Pruned::default());
+        };
+
+        // Every version, newest first, with its location
+        let mut chain = vec![(location, latest)];
+        while let Some((previous_page_id, previous_slot_id)) =
+            chain.last().unwrap().1.version.previous
+        {
+            let payload = self.read_version(previous_page_id, previous_slot_id)?;
+            chain.push(((previous_page_id, previous_slot_id), payload));
+        }
+
+        let deleted = chain[0]
+            .1
+            .version
+            .deleted_by
+            .is_some_and(|txn| txn < horizon);
+        let keep = match chain
+            .iter()
+            .position(|(_, payload)| payload.version.created_by < horizon)
+        {
+            _ if deleted => 0,
+            Some(index) => index + 1,
+            None => chain.len(),
+        };
+        let removed = chain.split_off(keep);
+        if removed.is_empty() {
+            return Ok(Pruned::default());
+        }
+
+        let load_all = |versions: &[(Rid, Payload)]| -> Result<Vec<Vec<u8>>, String> {
+            versions
+                .iter()
+                .map(|(_, payload)| self.load(payload))
+                .collect()
+        };
+        let pruned = Pruned {
+            removed: load_all(&removed)?,
+            kept: load_all(&chain)?,
+        };
+
+        // The oldest version left ends the chain
+        match chain.last() {
+            Some(((last_page_id, last_slot_id), last)) => {
+                let version = Version {
+                    previous: None,
+                    ..last.version
+                };
+                let end = self.start_operation();
+                self.set_version(*last_page_id, *last_slot_id, &version, end, Undo::Nothing)?;
+            }
+            None => self.delete_slot(page_id, slot_id, self.start_operation())?,
+        }
+
+        for ((version_page_id, version_slot_id), payload) in &removed {
+            if (*version_page_id, *version_slot_id) != (page_id, slot_id) {
+                self.delete_slot(*version_page_id, *version_slot_id, self.start_operation())?;
+            }
+            self.free_overflow(payload)?;
+        }
+        Ok(pruned)
+    }
+
+    // RIDs of every tuple, deleted or not (for the garbage collector)
+    pub fn rids(&self) -> Result<Vec<Rid>, String> {
+        let mut page_id = Some(self.first_page_id);
+        let mut rids = Vec::new();
+
+        while let Some(current_page_id) = page_id {
+            let frame_arc = self
+                .cache
+                .fetch_page(current_page_id)
+                .map_err(|_| "Failed to fetch page")?;
+            let frame = frame_arc.read().unwrap();
+            let next_page_id = match frame.page {
+                Some(ref page) => {
+                    for slot_id in 0..TablePage::read_slot_count(&page.data) as u16 {
+                        if let Some(Slot::Tuple(_) | Slot::Forward(..)) =
+                            TablePage::read_slot(&page.data, slot_id)
+                        {
+                            rids.push((current_page_id, slot_id));
+                        }
+                    }
+                    TablePage::read_next_page_id(&page.data)
+                }
+                None => return Err("Frame empty".to_string()),
+            };
+            drop(frame);
+            self.cache.unpin_page(current_page_id, None);
+            page_id = next_page_id.map(|id| id as usize);
+        }
+
+        Ok(rids)
+    }
+
+    // Does it keep the old versions of the tuples it changes (MVCC)?
+    // Index entries then have to stay until the garbage collector removes the versions.
+    pub fn keeps_versions(&self) -> bool {
+        self.versioning_txn().is_some()
+    }
+
+    // Give every page of the table (and its overflow chains) back to the allocator
+    // The heap must not be used afterwards
+    pub fn destroy(&self) -> Result<(), String> {
+        let mut page_id = Some(self.first_page_id);
+
+        while let Some(current_page_id) = page_id {
+            let frame_arc = self
+                .cache
+                .fetch_page(current_page_id)
+                .map_err(|_| "Failed to fetch page")?;
+            let frame = frame_arc.read().unwrap();
+            let (slot_count, next_page_id) = match frame.page {
+                Some(ref page) => (
+                    TablePage::read_slot_count(&page.data) as u16,
+                    TablePage::read_next_page_id(&page.data),
+                ),
+                None => return Err("Frame empty".to_string()),
+            };
+            drop(frame);
+            self.cache.unpin_page(current_page_id, None);
+
+            // Moved tuples own their overflow chain, forwarding slots don't
+            for slot_id in 0..slot_count {
+                if let Some(Slot::Tuple(payload) | Slot::Moved(payload)) =
+                    self.read_slot(current_page_id, slot_id)?
+                {
+                    self.free_overflow(&payload)?;
+                }
+            }
+
+            self.cache
+                .deallocate_page(current_page_id, self.writer)
+                .map_err(|e| format!("Failed to free page {}: {}", current_page_id, e))?;
+            page_id = next_page_id.map(|id| id as usize);
+        }
+
+        Ok(())
+    }
+
+    // Rough number of tuples, for the optimizer
+    // Counts the tuples a RID points to, page by page: deleted ones and old versions are left out
+    // A forwarded tuple is counted once, at its forwarding pointer
+    pub fn estimate_rows(&self) -> Result<usize, String> {
+        let mut page_id = Some(self.first_page_id);
+        let mut rows = 0;
+
+        while let Some(current_page_id) = page_id {
+            let frame_arc = self
+                .cache
+                .fetch_page(current_page_id)
+                .map_err(|_| "Failed to fetch page")?;
+            let frame = frame_arc.read().unwrap();
+            let next_page_id = match frame.page {
+                Some(ref page) => {
+                    let slot_count = TablePage::read_slot_count(&page.data) as u16;
+                    rows += (0..slot_count)
+                        .filter(|&slot_id| match TablePage::read_slot(&page.data, slot_id) {
+                            Some(Slot::Tuple(payload)) => payload.version.deleted_by.is_none(),
+                            Some(Slot::Forward(..)) => true,
+                            Some(Slot::Moved(_)) | None => false,
+                        })
+                        .count();
+                    TablePage::read_next_page_id(&page.data)
+                }
+                None => return Err("Frame empty".to_string()),
+            };
+            drop(frame);
+            self.cache.unpin_page(current_page_id, None);
+            page_id = next_page_id.map(|id| id as usize);
+        }
+
+        Ok(rows)
+    }
+
+    // Returns the replaced payload
+    // The operation ends once the RID leads to the new payload, with `undo`
+    fn update_payload(
+        &self,
+        page_id: usize,
+        slot_id: u16,
+        payload: &Payload,
+        end: OperationEnd,
+        undo: Undo,
+    ) -> Result<Payload, String> {
+        match self.read_slot(page_id, slot_id)? {
+            Some(Slot::Tuple(old_payload)) => {
+                if self.modify_page(page_id, end, undo.clone(), |tp| {
+                    tp.update_tuple(slot_id, payload)
+                })? {
+                    return Ok(old_payload);
+                }
+
+                self.relocate(page_id, slot_id, payload, end, undo)?;
+                Ok(old_payload)
+            }
+            Some(Slot::Forward(target_page_id, target_slot_id)) => {
+                let old_payload =
+                    self.read_moved(page_id, slot_id, target_page_id, target_slot_id)?;
+
+                if self.modify_page(target_page_id, end, undo.clone(), |tp| {
+                    tp.update_tuple(target_slot_id, payload)
+                })? {
+                    return Ok(old_payload);
+                }
+
+                // Moved tuple outgrew its page too: move it again,
+                // the original slot always points at the latest location
+                self.relocate(page_id, slot_id, payload, end, undo)?;
+                self.delete_slot(target_page_id, target_slot_id, self.start_operation())?;
+                Ok(old_payload)
+            }
+            Some(Slot::Moved(_)) | None => Err(not_found(page_id, slot_id)),
+        }
+    }
+
+    // Store the tuple on another page and point the original slot at it
+    // If the pointer cannot be written (e.g. the page cannot be read),
+    // the slot still points where it did and rollback empties the copy.
+    fn relocate(
+        &self,
+        page_id: usize,
+        slot_id: u16,
+        payload: &Payload,
+        end: OperationEnd,
+        undo: Undo,
+    ) -> Result<(), String> {
+        let copy = self.start_operation();
+        let (new_page_id, new_slot_id) =
+            self.insert_into_pages(payload, true, copy, |(copy_page_id, copy_slot_id)| {
+                Undo::EmptySlot {
+                    table: self.first_page_id as u32,
+                    page_id: copy_page_id as u32,
+                    slot_id: copy_slot_id,
+                }
+            })?;
+        self.set_forward(page_id, slot_id, new_page_id, new_slot_id, end, undo)
+    }
+
+    // The latest version of a tuple, and where it is stored
+    fn latest(&self, page_id: usize, slot_id: u16) -> Result<Option<(Rid, Payload)>, String> {
+        match self.read_slot(page_id, slot_id)? {
+            Some(Slot::Tuple(payload)) => Ok(Some(((page_id, slot_id), payload))),
+            Some(Slot::Forward(target_page_id, target_slot_id)) => {
+                let payload = self.read_moved(page_id, slot_id, target_page_id, target_slot_id)?;
+                Ok(Some(((target_page_id, target_slot_id), payload)))
+            }
+            // A moved tuple is only reachable through its original RID
+            Some(Slot::Moved(_)) | None => Ok(None),
+        }
+    }
+
+    // An older version, at the end of a `previous` pointer
+    fn read_version(&self, page_id: usize, slot_id: u16) -> Result<Payload, String> {
+        match self.read_slot(page_id, slot_id)? {
+            Some(Slot::Moved(payload)) => Ok(payload),
+            _ => Err(format!(
+                "Broken version chain at ({}, {})",
+                page_id, slot_id
+            )),
+        }
+    }
+
+    // Under MVCC the latest version moves to a slot of its own, stamped as replaced,
+    // and the new one takes its place at the RID, pointing to it
+    fn add_version(
+        &self,
+        txn: &Transaction,
+        page_id: usize,
+        slot_id: u16,
+        tuple: &[u8],
+    ) -> Result<(), String> {
+        let Some((_, latest)) = self.latest(page_id, slot_id)? else {
+            return Err(not_found(page_id, slot_id));
+        };
+        if !txn.check_write(&latest.version)? {
+            return Err(not_found(page_id, slot_id));
+        }
+        let old_tuple = self.load(&latest)?;
+
+        // Changed before by the same transaction: nobody else reads that version
+        let version = if latest.version.created_by == txn.id() {
+            latest.version
+        } else {
+            // The copy gets overflow pages of its own: the replaced ones are freed
+            // right away, whether the copy is removed first or not.
+            let end = self.start_operation();
+            let old_version = Version {
+                deleted_by: Some(txn.id()),
+                ..latest.version
+            };
+            let old = self.store(&old_tuple, old_version)?;
+            let old_rid =
+                self.insert_into_pages(&old, true, end, |(old_page_id, old_slot_id)| {
+                    Undo::RemoveTuple {
+                        table: self.first_page_id as u32,
+                        page_id: old_page_id as u32,
+                        slot_id: old_slot_id,
+                    }
+                })?;
+            Version {
+                previous: Some(old_rid),
+                ..Version::new(txn.id())
+            }
+        };
+
+        let undo = self.restore(page_id, slot_id, latest.version, old_tuple);
+        let end = self.start_operation();
+        let payload = self.store(tuple, version)?;
+        let replaced = self.update_payload(page_id, slot_id, &payload, end, undo)?;
+        self.record_write(WriteKind::Update, (page_id, slot_id));
+        self.free_overflow(&replaced)
+    }
+
+    // How to undo replacing the latest version of a tuple: store it again
+    fn restore(&self, page_id: usize, slot_id: u16, version: Version, tuple: Vec<u8>) -> Undo {
+        Undo::RestoreTuple {
+            table: self.first_page_id as u32,
+            page_id: page_id as u32,
+            slot_id,
+            version,
+            tuple,
+        }
+    }
+
+    // Payload at the end of a forwarding pointer
+    fn read_moved(
+        &self,
+        page_id: usize,
+        slot_id: u16,
+        target_page_id: usize,
+        target_slot_id: u16,
+    ) -> Result<Payload, String> {
+        match self.read_slot(target_page_id, target_slot_id)? {
+            Some(Slot::Moved(payload)) => Ok(payload),
+            _ => Err(format!(
+                "Broken forwarding pointer at ({}, {})",
+                page_id, slot_id
+            )),
+        }
+    }
+
+    // Turn a tuple into what is stored in its slot
+    // Big tuples keep their head in the slot, the rest goes to a chain of overflow pages
+    fn store(&self, tuple: &[u8], version: Version) -> Result<Payload, String> {
+        if tuple.len() <= MAX_INLINE_SIZE {
+            return Ok(Payload {
+                version,
+                ..Payload::inline(tuple)
+            });
+        }
+
+        let (head, tail) = tuple.split_at(OVERFLOW_HEAD_SIZE);
+        let first_page_id = write_chain(&self.cache, tail, self.writer)?;
+
+        let mut bytes = Vec::with_capacity(MAX_INLINE_SIZE);
+        bytes.extend_from_slice(&(tuple.len() as u32).to_ne_bytes());
+        bytes.extend_from_slice(&first_page_id.to_ne_bytes());
+        bytes.extend_from_slice(head);
+
+        Ok(Payload {
+            version,
+            bytes,
+            overflow: true,
+        })
+    }
+
+    // Reverse of `store`: reassemble the whole tuple
+    fn load(&self, payload: &Payload) -> Result<Vec<u8>, String> {
+        if !payload.overflow {
+            return Ok(payload.bytes.clone());
+        }
+
+        let (total_length, first_page_id) = parse_stub(payload)?;
+        let mut tuple = Vec::with_capacity(total_length);
+        tuple.extend_from_slice(&payload.bytes[OVERFLOW_STUB_HEADER..]);
+        if let Some(first_page_id) = first_page_id {
+            read_chain(&self.cache, first_page_id, &mut tuple)?;
+        }
+
+        if tuple.len() != total_length {
+            return Err(format!(
+                "Corrupted overflow chain: expected {} bytes, got {}",
+                total_length,
+                tuple.len()
+            ));
+        }
+
+        Ok(tuple)
+    }
+
+    // Lock the whole table for the transaction, if any
+    pub fn lock_table(&self, mode: LockMode) -> Result<(), String> {
+        match &self.txn {
+            Some(txn) => txn.lock_table(self.first_page_id, mode),
+            None => Ok(()),
+        }
+    }
+
+    // Lock a tuple for the transaction, if any
+    pub fn lock_tuple(&self, rid: (usize, u16), mode: LockMode) -> Result<(), String> {
+        match &self.txn {
+            Some(txn) => txn.lock_tuple(self.first_page_id, rid, mode),
+            None => Ok(()),
+        }
+    }
+
+    fn txn_id(&self) -> TxnId {
+        self.txn.as_ref().map_or(SYSTEM_TXN, |txn| txn.id())
+    }
+
+    fn start_operation(&self) -> OperationEnd {
+        self.cache.start_operation(self.writer)
+    }
+
+    // Before replacing or deleting the latest version of a tuple
+    // Returns false if it is already deleted.
+    fn check_write(&self, latest: &Version) -> Result<bool, String> {
+        match &self.txn {
+            Some(txn) => txn.check_write(latest),
+            None => Ok(latest.deleted_by.is_none()),
+        }
+    }
+
+    fn versioning_txn(&self) -> Option<&Transaction> {
+        self.txn.as_deref().filter(|txn| txn.snapshot().is_some())
+    }
+
+    fn visibility(&self, version: &Version) -> Visibility {
+        match &self.txn {
+            Some(txn) => txn.visibility(version),
+            // Without a transaction only the latest version counts
+            None if version.deleted_by.is_some() => Visibility::Deleted,
+            None => Visibility::Visible,
+        }
+    }
+
+    fn start_writing(&self) -> Result<(), String> {
+        match &self.txn {
+            Some(txn) => txn.start_writing(),
+            None => Ok(()),
+        }
+    }
+
+    fn record_write(&self, kind: WriteKind, rid: (usize, u16)) {
+        if let Some(txn) = &self.txn {
+            txn.record_write(kind, self.first_page_id, rid);
+        }
+    }
+
+    // Give the overflow pages of a payload back to the allocator
+    fn free_overflow(&self, payload: &Payload) -> Result<(), String> {
+        if !payload.overflow {
+            return Ok(());
+        }
+
+        match parse_stub(payload)? {
+            (_, Some(first_page_id)) => free_chain(&self.cache, first_page_id, self.writer),
+            (_, None) => Ok(()),
+        }
+    }
+
+    fn set_forward(
+        &self,
+        page_id: usize,
+        slot_id: u16,
+        target_page_id: usize,
+        target_slot_id: u16,
+        end: OperationEnd,
+        undo: Undo,
+    ) -> Result<(), String> {
+        let forwarded = self.modify_page(page_id, end, undo, |tp| {
+            tp.set_forward(slot_id, target_page_id as u32, target_slot_id)
+        })?;
+
+        if forwarded {
+            Ok(())
+        } else {
+            Err(format!(
+                "Failed to write forwarding pointer at ({}, {})",
+                page_id, slot_id
+            ))
+        }
+    }
+
+    fn set_version(
+        &self,
+        page_id: usize,
+        slot_id: u16,
+        version: &Version,
+        end: OperationEnd,
+        undo: Undo,
+    ) -> Result<(), String> {
+        if self.modify_page(page_id, end, undo, |tp| tp.set_version(slot_id, version))? {
+            Ok(())
+        } else {
+            Err(not_found(page_id, slot_id))
+        }
+    }
+
+    // Emptying a slot is never undone on its own
+    fn delete_slot(&self, page_id: usize, slot_id: u16, end: OperationEnd) -> Result<(), String> {
+        let deleted = self.modify_page(page_id, end, Undo::Nothing, |tp| {
+            tp.mark_delete(slot_id) && tp.apply_delete(slot_id)
+        })?;
+
+        if deleted {
+            Ok(())
+        } else {
+            Err(not_found(page_id, slot_id))
+        }
+    }
+
+    // Raw content of a slot
+    fn read_slot(&self, page_id: usize, slot_id: u16) -> Result<Option<Slot>, String> {
         let frame_arc = self
             .cache
             .fetch_page(page_id)
@@ -106,23 +887,209 @@ impl TableHeap {
         let frame = frame_arc.read().unwrap(); // Read lock is enough
 
         if let Some(ref page) = frame.page {
-            let slot_offset = HEADER_SIZE + (slot_id as usize * 8);
-
-            let tuple_offset_bytes = &page.data[slot_offset..slot_offset + 4];
-            let tuple_len_bytes = &page.data[slot_offset + 4..slot_offset + 8];
-
-            let offset = u32::from_ne_bytes(tuple_offset_bytes.try_into().unwrap()) as usize;
-            let len = u32::from_ne_bytes(tuple_len_bytes.try_into().unwrap()) as usize;
-
-            let mut result = vec![0u8; len];
-            result.copy_from_slice(&page.data[offset..offset + len]);
+            let slot = TablePage::read_slot(&page.data, slot_id);
 
             drop(frame);
-            self.cache.unpin_page(page_id, false);
+            self.cache.unpin_page(page_id, None);
 
-            Ok(result)
+            Ok(slot)
         } else {
             Err("Frame empty".to_string())
         }
     }
+
+    // Run a change on a page under its write lock, as the last step of an operation
+    // `f` returns whether the page was changed: the operation then ends, with `undo`,
+    // before the lock is released (see `Cache::end_operation_latched`)
+    fn modify_page<F>(
+        &self,
+        page_id: usize,
+        end: OperationEnd,
+        undo: Undo,
+        f: F,
+    ) -> Result<bool, String>
+    where
+        F: FnOnce(&mut TablePage) -> bool,
+    {
+        let frame_arc = self
+            .cache
+            .fetch_page(page_id)
+            .map_err(|_| "Failed to fetch page")?;
+
+        let mut frame = frame_arc.write().unwrap();
+
+        let changed = match frame.page {
+            Some(ref mut page) => f(&mut TablePage::new(&mut page.data)),
+            None => {
+                drop(frame);
+                self.cache.unpin_page(page_id, None);
+                return Err("Frame empty".to_string());
+            }
+        };
+        if changed {
+            self.cache
+                .end_operation_latched(&mut frame, self.writer, end, undo);
+        }
+
+        drop(frame);
+        self.cache.unpin_page(page_id, None);
+        Ok(changed)
+    }
+}
+
+// (Total length, First overflow page) of a spilled tuple
+fn parse_stub(payload: &Payload) -> Result<(usize, Option<usize>), String> {
+    if payload.bytes.len() < OVERFLOW_STUB_HEADER {
+        return Err("Corrupted overflow stub".to_string());
+    }
+
+    let total_length = u32::from_ne_bytes(payload.bytes[0..4].try_into().unwrap()) as usize;
+    let first_page_id = u32::from_ne_bytes(payload.bytes[4..8].try_into().unwrap()) as usize;
+    Ok((total_length, Some(first_page_id).filter(|&id| id != 0)))
+}
+
+fn not_found(page_id: usize, slot_id: u16) -> String {
+    format!("Tuple ({}, {}) does not exist", page_id, slot_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process, sync::Arc};
+
+    use super::*;
+    use crate::{
+        acid::{mvcc::Concurrency, recovery::rollback, transaction::TransactionManager, wal::Wal},
+        backend::pager::Pager,
+    };
+
+    // A heap in its own database file, with a transaction manager over it
+    struct TempHeap {
+        path: String,
+        wal: Arc<Wal>,
+        transactions: TransactionManager,
+        heap: TableHeap,
+    }
+
+    impl TempHeap {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir()
+                .join(format!("mysqlite-heap-{}-{}.db", process::id(), name))
+                .to_string_lossy()
+                .into_owned();
+            remove(&path);
+
+            let wal = Arc::new(Wal::open(&format!("{}-wal", path)).unwrap());
+            let cache = Arc::new(Cache::new(Pager::new(&path).unwrap(), 16, wal.clone()));
+            let transactions =
+                TransactionManager::new(cache.clone(), wal.clone(), Concurrency::Locking);
+            let heap = TableHeap::create(cache, SYSTEM_TXN).unwrap();
+            Self {
+                path,
+                wal,
+                transactions,
+                heap,
+            }
+        }
+
+        // A heap logging under a transaction of its own, without locks
+        fn writer(&self) -> (TxnId, TableHeap) {
+            let txn = self.wal.reserve_txn();
+            self.wal.begin(txn);
+            let heap = TableHeap::new(self.heap.cache.clone(), self.heap.first_page_id, txn);
+            (txn, heap)
+        }
+
+        fn in_transaction(&self, txn: &Arc<Transaction>) -> TableHeap {
+            TableHeap::new(self.heap.cache.clone(), self.heap.first_page_id, SYSTEM_TXN)
+                .in_transaction(txn.clone())
+        }
+    }
+
+    impl Drop for TempHeap {
+        fn drop(&mut self) {
+            remove(&self.path);
+        }
+    }
+
+    fn remove(path: &str) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}-wal", path));
+    }
+
+    #[test]
+    fn forwarded_tuple_survives_update_delete_rollback() {
+        let temp = TempHeap::new("forward-rollback");
+
+        // Fill the first page, so growing a tuple has to move it
+        let rids: Vec<_> = (0..40u8)
+            .map(|i| temp.heap.insert(&[i; 90]).unwrap())
+            .collect();
+        let (page_id, slot_id) = rids[3];
+
+        let txn = temp.transactions.begin().unwrap();
+        temp.in_transaction(&txn)
+            .update(page_id, slot_id, &[7; 600])
+            .unwrap();
+        temp.transactions.commit(&txn).unwrap();
+        assert!(matches!(
+            temp.heap.read_slot(page_id, slot_id).unwrap(),
+            Some(Slot::Forward(..))
+        ));
+
+        // Move it again, then delete it, and take both back
+        let txn = temp.transactions.begin().unwrap();
+        let heap = temp.in_transaction(&txn);
+        heap.update(page_id, slot_id, &[8; 900]).unwrap();
+        heap.delete(page_id, slot_id).unwrap();
+        assert!(heap.get_tuple(page_id, slot_id).is_err());
+        temp.transactions.abort(&txn).unwrap();
+
+        assert_eq!(temp.heap.get_tuple(page_id, slot_id).unwrap(), vec![7; 600]);
+        for (i, &(page_id, slot_id)) in rids.iter().enumerate().filter(|&(i, _)| i != 3) {
+            assert_eq!(
+                temp.heap.get_tuple(page_id, slot_id).unwrap(),
+                vec![i as u8; 90]
+            );
+        }
+    }
+
+    // Both writers change tuples of the same pages, one of them rolls back
+    #[test]
+    fn rollback_keeps_changes_of_other_transactions() {
+        let temp = TempHeap::new("interleaved-rollback");
+        let rids: Vec<_> = (0..20u8)
+            .map(|i| temp.heap.insert(&[i; 90]).unwrap())
+            .collect();
+        let (txn, rolled_back) = temp.writer();
+        let (_, other) = temp.writer();
+
+        let inserted = rolled_back.insert(&[100; 90]).unwrap();
+        let theirs = other.insert(&[101; 90]).unwrap();
+        rolled_back
+            .update(rids[0].0, rids[0].1, &[102; 600])
+            .unwrap();
+        other.update(rids[1].0, rids[1].1, &[103; 300]).unwrap();
+        rolled_back.delete(rids[2].0, rids[2].1).unwrap();
+        other.delete(rids[3].0, rids[3].1).unwrap();
+        let big = rolled_back.insert(&[104; 3 * PAGE_SIZE]).unwrap();
+        assert_eq!(inserted.0, theirs.0);
+
+        temp.wal.abort(txn);
+        rollback(&temp.heap.cache, &temp.wal, txn).unwrap();
+
+        let heap = &temp.heap;
+        assert!(heap.get_tuple(inserted.0, inserted.1).is_err());
+        assert!(heap.get_tuple(big.0, big.1).is_err());
+        assert_eq!(heap.get_tuple(theirs.0, theirs.1).unwrap(), vec![101; 90]);
+        assert_eq!(heap.get_tuple(rids[0].0, rids[0].1).unwrap(), vec![0; 90]);
+        assert_eq!(
+            heap.get_tuple(rids[1].0, rids[1].1).unwrap(),
+            vec![103; 300]
+        );
+        assert_eq!(heap.get_tuple(rids[2].0, rids[2].1).unwrap(), vec![2; 90]);
+        assert!(heap.get_tuple(rids[3].0, rids[3].1).is_err());
+        for (i, &(page_id, slot_id)) in rids.iter().enumerate().skip(4) {
+            assert_eq!(heap.get_tuple(page_id, slot_id).unwrap(), vec![i as u8; 90]);
+        }
+    }
 }
@@ -1,7 +1,4 @@
-use crate::indexing::{
-    table_heap::TableHeap,
-    table_page::{OFFSET_NEXT_PAGE_ID, OFFSET_SLOT_COUNT},
-};
+use crate::indexing::{btree::index::Rid, table_heap::TableHeap, table_page::TablePage};
 use std::sync::Arc;
 
 // An iterator that scans the entire table heap sequentially
@@ -20,58 +17,64 @@ impl TableIterator {
         }
     }
 
-    // Moves to the next tuple and returns it.
-    // Returns None if we reached the end of the table.
-    pub fn next(&mut self) -> Option<Vec<u8>> {
+    // Moves to the next tuple and returns it with its RID.
+    // Returns None at the end of the table. Errors include a page that cannot be read
+    // and a lock wait aborted by a deadlock.
+    // Under MVCC only the tuples the transaction's snapshot sees are returned.
+    pub fn try_next(&mut self) -> Result<Option<(Rid, Vec<u8>)>, String> {
         loop {
             // 1. Fetch the current page
             let frame_arc = self
                 .table_heap
                 .cache
                 .fetch_page(self.current_page_id)
-                .ok()?;
+                .map_err(|e| format!("Failed to fetch page {}: {}", self.current_page_id, e))?;
             let frame = frame_arc.read().unwrap();
 
             if let Some(ref page) = frame.page {
-                let count_bytes = &page.data[OFFSET_SLOT_COUNT..OFFSET_SLOT_COUNT + 4];
-                let slot_count = u32::from_ne_bytes(count_bytes.try_into().unwrap()) as u16;
+                let slot_count = TablePage::read_slot_count(&page.data) as u16;
 
                 // 2. Check if we have more slots in this page
                 if self.current_slot_id < slot_count {
                     drop(frame); // Release lock strictly before calling other methods
+                    self.table_heap.cache.unpin_page(self.current_page_id, None);
 
-                    let tuple = self
-                        .table_heap
-                        .get_tuple(self.current_page_id, self.current_slot_id)
-                        .ok();
+                    let rid = (self.current_page_id, self.current_slot_id);
+                    let tuple = self.table_heap.read_visible(rid.0, rid.1)?;
                     self.current_slot_id += 1;
 
-                    if tuple.is_some() {
-                        return tuple;
+                    // Deleted slots, old versions and tuples the transaction
+                    // does not see come back as None, skip them
+                    if let Some(tuple) = tuple {
+                        return Ok(Some((rid, tuple)));
                     }
                 } else {
                     // 3. No more slots in this page. Move to next page.
-                    let next_page_bytes = &page.data[OFFSET_NEXT_PAGE_ID..OFFSET_NEXT_PAGE_ID + 4];
-                    let next_page_id =
-                        u32::from_ne_bytes(next_page_bytes.try_into().unwrap()) as usize;
+                    let next_page_id = TablePage::read_next_page_id(&page.data);
 
                     drop(frame); // Release lock
-                    self.table_heap
-                        .cache
-                        .unpin_page(self.current_page_id, false);
+                    self.table_heap.cache.unpin_page(self.current_page_id, None);
 
-                    if next_page_id == 0 {
-                        return None; // End of Linked List
-                    }
+                    let Some(next_page_id) = next_page_id else {
+                        return Ok(None); // End of Linked List
+                    };
 
                     // Advance to next page, reset slot to 0
-                    self.current_page_id = next_page_id;
+                    self.current_page_id = next_page_id as usize;
                     self.current_slot_id = 0;
                 }
             } else {
                 // Should not happen if logic is correct
-                return None;
+                return Ok(None);
             }
         }
     }
 }
+
+impl Iterator for TableIterator {
+    type Item = Result<(Rid, Vec<u8>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().transpose()
+    }
+}
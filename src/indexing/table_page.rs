@@ -1,4 +1,7 @@
-use crate::backend::pager::{PAGE_SIZE, PageData};
+use crate::{
+    acid::log_record::{SYSTEM_TXN, TxnId},
+    backend::pager::{PageData, USABLE_SIZE},
+};
 
 // Memory header layout
 // Bytes 0-3: ID of this page
@@ -15,6 +18,121 @@ pub const OFFSET_SLOT_COUNT: usize = 16;
 pub const HEADER_SIZE: usize = 20;
 const SLOT_SIZE: usize = 8; // Offset(4) + Length(4)
 
+// High bits of a slot length
+// DELETE_FLAG: tuple is marked as deleted, the bytes are still there until the delete is applied
+// FORWARD_FLAG: the tuple was moved, the slot holds a forwarding pointer: PageID(4) + SlotID(2)
+// MOVED_FLAG: the tuple was moved here, it is only reachable through its original slot
+// OVERFLOW_FLAG: the bytes are only the head of the tuple, the rest is in overflow pages
+const DELETE_FLAG: u32 = 1 << 31;
+const FORWARD_FLAG: u32 = 1 << 30;
+const MOVED_FLAG: u32 = 1 << 29;
+const OVERFLOW_FLAG: u32 = 1 << 28;
+const LENGTH_MASK: u32 = OVERFLOW_FLAG - 1;
+
+// Every tuple reserves at least this much space,
+// so any slot can later be turned into a forwarding pointer
+pub const FORWARD_SIZE: usize = 6;
+
+// Version header in front of the bytes of every tuple
+// Bytes 0-7: ID of the transaction that created the version
+// Bytes 8-15: ID of the transaction that deleted or replaced it (0 = none)
+// Bytes 16-19: Page of the previous version (0 = none)
+// Bytes 20-21: Slot of the previous version
+const OFFSET_CREATED_BY: usize = 0;
+const OFFSET_DELETED_BY: usize = 8;
+const OFFSET_PREVIOUS_PAGE: usize = 16;
+const OFFSET_PREVIOUS_SLOT: usize = 20;
+pub const VERSION_SIZE: usize = 22;
+
+// Which transactions a version of a tuple belongs to (see `acid::mvcc`)
+// Older versions are kept in slots of their own, newest to oldest through `previous`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version {
+    pub created_by: TxnId,
+    pub deleted_by: Option<TxnId>,
+    pub previous: Option<(usize, u16)>,
+}
+
+impl Version {
+    pub fn new(created_by: TxnId) -> Self {
+        Self {
+            created_by,
+            deleted_by: None,
+            previous: None,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; VERSION_SIZE] {
+        let mut bytes = [0u8; VERSION_SIZE];
+        let (page_id, slot_id) = self.previous.unwrap_or((0, 0));
+        bytes[OFFSET_CREATED_BY..OFFSET_CREATED_BY + 8]
+            .copy_from_slice(&self.created_by.to_ne_bytes());
+        bytes[OFFSET_DELETED_BY..OFFSET_DELETED_BY + 8]
+            .copy_from_slice(&self.deleted_by.unwrap_or(0).to_ne_bytes());
+        bytes[OFFSET_PREVIOUS_PAGE..OFFSET_PREVIOUS_PAGE + 4]
+            .copy_from_slice(&(page_id as u32).to_ne_bytes());
+        bytes[OFFSET_PREVIOUS_SLOT..OFFSET_PREVIOUS_SLOT + 2]
+            .copy_from_slice(&slot_id.to_ne_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Self {
+        let u64_at =
+            |offset: usize| u64::from_ne_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let page_id = read_u32(bytes, OFFSET_PREVIOUS_PAGE) as usize;
+        let slot_id = u16::from_ne_bytes(
+            bytes[OFFSET_PREVIOUS_SLOT..OFFSET_PREVIOUS_SLOT + 2]
+                .try_into()
+                .unwrap(),
+        );
+
+        Self {
+            created_by: u64_at(OFFSET_CREATED_BY),
+            deleted_by: Some(u64_at(OFFSET_DELETED_BY)).filter(|&txn| txn != 0),
+            previous: Some((page_id, slot_id)).filter(|&(page_id, _)| page_id != 0),
+        }
+    }
+}
+
+// Bytes stored in a slot, after the version header
+// `overflow`: they are only the head of a tuple continued in overflow pages
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payload {
+    pub version: Version,
+    pub bytes: Vec<u8>,
+    pub overflow: bool,
+}
+
+impl Payload {
+    pub fn inline(bytes: &[u8]) -> Self {
+        Self {
+            version: Version::new(SYSTEM_TXN),
+            bytes: bytes.to_vec(),
+            overflow: false,
+        }
+    }
+
+    // Version header + bytes, as written in the data region
+    fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(VERSION_SIZE + self.bytes.len());
+        data.extend_from_slice(&self.version.encode());
+        data.extend_from_slice(&self.bytes);
+        data
+    }
+
+    fn flags(&self) -> u32 {
+        if self.overflow { OVERFLOW_FLAG } else { 0 }
+    }
+}
+
+// What a slot holds, as seen by readers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Slot {
+    Tuple(Payload),
+    Moved(Payload),
+    Forward(usize, u16),
+}
+
 // Table page is for solving the fragmentation problem
 // [Tuple A][Tuple B] -> delete [Tuple A] -> leave a hole
 // If the next tuple > [Tuple A] -> Not fit
@@ -23,6 +141,17 @@ const SLOT_SIZE: usize = 8; // Offset(4) + Length(4)
 // 1. Header & Slot: Top-down
 // 2. Tuple Data: Bottom-up
 // 3. Free space: the empty gap in the middle. 2 regions meet -> Page is full
+//
+// Deleted / shrunk tuples leave holes in the data region.
+// When the gap is too small but the holes would be enough, `compact` merges them.
+//
+// Deleting a tuple never removes its slot, so slot ids (and RIDs) stay stable:
+// 1. mark_delete: set DELETE_FLAG on the slot length (can be rolled back)
+// 2. apply_delete: the slot becomes zero-length (offset 0, length 0)
+// A zero-length slot is free again: the next insert into the page takes it.
+//
+// An updated tuple that no longer fits in the page is moved to another page.
+// Its original slot keeps a forwarding pointer, so the original RID still resolves.
 pub struct TablePage<'a> {
     data: &'a mut PageData,
 }
@@ -37,7 +166,7 @@ impl<'a> TablePage<'a> {
         self.write_u32(OFFSET_PAGE_ID, page_id);
         self.write_u32(OFFSET_PREV_PAGE_ID, prev_id);
         self.write_u32(OFFSET_NEXT_PAGE_ID, 0); // 0 acts as null
-        self.write_u32(OFFSET_FREE_SPACE, PAGE_SIZE as u32); // Points to end of page
+        self.write_u32(OFFSET_FREE_SPACE, USABLE_SIZE as u32); // Points to end of usable space
         self.write_u32(OFFSET_SLOT_COUNT, 0);
     }
 
@@ -55,6 +184,17 @@ impl<'a> TablePage<'a> {
     }
 
     pub fn insert_tuple(&mut self, tuple: &[u8]) -> Option<u16> {
+        self.insert_payload(&Payload::inline(tuple), false)
+    }
+
+    // Insert a payload (possibly an overflow head)
+    // `moved`: it is a tuple relocated from another page, or an old version (see `Slot::Moved`)
+    pub fn insert_payload(&mut self, payload: &Payload, moved: bool) -> Option<u16> {
+        let flags = payload.flags() | if moved { MOVED_FLAG } else { 0 };
+        self.insert_with_flags(&payload.encode(), flags)
+    }
+
+    fn insert_with_flags(&mut self, tuple: &[u8], flags: u32) -> Option<u16> {
         let size = tuple.len();
         let free_space_ptr = self.read_u32(OFFSET_FREE_SPACE) as usize;
         let slot_count = self.get_slot_count() as usize;
@@ -62,56 +202,350 @@ impl<'a> TablePage<'a> {
         // Calculate where the "Slots Area" ends
         let slots_end = HEADER_SIZE + (slot_count * SLOT_SIZE);
 
-        // Calculate required space: Data Size + New Slot Entry (8 bytes)
-        let space_needed = size + SLOT_SIZE;
+        // A slot emptied by `apply_delete` is reused before a new one is appended
+        let free_slot = self.free_slot();
+
+        // Calculate required space: Data Size + New Slot Entry (8 bytes) unless one is reused
+        let reserved = reserved_size(size);
+        let space_needed = reserved + if free_slot.is_some() { 0 } else { SLOT_SIZE };
         let space_available = free_space_ptr - slots_end;
 
         // Not enough space
         if space_needed > space_available {
-            return None;
+            // Holes left by deleted / shrunk tuples may be enough
+            if space_needed > self.total_free_space() {
+                return None;
+            }
+            self.compact();
         }
 
         // 1. Move the Free Space Pointer UP (towards 0)
-        let new_free_ptr = free_space_ptr - size;
-        self.write_u32(OFFSET_FREE_SPACE, new_free_ptr as u32);
+        let new_free_ptr = self.allocate_data(reserved);
 
         // 2. Write the Tuple Data
         self.data[new_free_ptr..new_free_ptr + size].copy_from_slice(tuple);
 
         // 3. Write the Slot Entry (Offset, Length)
-        // The slot is written at the current end of the slots area
-        let slot_offset = slots_end;
+        // The slot is the free one, or written at the current end of the slots area
+        let slot_id = free_slot.unwrap_or(slot_count as u16);
+        let slot_offset = HEADER_SIZE + (slot_id as usize * SLOT_SIZE);
         self.write_u32(slot_offset, new_free_ptr as u32); // Tuple Offset
-        self.write_u32(slot_offset + 4, size as u32); // Tuple Length
+        self.write_u32(slot_offset + 4, size as u32 | flags); // Tuple Length
 
         // 4. Increment Slot Count
-        self.write_u32(OFFSET_SLOT_COUNT, (slot_count + 1) as u32);
+        if free_slot.is_none() {
+            self.write_u32(OFFSET_SLOT_COUNT, (slot_count + 1) as u32);
+        }
 
         // Return the Slot ID (index)
-        Some(slot_count as u16)
+        Some(slot_id)
+    }
+
+    // First slot left zero-length by `apply_delete`
+    fn free_slot(&self) -> Option<u16> {
+        (0..self.get_slot_count() as u16)
+            .find(|&slot_id| self.read_u32(HEADER_SIZE + slot_id as usize * SLOT_SIZE + 4) == 0)
     }
 
+    // Raw bytes of the slot (only the head for an overflow tuple)
     pub fn get_tuple(&self, slot_id: u16) -> Option<Vec<u8>> {
-        let slot_count = self.get_slot_count() as u16;
-        if slot_id >= slot_count {
+        Self::read_tuple(self.data, slot_id)
+    }
+
+    // Replace the bytes of a live tuple (or moved tuple), keeping its slot id
+    // 1. Fits in the old space -> overwrite in place
+    // 2. Fits in the free space -> write it there, point the slot at it
+    // Returns false if neither works, the caller has to move the tuple
+    pub fn update_tuple(&mut self, slot_id: u16, payload: &Payload) -> bool {
+        let Some(slot_offset) = self.live_slot_offset(slot_id) else {
+            return false;
+        };
+
+        let length = self.read_u32(slot_offset + 4);
+        if length & FORWARD_FLAG != 0 {
+            return false;
+        }
+
+        let flags = (length & MOVED_FLAG) | payload.flags();
+        self.write_slot_data(slot_offset, &payload.encode(), flags)
+    }
+
+    // Rewrite the version header of a live tuple (or moved tuple) in place
+    pub fn set_version(&mut self, slot_id: u16, version: &Version) -> bool {
+        let Some(slot_offset) = self.live_slot_offset(slot_id) else {
+            return false;
+        };
+
+        if self.read_u32(slot_offset + 4) & FORWARD_FLAG != 0 {
+            return false;
+        }
+
+        let offset = self.read_u32(slot_offset) as usize;
+        self.data[offset..offset + VERSION_SIZE].copy_from_slice(&version.encode());
+        true
+    }
+
+    // Turn a live tuple into a forwarding pointer to its new location
+    pub fn set_forward(&mut self, slot_id: u16, page_id: u32, target_slot_id: u16) -> bool {
+        let Some(slot_offset) = self.live_slot_offset(slot_id) else {
+            return false;
+        };
+
+        let mut pointer = [0u8; FORWARD_SIZE];
+        pointer[0..4].copy_from_slice(&page_id.to_ne_bytes());
+        pointer[4..6].copy_from_slice(&target_slot_id.to_ne_bytes());
+
+        self.write_slot_data(slot_offset, &pointer, FORWARD_FLAG)
+    }
+
+    // Contiguous gap between the slot array and the tuple data
+    pub fn free_space(&self) -> usize {
+        let free_space_ptr = self.read_u32(OFFSET_FREE_SPACE) as usize;
+        let slots_end = HEADER_SIZE + (self.get_slot_count() as usize * SLOT_SIZE);
+        free_space_ptr - slots_end
+    }
+
+    // All free bytes in the page: the gap + the holes between tuples
+    pub fn total_free_space(&self) -> usize {
+        let slot_count = self.get_slot_count() as usize;
+        let slots_end = HEADER_SIZE + (slot_count * SLOT_SIZE);
+
+        let used: usize = (0..slot_count)
+            .map(|i| self.read_u32(HEADER_SIZE + i * SLOT_SIZE + 4))
+            .filter(|&length| length != 0)
+            .map(|length| reserved_size((length & LENGTH_MASK) as usize))
+            .sum();
+
+        USABLE_SIZE - slots_end - used
+    }
+
+    // Defragment the page
+    // Slide every tuple still holding bytes (including ones only marked as deleted)
+    // towards the end of the page, so all the holes merge into the free gap.
+    // Slot ids do not change, only their offsets.
+    pub fn compact(&mut self) {
+        let slot_count = self.get_slot_count() as usize;
+
+        // Copy the tuples out first, they may overlap their new positions
+        let mut tuples = Vec::new();
+        for i in 0..slot_count {
+            let slot_offset = HEADER_SIZE + i * SLOT_SIZE;
+            let length = self.read_u32(slot_offset + 4);
+            if length == 0 {
+                continue;
+            }
+
+            let offset = self.read_u32(slot_offset) as usize;
+            let size = (length & LENGTH_MASK) as usize;
+            tuples.push((slot_offset, self.data[offset..offset + size].to_vec()));
+        }
+
+        let mut free_space_ptr = USABLE_SIZE;
+        for (slot_offset, bytes) in tuples {
+            free_space_ptr -= reserved_size(bytes.len());
+            self.data[free_space_ptr..free_space_ptr + bytes.len()].copy_from_slice(&bytes);
+            self.write_u32(slot_offset, free_space_ptr as u32);
+        }
+
+        // Wipe the reclaimed bytes
+        let slots_end = HEADER_SIZE + (slot_count * SLOT_SIZE);
+        self.data[slots_end..free_space_ptr].fill(0);
+        self.write_u32(OFFSET_FREE_SPACE, free_space_ptr as u32);
+    }
+
+    // Write data for an existing slot, in place or in the free space
+    // Compact the page if only the holes together have enough room
+    fn write_slot_data(&mut self, slot_offset: usize, data: &[u8], flags: u32) -> bool {
+        let old_offset = self.read_u32(slot_offset) as usize;
+        let old_length = (self.read_u32(slot_offset + 4) & LENGTH_MASK) as usize;
+        let size = data.len();
+
+        let new_offset = if size <= reserved_size(old_length) {
+            old_offset
+        } else if reserved_size(size) <= self.free_space() {
+            self.allocate_data(reserved_size(size))
+        } else if reserved_size(size) <= self.total_free_space() + reserved_size(old_length) {
+            // Release the old bytes, then defragment to get one big gap
+            self.write_u32(slot_offset, 0);
+            self.write_u32(slot_offset + 4, 0);
+            self.compact();
+            self.allocate_data(reserved_size(size))
+        } else {
+            return false;
+        };
+
+        self.data[new_offset..new_offset + size].copy_from_slice(data);
+        self.write_u32(slot_offset, new_offset as u32);
+        self.write_u32(slot_offset + 4, size as u32 | flags);
+        true
+    }
+
+    // Take bytes from the free gap, returns their offset
+    fn allocate_data(&mut self, size: usize) -> usize {
+        let new_free_ptr = self.read_u32(OFFSET_FREE_SPACE) as usize - size;
+        self.write_u32(OFFSET_FREE_SPACE, new_free_ptr as u32);
+        new_free_ptr
+    }
+
+    // Flag the tuple as deleted, its bytes are kept
+    // Returns false if there is no live tuple at this slot
+    pub fn mark_delete(&mut self, slot_id: u16) -> bool {
+        let Some(slot_offset) = self.live_slot_offset(slot_id) else {
+            return false;
+        };
+
+        let length = self.read_u32(slot_offset + 4);
+        self.write_u32(slot_offset + 4, length | DELETE_FLAG);
+        true
+    }
+
+    // Undo `mark_delete`
+    pub fn rollback_delete(&mut self, slot_id: u16) -> bool {
+        if slot_id as u32 >= self.get_slot_count() {
+            return false;
+        }
+
+        let slot_offset = HEADER_SIZE + (slot_id as usize * SLOT_SIZE);
+        let length = self.read_u32(slot_offset + 4);
+        if length & DELETE_FLAG == 0 {
+            return false;
+        }
+
+        self.write_u32(slot_offset + 4, length & !DELETE_FLAG);
+        true
+    }
+
+    // Make the delete permanent: the slot becomes zero-length
+    // The slot itself stays, so other slot ids do not shift
+    pub fn apply_delete(&mut self, slot_id: u16) -> bool {
+        if slot_id as u32 >= self.get_slot_count() {
+            return false;
+        }
+
+        let slot_offset = HEADER_SIZE + (slot_id as usize * SLOT_SIZE);
+        if self.read_u32(slot_offset + 4) == 0 {
+            return false; // Already applied
+        }
+
+        self.write_u32(slot_offset, 0);
+        self.write_u32(slot_offset + 4, 0);
+        true
+    }
+
+    // Read-only helpers working on raw page data
+    // Used by readers holding only a read lock on the frame
+    pub fn read_slot_count(data: &PageData) -> u32 {
+        read_u32(data, OFFSET_SLOT_COUNT)
+    }
+
+    pub fn read_next_page_id(data: &PageData) -> Option<u32> {
+        let id = read_u32(data, OFFSET_NEXT_PAGE_ID);
+        if id == 0 { None } else { Some(id) }
+    }
+
+    // Bytes of a tuple stored in this slot (live or moved here)
+    // None if the slot does not exist, is deleted or only holds a forwarding pointer
+    pub fn read_tuple(data: &PageData, slot_id: u16) -> Option<Vec<u8>> {
+        match Self::read_slot(data, slot_id)? {
+            Slot::Tuple(payload) | Slot::Moved(payload) => Some(payload.bytes),
+            Slot::Forward(..) => None,
+        }
+    }
+
+    // None if the slot does not exist, or the tuple is deleted
+    pub fn read_slot(data: &PageData, slot_id: u16) -> Option<Slot> {
+        if slot_id as u32 >= Self::read_slot_count(data) {
             return None;
         }
 
         let slot_offset = HEADER_SIZE + (slot_id as usize * SLOT_SIZE);
-        let tuple_offset = self.read_u32(slot_offset) as usize;
-        let tuple_length = self.read_u32(slot_offset + 4) as usize;
+        let tuple_offset = read_u32(data, slot_offset) as usize;
+        let length = read_u32(data, slot_offset + 4);
+
+        if length & DELETE_FLAG != 0 || length == 0 {
+            return None;
+        }
+
+        let bytes = &data[tuple_offset..tuple_offset + (length & LENGTH_MASK) as usize];
+        if length & FORWARD_FLAG != 0 {
+            let page_id = u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as usize;
+            let slot_id = u16::from_ne_bytes(bytes[4..6].try_into().unwrap());
+            Some(Slot::Forward(page_id, slot_id))
+        } else {
+            let payload = Payload {
+                version: Version::decode(bytes),
+                bytes: bytes[VERSION_SIZE..].to_vec(),
+                overflow: length & OVERFLOW_FLAG != 0,
+            };
+
+            if length & MOVED_FLAG != 0 {
+                Some(Slot::Moved(payload))
+            } else {
+                Some(Slot::Tuple(payload))
+            }
+        }
+    }
+
+    // Offset of the slot entry, if it holds a live tuple
+    fn live_slot_offset(&self, slot_id: u16) -> Option<usize> {
+        if slot_id as u32 >= self.get_slot_count() {
+            return None;
+        }
 
-        let mut result = vec![0u8; tuple_length];
-        result.copy_from_slice(&self.data[tuple_offset..tuple_offset + tuple_length]);
-        Some(result)
+        let slot_offset = HEADER_SIZE + (slot_id as usize * SLOT_SIZE);
+        let length = self.read_u32(slot_offset + 4);
+        if length & DELETE_FLAG != 0 || length == 0 {
+            return None;
+        }
+        Some(slot_offset)
     }
 
     fn read_u32(&self, offset: usize) -> u32 {
-        let bytes = &self.data[offset..offset + 4];
-        u32::from_ne_bytes(bytes.try_into().unwrap())
+        read_u32(self.data, offset)
     }
 
     fn write_u32(&mut self, offset: usize, value: u32) {
         self.data[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
     }
 }
+
+// Space taken in the data region by a tuple of this length
+fn reserved_size(length: usize) -> usize {
+    length.max(FORWARD_SIZE)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::pager::PAGE_SIZE;
+
+    fn page() -> PageData {
+        let mut data = [0u8; PAGE_SIZE];
+        TablePage::new(&mut data).init(1, 0);
+        data
+    }
+
+    #[test]
+    fn inserts_reuse_deleted_slots() {
+        let mut data = page();
+        let mut tp = TablePage::new(&mut data);
+        let a = tp.insert_tuple(b"a").unwrap();
+        let b = tp.insert_tuple(b"b").unwrap();
+        let c = tp.insert_tuple(b"c").unwrap();
+
+        // Only marked: the slot still belongs to the deleted tuple
+        assert!(tp.mark_delete(b));
+        assert_eq!(tp.insert_tuple(b"d"), Some(3));
+
+        assert!(tp.apply_delete(b));
+        assert_eq!(tp.insert_tuple(b"e"), Some(b));
+        assert_eq!(tp.get_slot_count(), 4);
+        assert_eq!(tp.get_tuple(a).as_deref(), Some(&b"a"[..]));
+        assert_eq!(tp.get_tuple(b).as_deref(), Some(&b"e"[..]));
+        assert_eq!(tp.get_tuple(c).as_deref(), Some(&b"c"[..]));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod acid;
+pub mod backend;
+pub mod catalog;
+pub mod cli;
+pub mod database;
+pub mod frontend;
+pub mod indexing;
+pub mod row;
+pub mod types;
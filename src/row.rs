@@ -0,0 +1,238 @@
+use crate::{
+    catalog::schema::Schema,
+    types::{DataType, Value},
+};
+
+// Record format
+// [Null bitmap][Fixed-width section][Variable-length data]
+//
+// 1. Null bitmap: 1 bit per column, ceil(n / 8) bytes. Bit set -> value is NULL
+// 2. Fixed-width section: one entry per column, in schema order
+//    INTEGER / REAL: 8 bytes
+//    BOOLEAN: 1 byte
+//    TEXT / BLOB: Offset(4) + Length(4), pointing into the variable-length data
+//    A NULL value still takes its entry (zeroed), so offsets only depend on the schema
+// 3. Variable-length data: TEXT / BLOB bytes, back to back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub values: Vec<Value>,
+}
+
+impl Row {
+    pub fn new(values: Vec<Value>) -> Self {
+        Self { values }
+    }
+
+    // Encode the row into tuple bytes
+    // Fails if the values do not match the schema
+    pub fn serialize(&self, schema: &Schema) -> Result<Vec<u8>, String> {
+        if self.values.len() != schema.columns.len() {
+            return Err(format!(
+                "Expected {} values, but got {}",
+                schema.columns.len(),
+                self.values.len()
+            ));
+        }
+
+        let bitmap_size = null_bitmap_size(schema);
+        let fixed_size: usize = schema
+            .columns
+            .iter()
+            .map(|c| fixed_width(c.data_type))
+            .sum();
+
+        let mut buf = vec![0u8; bitmap_size + fixed_size];
+        let mut fixed_offset = bitmap_size;
+
+        for (i, (value, column)) in self.values.iter().zip(&schema.columns).enumerate() {
+            let width = fixed_width(column.data_type);
+            let slot = fixed_offset..fixed_offset + width;
+            fixed_offset += width;
+
+            match (value, column.data_type) {
+                (Value::Null, _) => {
+                    buf[i / 8] |= 1 << (i % 8);
+                }
+                (Value::Integer(v), DataType::Integer) => {
+                    buf[slot].copy_from_slice(&v.to_ne_bytes());
+                }
+                (Value::Real(v), DataType::Real) => {
+                    buf[slot].copy_from_slice(&v.to_ne_bytes());
+                }
+                (Value::Boolean(v), DataType::Boolean) => {
+                    buf[slot.start] = *v as u8;
+                }
+                (Value::Text(v), DataType::Text) => {
+                    write_varlen(&mut buf, slot.start, v.as_bytes());
+                }
+                (Value::Blob(v), DataType::Blob) => {
+                    write_varlen(&mut buf, slot.start, v);
+                }
+                (value, expected) => {
+                    return Err(format!(
+                        "Column '{}' expects {}, but got {}",
+                        column.name,
+                        expected,
+                        value.data_type().unwrap()
+                    ));
+                }
+            }
+        }
+
+        Ok(buf)
+    }
+
+    // Decode tuple bytes written by `serialize` with the same schema
+    pub fn deserialize(bytes: &[u8], schema: &Schema) -> Result<Self, String> {
+        let bitmap_size = null_bitmap_size(schema);
+        let fixed_size: usize = schema
+            .columns
+            .iter()
+            .map(|c| fixed_width(c.data_type))
+            .sum();
+        if bytes.len() < bitmap_size + fixed_size {
+            return Err("Corrupted tuple: shorter than its fixed-width section".to_string());
+        }
+
+        let mut values = Vec::with_capacity(schema.columns.len());
+        let mut fixed_offset = bitmap_size;
+
+        for (i, column) in schema.columns.iter().enumerate() {
+            let width = fixed_width(column.data_type);
+            let slot = &bytes[fixed_offset..fixed_offset + width];
+            fixed_offset += width;
+
+            if bytes[i / 8] & (1 << (i % 8)) != 0 {
+                values.push(Value::Null);
+                continue;
+            }
+
+            let value = match column.data_type {
+                DataType::Integer => Value::Integer(i64::from_ne_bytes(slot.try_into().unwrap())),
+                DataType::Real => Value::Real(f64::from_ne_bytes(slot.try_into().unwrap())),
+                DataType::Boolean => Value::Boolean(slot[0] != 0),
+                DataType::Text => {
+                    let data = read_varlen(bytes, slot)?;
+                    Value::Text(
+                        String::from_utf8(data.to_vec())
+                            .map_err(|_| "Corrupted tuple: invalid UTF-8 text".to_string())?,
+                    )
+                }
+                DataType::Blob => Value::Blob(read_varlen(bytes, slot)?.to_vec()),
+            };
+            values.push(value);
+        }
+
+        Ok(Self { values })
+    }
+}
+
+fn null_bitmap_size(schema: &Schema) -> usize {
+    schema.columns.len().div_ceil(8)
+}
+
+fn fixed_width(data_type: DataType) -> usize {
+    match data_type {
+        DataType::Integer | DataType::Real => 8,
+        DataType::Boolean => 1,
+        DataType::Text | DataType::Blob => 8, // Offset(4) + Length(4)
+    }
+}
+
+// Append the data at the end of the buffer, and point the fixed entry at it
+fn write_varlen(buf: &mut Vec<u8>, slot_start: usize, data: &[u8]) {
+    let offset = buf.len() as u32;
+    buf[slot_start..slot_start + 4].copy_from_slice(&offset.to_ne_bytes());
+    buf[slot_start + 4..slot_start + 8].copy_from_slice(&(data.len() as u32).to_ne_bytes());
+    buf.extend_from_slice(data);
+}
+
+fn read_varlen<'a>(bytes: &'a [u8], slot: &[u8]) -> Result<&'a [u8], String> {
+    let offset = u32::from_ne_bytes(slot[0..4].try_into().unwrap()) as usize;
+    let len = u32::from_ne_bytes(slot[4..8].try_into().unwrap()) as usize;
+
+    bytes
+        .get(offset..offset + len)
+        .ok_or_else(|| "Corrupted tuple: variable-length data out of bounds".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::pager::PAGE_SIZE, catalog::schema::Column};
+
+    // Nine columns: the null bitmap takes two bytes
+    fn schema() -> Schema {
+        let types = [
+            DataType::Integer,
+            DataType::Text,
+            DataType::Real,
+            DataType::Boolean,
+            DataType::Blob,
+            DataType::Integer,
+            DataType::Text,
+            DataType::Blob,
+            DataType::Integer,
+        ];
+        Schema::new(
+            types
+                .iter()
+                .enumerate()
+                .map(|(i, &data_type)| Column::new(&format!("c{}", i), data_type))
+                .collect(),
+        )
+    }
+
+    fn round_trip(values: Vec<Value>) {
+        let row = Row::new(values);
+        let bytes = row.serialize(&schema()).unwrap();
+        assert_eq!(Row::deserialize(&bytes, &schema()).unwrap(), row);
+    }
+
+    #[test]
+    fn rows_round_trip_with_nulls() {
+        round_trip(vec![Value::Null; 9]);
+        round_trip(vec![
+            Value::Integer(i64::MIN),
+            Value::Null,
+            Value::Real(-0.5),
+            Value::Boolean(true),
+            Value::Null,
+            Value::Integer(i64::MAX),
+            Value::Text(String::new()),
+            Value::Blob(vec![0, 255]),
+            Value::Null,
+        ]);
+    }
+
+    #[test]
+    fn rows_bigger_than_a_page_round_trip() {
+        let text = "é".repeat(PAGE_SIZE * 2);
+        let blob = vec![0xab; PAGE_SIZE * 3];
+        round_trip(vec![
+            Value::Integer(1),
+            Value::Text(text),
+            Value::Null,
+            Value::Boolean(false),
+            Value::Blob(blob),
+            Value::Null,
+            Value::Text("after".to_string()),
+            Value::Null,
+            Value::Integer(-1),
+        ]);
+    }
+
+    #[test]
+    fn bad_rows_are_rejected() {
+        let mut values = vec![Value::Null; 9];
+        values[0] = Value::Text("1".to_string());
+        assert!(Row::new(values).serialize(&schema()).is_err());
+        assert!(Row::new(vec![Value::Null]).serialize(&schema()).is_err());
+
+        let mut values = vec![Value::Null; 9];
+        values[1] = Value::Text("abc".to_string());
+        let bytes = Row::new(values).serialize(&schema()).unwrap();
+        assert!(Row::deserialize(&bytes[..bytes.len() - 1], &schema()).is_err());
+        assert!(Row::deserialize(&bytes[..4], &schema()).is_err());
+    }
+}
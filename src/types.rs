@@ -0,0 +1,127 @@
+use std::{cmp::Ordering, fmt};
+
+// Column types supported by the storage engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataType {
+    Integer,
+    Real,
+    Text,
+    Blob,
+    Boolean,
+}
+
+impl DataType {
+    // Stable code used when a type is written to disk
+    pub fn to_code(self) -> u8 {
+        match self {
+            DataType::Integer => 1,
+            DataType::Real => 2,
+            DataType::Text => 3,
+            DataType::Blob => 4,
+            DataType::Boolean => 5,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(DataType::Integer),
+            2 => Some(DataType::Real),
+            3 => Some(DataType::Text),
+            4 => Some(DataType::Blob),
+            5 => Some(DataType::Boolean),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DataType::Integer => "INTEGER",
+            DataType::Real => "REAL",
+            DataType::Text => "TEXT",
+            DataType::Blob => "BLOB",
+            DataType::Boolean => "BOOLEAN",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A single value stored in a column
+// Null is a value, not a type: any column can hold it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+    Boolean(bool),
+}
+
+impl Value {
+    // None for Null
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Null => None,
+            Value::Integer(_) => Some(DataType::Integer),
+            Value::Real(_) => Some(DataType::Real),
+            Value::Text(_) => Some(DataType::Text),
+            Value::Blob(_) => Some(DataType::Blob),
+            Value::Boolean(_) => Some(DataType::Boolean),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    // SQL comparison, None if either side is NULL or the types can't be compared
+    // INTEGER and REAL compare by numeric value
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Real(a), Value::Real(b)) => a.partial_cmp(b),
+            (Value::Integer(a), Value::Real(b)) => (*a as f64).partial_cmp(b),
+            (Value::Real(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            (Value::Blob(a), Value::Blob(b)) => Some(a.cmp(b)),
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    // Convert a value for storage in a column of the given type
+    // Only INTEGER -> REAL is implicit, NULL fits any column
+    pub fn coerce(self, data_type: DataType) -> Result<Value, String> {
+        match (self, data_type) {
+            (Value::Integer(v), DataType::Real) => Ok(Value::Real(v as f64)),
+            (value, _) if value.data_type().is_none_or(|t| t == data_type) => Ok(value),
+            (value, _) => Err(format!(
+                "Cannot store {} value in a {} column",
+                value.data_type().unwrap(),
+                data_type
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(v) => write!(f, "{}", v),
+            // Always with a fractional part or an exponent (1.0, 1e100), never like an integer
+            Value::Real(v) => write!(f, "{:?}", v),
+            Value::Text(v) => write!(f, "{}", v),
+            Value::Blob(v) => {
+                write!(f, "x'")?;
+                for byte in v {
+                    write!(f, "{:02x}", byte)?;
+                }
+                write!(f, "'")
+            }
+            Value::Boolean(v) => write!(f, "{}", v),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+#![allow(dead_code)]
+
+use std::{env, fs, process, thread, time::Duration};
+
+use mysqlite::{
+    database::{Database, QueryResult},
+    types::Value,
+};
+
+// How long a statement gets to finish before it is taken as blocked
+pub const BLOCKED: Duration = Duration::from_millis(300);
+
+// A database file in the system temp directory, deleted with its log when dropped
+pub struct TempDb {
+    pub path: String,
+}
+
+impl TempDb {
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir()
+            .join(format!("mysqlite-test-{}-{}.db", process::id(), name))
+            .to_string_lossy()
+            .into_owned();
+        let db = Self { path };
+        db.remove();
+        db
+    }
+
+    pub fn open(&self) -> Database {
+        Database::open(&self.path).unwrap()
+    }
+
+    fn remove(&self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = fs::remove_file(format!("{}-wal", self.path));
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+// Run one statement
+pub fn run(db: &mut Database, sql: &str) -> Result<QueryResult, String> {
+    db.execute(sql).pop().unwrap()
+}
+
+// Run statements that must succeed
+pub fn exec(db: &mut Database, sql: &str) {
+    for result in db.execute(sql) {
+        if let Err(e) = result {
+            panic!("{}: {}", sql, e);
+        }
+    }
+}
+
+// The rows of a SELECT
+pub fn query(db: &mut Database, sql: &str) -> Result<Vec<Vec<Value>>, String> {
+    match run(db, sql)? {
+        QueryResult::Rows { rows, .. } => Ok(rows.into_iter().map(|row| row.values).collect()),
+        _ => Err(format!("{} returned no rows", sql)),
+    }
+}
+
+// The single integer a SELECT returns
+pub fn query_int(db: &mut Database, sql: &str) -> i64 {
+    match query(db, sql).unwrap().as_slice() {
+        [row] => match row.as_slice() {
+            [Value::Integer(n)] => *n,
+            other => panic!("{} returned {:?}", sql, other),
+        },
+        rows => panic!("{} returned {} rows", sql, rows.len()),
+    }
+}
+
+// Run a statement on a session of its own thread
+// The session comes back with the result when the statement is done.
+pub fn spawn(
+    mut db: Database,
+    sql: &str,
+) -> thread::JoinHandle<(Database, Result<QueryResult, String>)> {
+    let sql = sql.to_string();
+    thread::spawn(move || {
+        let result = run(&mut db, &sql);
+        (db, result)
+    })
+}
+
+// Is the statement still running after a while?
+pub fn is_blocked<T>(handle: &thread::JoinHandle<T>) -> bool {
+    thread::sleep(BLOCKED);
+    !handle.is_finished()
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use common::{TempDb, exec};
+use mysqlite::database::{Database, QueryResult};
+
+fn explain(db: &mut Database, sql: &str) -> Vec<String> {
+    match db.execute(&format!("EXPLAIN {}", sql)).pop() {
+        Some(Ok(QueryResult::Plan(lines))) => lines,
+        _ => panic!("EXPLAIN {} returned no plan", sql),
+    }
+}
+
+#[test]
+fn estimates_count_live_tuples_and_never_reach_zero() {
+    let temp = TempDb::new("explain-estimates");
+    let mut db = temp.open();
+    exec(&mut db, "CREATE TABLE t (id INTEGER, v TEXT)");
+    for id in 0..20 {
+        exec(&mut db, &format!("INSERT INTO t VALUES ({}, 'v')", id));
+    }
+    exec(&mut db, "DELETE FROM t WHERE id >= 4");
+
+    let plan = explain(&mut db, "SELECT id FROM t");
+    assert!(
+        plan.iter().any(|line| line.contains("estimated rows: 4")),
+        "{:?}",
+        plan
+    );
+
+    let plan = explain(&mut db, "SELECT id FROM t WHERE id = 1 AND v = 'v'");
+    assert!(
+        plan.iter().all(|line| !line.contains("estimated rows: 0")),
+        "{:?}",
+        plan
+    );
+}
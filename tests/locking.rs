@@ -0,0 +1,95 @@
+// Two-phase locking between sessions of the same database
+mod common;
+
+use common::{TempDb, exec, is_blocked, query_int, run, spawn};
+use mysqlite::database::{Database, QueryResult};
+
+fn setup(name: &str) -> (TempDb, Database) {
+    let file = TempDb::new(name);
+    let mut db = file.open();
+    exec(
+        &mut db,
+        "CREATE TABLE t (id INTEGER, v INTEGER); INSERT INTO t VALUES (1, 10), (2, 20);",
+    );
+    (file, db)
+}
+
+#[test]
+fn reader_waits_for_writer() {
+    let (_file, mut writer) = setup("reader-waits");
+    exec(&mut writer, "BEGIN; UPDATE t SET v = 11 WHERE id = 1;");
+
+    let reader = spawn(writer.connect(), "SELECT v FROM t WHERE id = 1");
+    assert!(is_blocked(&reader));
+
+    exec(&mut writer, "COMMIT");
+    let (_, result) = reader.join().unwrap();
+    match result.unwrap() {
+        QueryResult::Rows { rows, .. } => assert_eq!(rows[0].values[0].to_string(), "11"),
+        _ => panic!("SELECT returned no rows"),
+    }
+}
+
+#[test]
+fn writers_of_different_rows_do_not_wait() {
+    let (_file, mut first) = setup("writers-disjoint");
+    // Index scans: neither statement reads the other's row
+    exec(&mut first, "CREATE INDEX t_id ON t (id)");
+    exec(&mut first, "BEGIN; UPDATE t SET v = 11 WHERE id = 1;");
+
+    let second = spawn(first.connect(), "UPDATE t SET v = 21 WHERE id = 2");
+    let (mut second, result) = second.join().unwrap();
+    assert!(result.is_ok());
+
+    // The same row still waits
+    let third = spawn(second.connect(), "UPDATE t SET v = 12 WHERE id = 1");
+    assert!(is_blocked(&third));
+
+    exec(&mut first, "ROLLBACK");
+    let (_, result) = third.join().unwrap();
+    assert!(result.is_ok());
+    assert_eq!(query_int(&mut second, "SELECT v FROM t WHERE id = 1"), 12);
+    assert_eq!(query_int(&mut second, "SELECT v FROM t WHERE id = 2"), 21);
+}
+
+#[test]
+fn shared_lock_upgrade_waits_for_other_readers() {
+    let (_file, mut first) = setup("upgrade");
+    let mut second = first.connect();
+    exec(&mut first, "BEGIN; SELECT v FROM t WHERE id = 1;");
+    exec(&mut second, "BEGIN; SELECT v FROM t WHERE id = 1;");
+
+    // Shared -> exclusive, the other reader still holds its shared lock
+    let update = spawn(first, "UPDATE t SET v = 11 WHERE id = 1");
+    assert!(is_blocked(&update));
+
+    exec(&mut second, "COMMIT");
+    let (mut first, result) = update.join().unwrap();
+    assert!(result.is_ok());
+    exec(&mut first, "COMMIT");
+    assert_eq!(query_int(&mut second, "SELECT v FROM t WHERE id = 1"), 11);
+}
+
+#[test]
+fn deadlock_victim_is_rolled_back() {
+    let (_file, mut first) = setup("deadlock");
+    let mut second = first.connect();
+    exec(&mut first, "BEGIN; SELECT v FROM t WHERE id = 1;");
+    exec(&mut second, "BEGIN; SELECT v FROM t WHERE id = 1;");
+
+    // Each one waits for the other's shared lock to upgrade:
+    // the younger transaction is the victim
+    let first_update = spawn(first, "UPDATE t SET v = 11 WHERE id = 1");
+    assert!(is_blocked(&first_update));
+    let second_update = spawn(second, "UPDATE t SET v = 12 WHERE id = 1");
+
+    let (mut second, result) = second_update.join().unwrap();
+    assert!(matches!(result, Err(e) if e.contains("deadlock")));
+    // Rolled back as a whole, its locks are gone
+    assert!(run(&mut second, "COMMIT").is_err());
+
+    let (mut first, result) = first_update.join().unwrap();
+    assert!(result.is_ok());
+    exec(&mut first, "COMMIT");
+    assert_eq!(query_int(&mut second, "SELECT v FROM t WHERE id = 1"), 11);
+}
@@ -0,0 +1,187 @@
+// Snapshot isolation between sessions of the same database
+mod common;
+
+use std::sync::Arc;
+
+use common::{TempDb, exec, is_blocked, query_int, run, spawn};
+use mysqlite::{
+    acid::{
+        log_record::SYSTEM_TXN,
+        mvcc::Concurrency,
+        transaction::{Dropped, TransactionManager},
+        wal::Wal,
+    },
+    backend::{cache::Cache, pager::Pager},
+    database::Database,
+    indexing::table_heap::TableHeap,
+};
+
+fn setup(name: &str) -> (TempDb, Database) {
+    let file = TempDb::new(name);
+    let mut db = file.open();
+    db.set_concurrency(Concurrency::Mvcc).unwrap();
+    exec(
+        &mut db,
+        "CREATE TABLE t (id INTEGER, v INTEGER); INSERT INTO t VALUES (1, 10), (2, 20);",
+    );
+    (file, db)
+}
+
+#[test]
+fn readers_do_not_wait_for_writers() {
+    let (_file, mut writer) = setup("readers");
+    let mut reader = writer.connect();
+    exec(&mut writer, "BEGIN; UPDATE t SET v = 11 WHERE id = 1;");
+
+    assert_eq!(query_int(&mut reader, "SELECT v FROM t WHERE id = 1"), 10);
+    exec(&mut writer, "COMMIT");
+    assert_eq!(query_int(&mut reader, "SELECT v FROM t WHERE id = 1"), 11);
+}
+
+#[test]
+fn first_updater_wins() {
+    let (_file, mut first) = setup("lost-update");
+    let mut second = first.connect();
+    exec(&mut first, "BEGIN; SELECT v FROM t WHERE id = 1;");
+    exec(&mut second, "BEGIN; SELECT v FROM t WHERE id = 1;");
+
+    exec(&mut first, "UPDATE t SET v = 11 WHERE id = 1");
+    let update = spawn(second, "UPDATE t SET v = 12 WHERE id = 1");
+    assert!(is_blocked(&update));
+
+    // The second update would overwrite a change it never saw
+    exec(&mut first, "COMMIT");
+    let (mut second, result) = update.join().unwrap();
+    assert!(matches!(result, Err(e) if e.contains("changed the same row")));
+    assert!(run(&mut second, "COMMIT").is_err());
+    assert_eq!(query_int(&mut second, "SELECT v FROM t WHERE id = 1"), 11);
+}
+
+#[test]
+fn write_skew_fails_validation() {
+    let (_file, mut first) = setup("write-skew");
+    let mut second = first.connect();
+
+    // Each one keeps the total at 30 or more, given what it read
+    exec(&mut first, "BEGIN; SELECT SUM(v) FROM t;");
+    exec(&mut second, "BEGIN; SELECT SUM(v) FROM t;");
+    exec(&mut first, "UPDATE t SET v = 0 WHERE id = 1; COMMIT;");
+    exec(&mut second, "UPDATE t SET v = 0 WHERE id = 2");
+
+    // Row 1 changed under the second one's snapshot
+    let result = run(&mut second, "COMMIT");
+    assert!(matches!(result, Err(e) if e.contains("changed a row it read")));
+    assert_eq!(query_int(&mut second, "SELECT SUM(v) FROM t"), 20);
+}
+
+#[test]
+fn old_versions_stay_while_a_snapshot_reads_them() {
+    let (_file, mut reader) = setup("gc-visibility");
+    let mut writer = reader.connect();
+    exec(&mut writer, "CREATE INDEX t_v ON t (v)");
+    exec(&mut reader, "BEGIN; SELECT v FROM t WHERE id = 1;");
+
+    // Garbage is collected after each statement of the writer
+    for v in 11..20 {
+        exec(&mut writer, &format!("UPDATE t SET v = {} WHERE id = 1", v));
+    }
+    exec(&mut writer, "DELETE FROM t WHERE id = 2");
+
+    assert_eq!(query_int(&mut reader, "SELECT v FROM t WHERE id = 1"), 10);
+    assert_eq!(query_int(&mut reader, "SELECT id FROM t WHERE v = 10"), 1);
+    assert_eq!(query_int(&mut reader, "SELECT COUNT(*) FROM t"), 2);
+    exec(&mut reader, "COMMIT");
+
+    assert_eq!(query_int(&mut reader, "SELECT v FROM t WHERE id = 1"), 19);
+    assert_eq!(
+        query_int(&mut reader, "SELECT COUNT(*) FROM t WHERE v = 10"),
+        0
+    );
+    assert_eq!(query_int(&mut reader, "SELECT COUNT(*) FROM t"), 1);
+}
+
+#[test]
+fn garbage_is_pruned_once_no_snapshot_sees_it() {
+    let file = TempDb::new("gc-prune");
+    let wal = Arc::new(Wal::open(&format!("{}-wal", file.path)).unwrap());
+    let cache = Arc::new(Cache::new(Pager::new(&file.path).unwrap(), 16, wal.clone()));
+    let transactions = TransactionManager::new(cache.clone(), wal, Concurrency::Mvcc);
+    let first_page_id = TableHeap::create(cache.clone(), SYSTEM_TXN)
+        .unwrap()
+        .first_page_id();
+    let heap = |txn| TableHeap::new(cache.clone(), first_page_id, SYSTEM_TXN).in_transaction(txn);
+
+    let txn = transactions.begin().unwrap();
+    let rid = heap(txn.clone()).insert(b"old").unwrap();
+    transactions.commit(&txn).unwrap();
+
+    let reader = transactions.begin().unwrap();
+    let writer = transactions.begin().unwrap();
+    heap(writer.clone()).update(rid.0, rid.1, b"new").unwrap();
+    transactions.commit(&writer).unwrap();
+
+    // The reader still sees the old version
+    assert!(transactions.take_garbage().is_empty());
+    let collector = transactions.begin().unwrap();
+    collector.start_writing().unwrap();
+    let pruner = TableHeap::new(cache.clone(), first_page_id, collector.id());
+    let pruned = pruner.prune(rid.0, rid.1, transactions.horizon()).unwrap();
+    assert!(pruned.removed.is_empty());
+    transactions.commit(&collector).unwrap();
+    assert_eq!(
+        heap(reader.clone()).get_tuple(rid.0, rid.1).unwrap(),
+        b"old"
+    );
+    transactions.commit(&reader).unwrap();
+
+    assert_eq!(transactions.take_garbage(), vec![(first_page_id, rid)]);
+    let collector = transactions.begin().unwrap();
+    collector.start_writing().unwrap();
+    let pruner = TableHeap::new(cache.clone(), first_page_id, collector.id());
+    let pruned = pruner.prune(rid.0, rid.1, transactions.horizon()).unwrap();
+    transactions.commit(&collector).unwrap();
+    assert_eq!(pruned.removed, vec![b"old".to_vec()]);
+    assert_eq!(pruned.kept, vec![b"new".to_vec()]);
+}
+
+#[test]
+fn dropped_pages_stay_while_a_snapshot_reads_them() {
+    let file = TempDb::new("drop-visibility");
+    let wal = Arc::new(Wal::open(&format!("{}-wal", file.path)).unwrap());
+    let cache = Arc::new(Cache::new(Pager::new(&file.path).unwrap(), 16, wal.clone()));
+    let transactions = TransactionManager::new(cache.clone(), wal, Concurrency::Mvcc);
+    let heap = TableHeap::create(cache.clone(), SYSTEM_TXN).unwrap();
+    let rid = heap.insert(b"row").unwrap();
+
+    let reader = transactions.begin().unwrap();
+    let dropper = transactions.begin().unwrap();
+    dropper.start_writing().unwrap();
+    dropper.drop_on_commit(Dropped::Table(heap.first_page_id()));
+    transactions.commit(&dropper).unwrap();
+
+    // A new table would get the dropped page if it were free
+    transactions.free_dropped();
+    let other = TableHeap::create(cache.clone(), SYSTEM_TXN).unwrap();
+    assert_ne!(other.first_page_id(), heap.first_page_id());
+    assert_eq!(heap.get_tuple(rid.0, rid.1).unwrap(), b"row");
+
+    transactions.commit(&reader).unwrap();
+    transactions.free_dropped();
+    let other = TableHeap::create(cache.clone(), SYSTEM_TXN).unwrap();
+    assert_eq!(other.first_page_id(), heap.first_page_id());
+}
+
+#[test]
+fn modes_do_not_switch_while_another_session_is_in_a_transaction() {
+    let (file, mut first) = setup("switch");
+    let mut second = first.connect();
+    exec(&mut second, "BEGIN; SELECT v FROM t WHERE id = 1;");
+
+    assert!(first.set_concurrency(Concurrency::Locking).is_err());
+    assert_eq!(first.concurrency(), Concurrency::Mvcc);
+
+    exec(&mut second, "COMMIT");
+    first.set_concurrency(Concurrency::Locking).unwrap();
+    drop((first, second));
+    assert_eq!(file.open().concurrency(), Concurrency::Locking);
+}